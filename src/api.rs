@@ -1,32 +1,74 @@
-use bson::doc;
-use futures::TryStreamExt;
-
 use bson::oid::ObjectId;
-use mongodb::Database;
+use chrono::{DateTime, Utc};
+use lettre::Address;
 use poem::web::Data;
-use poem_openapi::param::Path;
+use poem_openapi::param::{Path, Query};
 use poem_openapi::{payload::Json, OpenApi};
 
 use poem_openapi::Tags;
 
-use crate::models::{Check, CheckHistory, Error, HTTPMethod, NewCheck, UpdateCheck};
+use crate::auth::ApiKeyAuth;
+use crate::badges;
+use crate::heartbeats;
+use crate::models::{
+    validate_headers, ApiKey, ApiKeyScope, BadgeToken, Check, CheckPage, CheckSortField,
+    CheckState, DeliveryStatus, Error, Frequency, FrequencyKind, HTTPMethod, HeartbeatSignal,
+    HistoryPage, IncidentStatus, IncidentUpdate, MaintenanceWindow, MintedApiKey, NewApiKey,
+    NewCheck, NewIncidentUpdate, NewMaintenanceWindow, NewNotificationChannel, NewProject,
+    NewStatusPage, NewUser, NotificationChannel, Project, ProjectQuota, Resolution, SortOrder,
+    StatsWindow, Status, StatusPage, UpdateCheck, UpdateMaintenanceWindow,
+    UpdateNotificationChannel, UpdateProject, UpdateStatusPage, User,
+};
+use crate::monitor::Probe;
+use crate::pagination::{Cursor, Page, PageRequest};
+use crate::scheduler::Scheduler;
+use crate::stats;
+use crate::status_page::PageCache;
+use crate::store::{
+    self, ApiKeyFields, ChannelFields, CheckFields, CheckFilter, DeliveryFilter, HistoryFilter,
+    IncidentFilter, MaintenanceWindowFields, ProjectFields, StatusPageFields, Storage, Store,
+};
+
+/// Largest page any listing returns
+const MAX_PAGE_SIZE: u32 = 1000;
 
 #[derive(Tags)]
 pub(crate) enum APITags {
     Check,
     History,
+    Stats,
+    Incident,
+    Heartbeat,
+    Maintenance,
+    Channel,
+    Delivery,
+    ApiKey,
+    User,
+    Project,
+    StatusPage,
+    Badge,
 }
 
 pub(crate) struct MonitorAPI;
 
 mod responses {
-    use crate::models::{Check, CheckHistory, Error};
+    // Responses are built once per request, boxing the payloads would not buy anything
+    #![allow(clippy::large_enum_variant)]
+
+    use crate::models::{
+        ApiKey, BadgeToken, Check, CheckPage, CheckStats, Error, HeartbeatPing, HistoryPage,
+        Incident, MaintenanceWindow, MintedApiKey, NotificationChannel, Project, StatusPage, User,
+        WebhookDelivery,
+    };
     use poem_openapi::{payload::Json, ApiResponse};
 
     #[derive(ApiResponse)]
     pub(crate) enum ReadChecksResponse {
-        #[oai(status = 201)]
-        Success(Json<Vec<Check>>),
+        #[oai(status = 200)]
+        Success(Json<CheckPage>),
+
+        #[oai(status = 400)]
+        BadRequest(Json<Error>),
 
         #[oai(status = 500)]
         InternalServerError(Json<Error>),
@@ -51,6 +93,12 @@ mod responses {
 
         #[oai(status = 400)]
         BadRequest(Json<Error>),
+
+        #[oai(status = 403)]
+        Forbidden(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
     }
 
     #[derive(ApiResponse)]
@@ -64,6 +112,9 @@ mod responses {
         #[oai(status = 400)]
         BadRequest(Json<Error>),
 
+        #[oai(status = 403)]
+        Forbidden(Json<Error>),
+
         #[oai(status = 500)]
         InternalServerError(Json<Error>),
     }
@@ -82,15 +133,123 @@ mod responses {
 
     #[derive(ApiResponse)]
     pub(crate) enum ReadHistoryResponse {
+        #[oai(status = 200)]
+        Success(Json<HistoryPage>),
+
+        #[oai(status = 400)]
+        BadRequest(Json<Error>),
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum ReadIncidentsResponse {
+        #[oai(status = 200)]
+        Success(Json<Vec<Incident>>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum ReadIncidentResponse {
+        #[oai(status = 200)]
+        Success(Json<Incident>),
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum ReadDeliveriesResponse {
+        #[oai(status = 200)]
+        Success(Json<Vec<WebhookDelivery>>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum ReadDeliveryResponse {
+        #[oai(status = 200)]
+        Success(Json<WebhookDelivery>),
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum ReadPingsResponse {
+        #[oai(status = 200)]
+        Success(Json<Vec<HeartbeatPing>>),
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum ReadMaintenanceWindowsResponse {
+        #[oai(status = 200)]
+        Success(Json<Vec<MaintenanceWindow>>),
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum ReadMaintenanceWindowResponse {
+        #[oai(status = 200)]
+        Success(Json<MaintenanceWindow>),
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum CreateMaintenanceWindowResponse {
         #[oai(status = 201)]
-        Success(Json<Vec<CheckHistory>>),
+        Success(Json<MaintenanceWindow>),
+
+        #[oai(status = 400)]
+        BadRequest(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum UpdateMaintenanceWindowResponse {
+        #[oai(status = 204)]
+        Success,
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 400)]
+        BadRequest(Json<Error>),
 
         #[oai(status = 500)]
         InternalServerError(Json<Error>),
     }
 
     #[derive(ApiResponse)]
-    pub(crate) enum DeleteHistoryResponse {
+    pub(crate) enum DeleteMaintenanceWindowResponse {
         #[oai(status = 204)]
         Success,
 
@@ -100,209 +259,901 @@ mod responses {
         #[oai(status = 500)]
         InternalServerError(Json<Error>),
     }
-}
 
-#[OpenApi]
-impl MonitorAPI {
-    /// Read all checks
-    #[oai(method = "get", path = "/", tag = APITags::Check)]
-    async fn read_checks(&self, Data(database): Data<&Database>) -> responses::ReadChecksResponse {
-        let collection = database.collection::<Check>("checks");
-        let checks = collection.find(doc! {}).await;
-        if checks.is_err() {
-            let err = unsafe { checks.unwrap_err_unchecked() };
-            return responses::ReadChecksResponse::InternalServerError(Json(
-                Error::internal_server_error(err.to_string()),
-            ));
-        }
+    #[derive(ApiResponse)]
+    pub(crate) enum ReadChannelsResponse {
+        #[oai(status = 200)]
+        Success(Json<Vec<NotificationChannel>>),
 
-        responses::ReadChecksResponse::Success(Json(checks.unwrap().try_collect().await.unwrap()))
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
     }
 
-    /// Read one check
-    #[oai(method = "get", path = "/:check_id", tag = APITags::Check)]
-    async fn read_check(
-        &self,
-        Data(database): Data<&Database>,
-        Path(check_id): Path<ObjectId>,
-    ) -> responses::ReadCheckResponse {
-        let collection = database.collection::<Check>("checks");
-        let check = collection.find_one(doc! {"_id": check_id}).await;
-        if check.is_err() {
-            let err = unsafe { check.unwrap_err_unchecked() };
-            return responses::ReadCheckResponse::InternalServerError(Json(
-                Error::internal_server_error(err.to_string()),
-            ));
-        }
+    #[derive(ApiResponse)]
+    pub(crate) enum ReadChannelResponse {
+        #[oai(status = 200)]
+        Success(Json<NotificationChannel>),
 
-        if let Some(check) = check.unwrap() {
-            return responses::ReadCheckResponse::Success(Json(check));
-        } else {
-            return responses::ReadCheckResponse::NotFound(Json(Error::not_found(format!(
-                "Check not found with id '{check_id}'"
-            ))));
-        }
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
     }
 
-    /// Create new check
-    #[oai(method = "post", path = "/", tag = APITags::Check)]
-    async fn create_check(
-        &self,
-        Data(database): Data<&Database>,
-        Json(new_check): Json<NewCheck>,
-    ) -> responses::CreateCheckResponse {
-        let check = Check::from_new(new_check);
+    #[derive(ApiResponse)]
+    pub(crate) enum CreateChannelResponse {
+        #[oai(status = 201)]
+        Success(Json<NotificationChannel>),
 
-        if check.expected_body.is_some() && check.method == HTTPMethod::HEAD {
-            return responses::CreateCheckResponse::BadRequest(Json(Error::bad_request(
-                "Expected body parameter is only allowed with GET requests.".to_string(),
-            )));
-        }
+        #[oai(status = 400)]
+        BadRequest(Json<Error>),
+    }
 
-        let collection = database.collection::<Check>("checks");
-        let result = collection.insert_one(check.clone()).await;
-        result.map_or_else(
-            |e| responses::CreateCheckResponse::BadRequest(Json(Error::bad_request(e.to_string()))),
-            |_| responses::CreateCheckResponse::Success(Json(check)),
-        )
+    #[derive(ApiResponse)]
+    pub(crate) enum UpdateChannelResponse {
+        #[oai(status = 204)]
+        Success,
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 400)]
+        BadRequest(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
     }
 
-    /// Update check
-    #[oai(method = "put", path = "/:check_id", tag = APITags::Check)]
-    async fn update_check(
-        &self,
-        Data(database): Data<&Database>,
-        Path(check_id): Path<ObjectId>,
-        Json(update): Json<UpdateCheck>,
-    ) -> responses::UpdateCheckResponse {
-        let mut update_doc = doc! {
-            "updated_at": chrono::Utc::now()
-                .to_string(),
-        };
-        if let Some(frequency) = update.frequency {
-            update_doc.insert("frequency", frequency.to_string());
-        }
+    #[derive(ApiResponse)]
+    pub(crate) enum DeleteChannelResponse {
+        #[oai(status = 204)]
+        Success,
 
-        if let Some(url) = update.url {
-            update_doc.insert("url", url);
-        }
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
 
-        if let Some(method) = update.method {
-            update_doc.insert("method", method.to_string());
-        }
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
 
-        if let Some(expected_body) = update.expected_body {
-            if let Ok(bson_expected_body) = bson::to_bson(&expected_body) {
-                update_doc.insert("expected_body", bson_expected_body);
-            } else {
-                return responses::UpdateCheckResponse::BadRequest(Json(Error::bad_request(
-                    "Failed to serialize expected_body".to_string(),
-                )));
-            }
-        }
+    #[derive(ApiResponse)]
+    pub(crate) enum ReadApiKeysResponse {
+        #[oai(status = 200)]
+        Success(Json<Vec<ApiKey>>),
 
-        if let Some(hook) = update.hook {
-            update_doc.insert("hook", bson::to_bson(&hook).unwrap());
-        }
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
 
-        let mut filter = doc! {"_id": check_id};
-        if update_doc.contains_key("expected_body") {
-            if let Some(updating_method) = update_doc.get("method") {
-                if updating_method.as_str().unwrap() == "HEAD" {
-                    // If we are updating both the method and the expected body,
-                    // ensure that the method is GET, otherwise the ping will fail
-                    return responses::UpdateCheckResponse::BadRequest(Json(Error::bad_request(
-                        "Expected body parameter is only allowed with GET requests.".to_string(),
-                    )));
-                }
-            } else {
-                // If we are providing an expected body, ensure that the method
-                // used to ping the service is GET, otherwise the ping will fail
-                filter.insert("method", HTTPMethod::GET.to_string());
-            }
-        }
+    #[derive(ApiResponse)]
+    pub(crate) enum CreateApiKeyResponse {
+        #[oai(status = 201)]
+        Success(Json<MintedApiKey>),
 
-        let collection = database.collection::<Check>("checks");
-        let update = collection
-            .update_one(
-                filter,
-                doc! {
-                    "$set":  update_doc
-                },
-            )
-            .await;
+        #[oai(status = 400)]
+        BadRequest(Json<Error>),
 
-        if let Ok(update) = update {
-            if update.matched_count > 0 {
-                responses::UpdateCheckResponse::Success
-            } else {
-                responses::UpdateCheckResponse::NotFound(Json(Error::not_found(format!(
-                    "Check not found with id '{check_id}'"
-                ))))
-            }
-        } else {
-            let err = unsafe { update.unwrap_err_unchecked() };
-            responses::UpdateCheckResponse::InternalServerError(Json(Error::internal_server_error(
-                err.to_string(),
-            )))
-        }
+        #[oai(status = 403)]
+        Forbidden(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
     }
 
-    /// Delete check
-    #[oai(method = "delete", path = "/:check_id", tag = APITags::History)]
-    async fn delete_check(
-        &self,
-        Data(database): Data<&Database>,
-        Path(check_id): Path<ObjectId>,
-    ) -> responses::DeleteCheckResponse {
-        let collection = database.collection::<Check>("checks");
-        let delete = collection.delete_one(doc! {"_id": check_id}).await;
-        if let Ok(delete) = delete {
-            if delete.deleted_count > 0 {
-                responses::DeleteCheckResponse::Success
-            } else {
-                responses::DeleteCheckResponse::NotFound(Json(Error::not_found(format!(
-                    "Check not found with id '{check_id}'"
-                ))))
-            }
-        } else {
-            let err = unsafe { delete.unwrap_err_unchecked() };
-            responses::DeleteCheckResponse::InternalServerError(Json(Error::internal_server_error(
-                err.to_string(),
-            )))
-        }
+    #[derive(ApiResponse)]
+    pub(crate) enum RevokeApiKeyResponse {
+        #[oai(status = 204)]
+        Success,
+
+        #[oai(status = 403)]
+        Forbidden(Json<Error>),
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
     }
 
-    /// Read check history
-    #[oai(method = "get", path = "/:check_id/history", tag = APITags::History)]
-    async fn read_history(
-        &self,
-        Data(database): Data<&Database>,
-        Path(check_id): Path<ObjectId>,
-    ) -> responses::ReadHistoryResponse {
-        let collection = database.collection::<CheckHistory>("checks_history");
-        let checks = collection.find(doc! {"check_id": check_id}).await;
-        if checks.is_err() {
-            let err = unsafe { checks.unwrap_err_unchecked() };
-            return responses::ReadHistoryResponse::InternalServerError(Json(
-                Error::internal_server_error(err.to_string()),
-            ));
-        }
+    #[derive(ApiResponse)]
+    pub(crate) enum ReadUsersResponse {
+        #[oai(status = 200)]
+        Success(Json<Vec<User>>),
 
-        responses::ReadHistoryResponse::Success(Json(checks.unwrap().try_collect().await.unwrap()))
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
     }
 
-    /// Delete check history
-    #[oai(method = "delete", path = "/:check_id/history", tag = APITags::History)]
-    async fn delete_history(
-        &self,
-        Data(database): Data<&Database>,
-        Path(check_id): Path<ObjectId>,
-    ) -> responses::DeleteHistoryResponse {
-        let collection = database.collection::<CheckHistory>("checks_history");
-        let delete = collection.delete_one(doc! {"check_id": check_id}).await;
-        if let Ok(delete) = delete {
-            if delete.deleted_count > 0 {
+    #[derive(ApiResponse)]
+    pub(crate) enum CreateUserResponse {
+        #[oai(status = 201)]
+        Success(Json<User>),
+
+        #[oai(status = 400)]
+        BadRequest(Json<Error>),
+
+        #[oai(status = 403)]
+        Forbidden(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum DeleteUserResponse {
+        #[oai(status = 204)]
+        Success,
+
+        #[oai(status = 403)]
+        Forbidden(Json<Error>),
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum ReadProjectsResponse {
+        #[oai(status = 200)]
+        Success(Json<Vec<Project>>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum ReadProjectResponse {
+        #[oai(status = 200)]
+        Success(Json<Project>),
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum CreateProjectResponse {
+        #[oai(status = 201)]
+        Success(Json<Project>),
+
+        #[oai(status = 400)]
+        BadRequest(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum UpdateProjectResponse {
+        #[oai(status = 204)]
+        Success,
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 400)]
+        BadRequest(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum CreateIncidentUpdateResponse {
+        #[oai(status = 201)]
+        Success(Json<Incident>),
+
+        #[oai(status = 400)]
+        BadRequest(Json<Error>),
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum ReadStatusPagesResponse {
+        #[oai(status = 200)]
+        Success(Json<Vec<StatusPage>>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum ReadStatusPageResponse {
+        #[oai(status = 200)]
+        Success(Json<StatusPage>),
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum CreateStatusPageResponse {
+        #[oai(status = 201)]
+        Success(Json<StatusPage>),
+
+        #[oai(status = 400)]
+        BadRequest(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum UpdateStatusPageResponse {
+        #[oai(status = 204)]
+        Success,
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 400)]
+        BadRequest(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum DeleteStatusPageResponse {
+        #[oai(status = 204)]
+        Success,
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum CreateBadgeTokenResponse {
+        #[oai(status = 201)]
+        Success(Json<BadgeToken>),
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum DeleteBadgeTokenResponse {
+        #[oai(status = 204)]
+        Success,
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum ReadStatsResponse {
+        #[oai(status = 200)]
+        Success(Json<CheckStats>),
+
+        #[oai(status = 400)]
+        BadRequest(Json<Error>),
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+
+    #[derive(ApiResponse)]
+    pub(crate) enum DeleteHistoryResponse {
+        #[oai(status = 204)]
+        Success,
+
+        #[oai(status = 404)]
+        NotFound(Json<Error>),
+
+        #[oai(status = 500)]
+        InternalServerError(Json<Error>),
+    }
+}
+
+#[OpenApi]
+impl MonitorAPI {
+    /// Read checks one page at a time, most recently created first by default
+    #[oai(method = "get", path = "/", tag = APITags::Check)]
+    #[allow(clippy::too_many_arguments)]
+    async fn read_checks(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Query(method): Query<Option<HTTPMethod>>,
+        Query(frequency): Query<Option<FrequencyKind>>,
+        Query(current_state): Query<Option<CheckState>>,
+        /// Only checks having every one of these tags
+        Query(tags): Query<Option<Vec<String>>>,
+        /// Case insensitive text searched in the URL and the name
+        Query(search): Query<Option<String>>,
+        Query(sort): Query<Option<CheckSortField>>,
+        Query(order): Query<Option<SortOrder>>,
+        /// Maximum number of checks returned, defaults to 100 and is capped at 1000
+        Query(limit): Query<Option<u32>>,
+        /// `next_cursor` of the previous page
+        Query(cursor): Query<Option<String>>,
+    ) -> responses::ReadChecksResponse {
+        let sort = sort.unwrap_or_default();
+        let order = order.unwrap_or_default();
+        let limit = limit.unwrap_or(100).clamp(1, MAX_PAGE_SIZE) as usize;
+
+        let cursor = match cursor.as_deref().map(Cursor::decode).transpose() {
+            Ok(cursor) => cursor,
+            Err(err) => {
+                return responses::ReadChecksResponse::BadRequest(Json(Error::bad_request(err)))
+            }
+        };
+        let filter = CheckFilter {
+            project_id: Some(caller.project_id),
+            method,
+            frequency,
+            current_state,
+            tags: tags.unwrap_or_default(),
+            search: search.filter(|search| !search.is_empty()),
+        };
+        let request = PageRequest {
+            order,
+            limit,
+            cursor,
+        };
+
+        match store.find_checks(&filter, &sort, &request).await {
+            Ok(Page { items, next_cursor }) => {
+                responses::ReadChecksResponse::Success(Json(CheckPage { items, next_cursor }))
+            }
+            Err(err) => responses::ReadChecksResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Read one check
+    #[oai(method = "get", path = "/:check_id", tag = APITags::Check)]
+    async fn read_check(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(check_id): Path<ObjectId>,
+    ) -> responses::ReadCheckResponse {
+        let check = owned_check(store, caller, check_id).await;
+        if check.is_err() {
+            let err = unsafe { check.unwrap_err_unchecked() };
+            return responses::ReadCheckResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            ));
+        }
+
+        if let Some(check) = check.unwrap() {
+            responses::ReadCheckResponse::Success(Json(check))
+        } else {
+            responses::ReadCheckResponse::NotFound(Json(Error::not_found(format!(
+                "Check not found with id '{check_id}'"
+            ))))
+        }
+    }
+
+    /// Create new check
+    #[oai(method = "post", path = "/", tag = APITags::Check)]
+    async fn create_check(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Data(scheduler): Data<&Scheduler>,
+        Json(new_check): Json<NewCheck>,
+    ) -> responses::CreateCheckResponse {
+        let mut check = Check::from_new(new_check, caller.project_id);
+        check.ensure_heartbeat();
+
+        if let Err(err) = check.kind.validate(&check) {
+            return responses::CreateCheckResponse::BadRequest(Json(Error::bad_request(err)));
+        }
+
+        if let Err(err) = check.frequency.validate() {
+            return responses::CreateCheckResponse::BadRequest(Json(Error::bad_request(err)));
+        }
+
+        if let Err(err) = heartbeats::validate_grace(check.grace_secs) {
+            return responses::CreateCheckResponse::BadRequest(Json(Error::bad_request(err)));
+        }
+
+        if check.timeout_ms == Some(0) {
+            return responses::CreateCheckResponse::BadRequest(Json(Error::bad_request(
+                "Timeout must be greater than zero.".to_string(),
+            )));
+        }
+
+        if let Some(Err(err)) = check.retry.as_ref().map(|retry| retry.validate()) {
+            return responses::CreateCheckResponse::BadRequest(Json(Error::bad_request(err)));
+        }
+
+        if let Some(Err(err)) = check.headers.as_ref().map(validate_headers) {
+            return responses::CreateCheckResponse::BadRequest(Json(Error::bad_request(err)));
+        }
+
+        if check.failure_threshold == Some(0) || check.recovery_threshold == Some(0) {
+            return responses::CreateCheckResponse::BadRequest(Json(Error::bad_request(
+                "Thresholds must be greater than zero.".to_string(),
+            )));
+        }
+
+        if let Some(Err(err)) = check.sla_target.map(validate_sla_target) {
+            return responses::CreateCheckResponse::BadRequest(Json(Error::bad_request(err)));
+        }
+
+        if let Some(Err(err)) = check
+            .assertions
+            .iter()
+            .map(|a| a.validate())
+            .find(Result::is_err)
+        {
+            return responses::CreateCheckResponse::BadRequest(Json(Error::bad_request(err)));
+        }
+
+        if (check.expected_body.is_some() || check.body.is_some())
+            && check.method == HTTPMethod::HEAD
+        {
+            return responses::CreateCheckResponse::BadRequest(Json(Error::bad_request(
+                "Body parameters are not allowed with HEAD requests.".to_string(),
+            )));
+        }
+
+        if let Err(err) = ensure_channels_exist(store, caller, &check.channels).await {
+            return responses::CreateCheckResponse::BadRequest(Json(Error::bad_request(err)));
+        }
+
+        let quota = match project_quota(store, caller).await {
+            Ok(quota) => quota,
+            Err(err) => {
+                return responses::CreateCheckResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        };
+        let outcome = match store.count_checks(caller.project_id).await {
+            Ok(count) => quota
+                .validate_count(count)
+                .and_then(|_| quota.validate_check(&check)),
+            Err(err) => {
+                return responses::CreateCheckResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        };
+        if let Err(err) = outcome {
+            return responses::CreateCheckResponse::Forbidden(Json(Error::forbidden(err)));
+        }
+
+        let result = store.insert_check(&check).await;
+        result.map_or_else(
+            |e| responses::CreateCheckResponse::BadRequest(Json(Error::bad_request(e.to_string()))),
+            |_| {
+                scheduler.schedule(check.clone());
+                responses::CreateCheckResponse::Success(Json(check))
+            },
+        )
+    }
+
+    /// Update check
+    #[oai(method = "put", path = "/:check_id", tag = APITags::Check)]
+    async fn update_check(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Data(scheduler): Data<&Scheduler>,
+        Path(check_id): Path<ObjectId>,
+        Json(update): Json<UpdateCheck>,
+    ) -> responses::UpdateCheckResponse {
+        let current = match owned_check(store, caller, check_id).await {
+            Ok(Some(current)) => current,
+            Ok(None) => {
+                return responses::UpdateCheckResponse::NotFound(Json(Error::not_found(format!(
+                    "Check not found with id '{check_id}'"
+                ))))
+            }
+            Err(err) => {
+                return responses::UpdateCheckResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        };
+
+        if let Some(Err(err)) = update.frequency.as_ref().map(Frequency::validate) {
+            return responses::UpdateCheckResponse::BadRequest(Json(Error::bad_request(err)));
+        }
+
+        if let Some(Err(err)) = update.grace_secs.map(heartbeats::validate_grace) {
+            return responses::UpdateCheckResponse::BadRequest(Json(Error::bad_request(err)));
+        }
+
+        if let Some(Some(Err(err))) = update
+            .headers
+            .as_ref()
+            .map(|headers| headers.as_ref().map(validate_headers))
+        {
+            return responses::UpdateCheckResponse::BadRequest(Json(Error::bad_request(err)));
+        }
+
+        if let Some(ref assertions) = update.assertions {
+            if let Some(Err(err)) = assertions.iter().map(|a| a.validate()).find(Result::is_err) {
+                return responses::UpdateCheckResponse::BadRequest(Json(Error::bad_request(err)));
+            }
+        }
+
+        if update.failure_threshold == Some(Some(0)) || update.recovery_threshold == Some(Some(0)) {
+            return responses::UpdateCheckResponse::BadRequest(Json(Error::bad_request(
+                "Thresholds must be greater than zero.".to_string(),
+            )));
+        }
+
+        if let Some(Some(Err(err))) = update
+            .sla_target
+            .map(|sla_target| sla_target.map(validate_sla_target))
+        {
+            return responses::UpdateCheckResponse::BadRequest(Json(Error::bad_request(err)));
+        }
+
+        if let Some(ref channels) = update.channels {
+            if let Err(err) = ensure_channels_exist(store, caller, channels).await {
+                return responses::UpdateCheckResponse::BadRequest(Json(Error::bad_request(err)));
+            }
+        }
+
+        if update.timeout_ms == Some(Some(0)) {
+            return responses::UpdateCheckResponse::BadRequest(Json(Error::bad_request(
+                "Timeout must be greater than zero.".to_string(),
+            )));
+        }
+
+        if let Some(Some(Err(err))) = update
+            .retry
+            .as_ref()
+            .map(|retry| retry.as_ref().map(|retry| retry.validate()))
+        {
+            return responses::UpdateCheckResponse::BadRequest(Json(Error::bad_request(err)));
+        }
+
+        let mut fields = CheckFields {
+            kind: update.kind,
+            name: update.name,
+            tags: update.tags,
+            frequency: update.frequency,
+            url: update.url,
+            method: update.method,
+            headers: update.headers,
+            query: update.query,
+            body: update.body,
+            expected_body: update.expected_body,
+            dns: update.dns,
+            grace_secs: update.grace_secs,
+            assertions: update.assertions,
+            channels: update.channels,
+            timeout_ms: update.timeout_ms,
+            retry: update.retry,
+            failure_threshold: update.failure_threshold,
+            recovery_threshold: update.recovery_threshold,
+            sla_target: update.sla_target,
+            certificate_policy: update.certificate_policy,
+            certificate_expiry_days: update.certificate_expiry_days,
+            updated_at: Some(Utc::now()),
+            ..Default::default()
+        };
+
+        // What a probe accepts depends on the other fields, validate the check as it will be saved
+        if fields.changes_target() {
+            let mut check = match store::merge(&current, &fields) {
+                Ok(check) => check,
+                Err(err) => {
+                    return responses::UpdateCheckResponse::BadRequest(Json(Error::bad_request(
+                        err.to_string(),
+                    )))
+                }
+            };
+            // Turning into a heartbeat check issues a ping URL, turning into anything else drops it
+            let had_heartbeat = check.heartbeat.is_some();
+            check.ensure_heartbeat();
+            if check.heartbeat.is_some() != had_heartbeat {
+                fields.heartbeat = Some(check.heartbeat.clone());
+                fields.url = Some(check.url.clone());
+            }
+            if let Err(err) = check.kind.validate(&check) {
+                return responses::UpdateCheckResponse::BadRequest(Json(Error::bad_request(err)));
+            }
+            if (check.expected_body.is_some() || check.body.is_some())
+                && check.method == HTTPMethod::HEAD
+            {
+                // The ping would fail, whether the method or the body is being changed
+                return responses::UpdateCheckResponse::BadRequest(Json(Error::bad_request(
+                    "Body parameters are not allowed with HEAD requests.".to_string(),
+                )));
+            }
+        }
+
+        // How often the check runs changes with its frequency, and heartbeat checks do not run
+        if fields.frequency.is_some() || fields.kind.is_some() {
+            let outcome = match project_quota(store, caller).await {
+                Ok(quota) => store::merge(&current, &fields)
+                    .map_err(|err| err.to_string())
+                    .map(|check| quota.validate_check(&check)),
+                Err(err) => {
+                    return responses::UpdateCheckResponse::InternalServerError(Json(
+                        Error::internal_server_error(err.to_string()),
+                    ))
+                }
+            };
+            match outcome {
+                Ok(Ok(())) => (),
+                Ok(Err(err)) => {
+                    return responses::UpdateCheckResponse::Forbidden(Json(Error::forbidden(err)))
+                }
+                Err(err) => {
+                    return responses::UpdateCheckResponse::BadRequest(Json(Error::bad_request(
+                        err,
+                    )))
+                }
+            }
+        }
+
+        match store.update_check(check_id, &fields).await {
+            Ok(Some(check)) => {
+                scheduler.schedule(check);
+                responses::UpdateCheckResponse::Success
+            }
+            Ok(None) => responses::UpdateCheckResponse::NotFound(Json(Error::not_found(format!(
+                "Check not found with id '{check_id}'"
+            )))),
+            Err(err) => responses::UpdateCheckResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Delete check
+    #[oai(method = "delete", path = "/:check_id", tag = APITags::History)]
+    async fn delete_check(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Data(scheduler): Data<&Scheduler>,
+        Path(check_id): Path<ObjectId>,
+    ) -> responses::DeleteCheckResponse {
+        match owned_check(store, caller, check_id).await {
+            Ok(Some(_)) => (),
+            Ok(None) => {
+                return responses::DeleteCheckResponse::NotFound(Json(Error::not_found(format!(
+                    "Check not found with id '{check_id}'"
+                ))))
+            }
+            Err(err) => {
+                return responses::DeleteCheckResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        }
+
+        let delete = store.delete_check(check_id).await;
+        if let Ok(deleted) = delete {
+            if deleted {
+                scheduler.unschedule(check_id);
+                responses::DeleteCheckResponse::Success
+            } else {
+                responses::DeleteCheckResponse::NotFound(Json(Error::not_found(format!(
+                    "Check not found with id '{check_id}'"
+                ))))
+            }
+        } else {
+            let err = unsafe { delete.unwrap_err_unchecked() };
+            responses::DeleteCheckResponse::InternalServerError(Json(Error::internal_server_error(
+                err.to_string(),
+            )))
+        }
+    }
+
+    /// Read check history one page at a time, most recent first by default.
+    ///
+    /// Long time ranges are read from rollups instead of raw entries, unless a
+    /// resolution is asked for.
+    #[oai(method = "get", path = "/:check_id/history", tag = APITags::History)]
+    #[allow(clippy::too_many_arguments)]
+    async fn read_history(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(check_id): Path<ObjectId>,
+        /// Only entries created at or after this moment
+        Query(from): Query<Option<DateTime<Utc>>>,
+        /// Only entries created before this moment
+        Query(to): Query<Option<DateTime<Utc>>>,
+        /// Rollups match `Error` when any of their executions failed
+        Query(status): Query<Option<Status>>,
+        /// Defaults to raw entries, or to a resolution fitting the range when `from` is given
+        Query(resolution): Query<Option<Resolution>>,
+        /// Maximum number of entries returned, defaults to 100 and is capped at 1000
+        Query(limit): Query<Option<u32>>,
+        /// `next_cursor` of the previous page
+        Query(cursor): Query<Option<String>>,
+        Query(order): Query<Option<SortOrder>>,
+    ) -> responses::ReadHistoryResponse {
+        let order = order.unwrap_or_default();
+        let limit = limit.unwrap_or(100).clamp(1, MAX_PAGE_SIZE) as usize;
+        let resolution = resolution.unwrap_or_else(|| match from {
+            Some(from) => Resolution::for_span(to.unwrap_or_else(Utc::now) - from),
+            None => Resolution::Raw,
+        });
+        let cursor = match cursor.as_deref().map(Cursor::decode).transpose() {
+            Ok(cursor) => cursor,
+            Err(err) => {
+                return responses::ReadHistoryResponse::BadRequest(Json(Error::bad_request(err)))
+            }
+        };
+        if status == Some(Status::Maintenance) && resolution != Resolution::Raw {
+            return responses::ReadHistoryResponse::BadRequest(Json(Error::bad_request(
+                "Rollups do not tell maintenance apart, read it at the `Raw` resolution."
+                    .to_string(),
+            )));
+        }
+        match owned_check(store, caller, check_id).await {
+            Ok(Some(_)) => (),
+            Ok(None) => {
+                return responses::ReadHistoryResponse::NotFound(Json(Error::not_found(format!(
+                    "Check not found with id '{check_id}'"
+                ))))
+            }
+            Err(err) => {
+                return responses::ReadHistoryResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        }
+        let filter = HistoryFilter {
+            check_id,
+            // Rollups overlapping the start of the range are included
+            from: from.map(|from| resolution.floor(from)),
+            to,
+            status,
+        };
+        let request = PageRequest {
+            order,
+            limit,
+            cursor,
+        };
+
+        let page = if resolution == Resolution::Raw {
+            store
+                .find_history(&filter, &request)
+                .await
+                .map(|page| HistoryPage {
+                    resolution,
+                    items: page.items,
+                    rollups: vec![],
+                    next_cursor: page.next_cursor,
+                })
+        } else {
+            store
+                .find_rollups(&filter, resolution, &request)
+                .await
+                .map(|page| HistoryPage {
+                    resolution,
+                    items: vec![],
+                    rollups: page.items,
+                    next_cursor: page.next_cursor,
+                })
+        };
+        match page {
+            Ok(page) => responses::ReadHistoryResponse::Success(Json(page)),
+            Err(err) => responses::ReadHistoryResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Read check uptime, incidents and latency over a window, and how they compare to its SLA
+    #[oai(method = "get", path = "/:check_id/stats", tag = APITags::Stats)]
+    #[allow(clippy::too_many_arguments)]
+    async fn read_stats(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(check_id): Path<ObjectId>,
+        /// Defaults to the last 24 hours
+        Query(window): Query<Option<StatsWindow>>,
+        /// Start of a custom window
+        Query(from): Query<Option<DateTime<Utc>>>,
+        /// End of a custom window, defaults to now
+        Query(to): Query<Option<DateTime<Utc>>>,
+    ) -> responses::ReadStatsResponse {
+        let now = Utc::now();
+        let (from, to) = match window.unwrap_or_default().duration() {
+            Some(duration) => (now - duration, now),
+            None => match (from, to.unwrap_or(now)) {
+                (Some(from), to) if from < to => (from, to),
+                (Some(_), _) => {
+                    return responses::ReadStatsResponse::BadRequest(Json(Error::bad_request(
+                        "The window must start before it ends.".to_string(),
+                    )))
+                }
+                (None, _) => {
+                    return responses::ReadStatsResponse::BadRequest(Json(Error::bad_request(
+                        "A custom window requires `from`.".to_string(),
+                    )))
+                }
+            },
+        };
+
+        let check = match owned_check(store, caller, check_id).await {
+            Ok(Some(check)) => check,
+            Ok(None) => {
+                return responses::ReadStatsResponse::NotFound(Json(Error::not_found(format!(
+                    "Check not found with id '{check_id}'"
+                ))))
+            }
+            Err(err) => {
+                return responses::ReadStatsResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        };
+
+        match stats::read(store, &check, from, to).await {
+            Ok(stats) => responses::ReadStatsResponse::Success(Json(stats)),
+            Err(err) => responses::ReadStatsResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Delete check history
+    #[oai(method = "delete", path = "/:check_id/history", tag = APITags::History)]
+    async fn delete_history(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(check_id): Path<ObjectId>,
+    ) -> responses::DeleteHistoryResponse {
+        match owned_check(store, caller, check_id).await {
+            Ok(Some(_)) => (),
+            Ok(None) => {
+                return responses::DeleteHistoryResponse::NotFound(Json(Error::not_found(format!(
+                    "Check not found with id '{check_id}'"
+                ))))
+            }
+            Err(err) => {
+                return responses::DeleteHistoryResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        }
+
+        let delete = store.delete_history(check_id).await;
+        if let Ok(deleted) = delete {
+            if deleted > 0 {
                 responses::DeleteHistoryResponse::Success
             } else {
                 responses::DeleteHistoryResponse::NotFound(Json(Error::not_found(format!(
@@ -310,10 +1161,1317 @@ impl MonitorAPI {
                 ))))
             }
         } else {
-            let err = unsafe { delete.unwrap_err_unchecked() };
-            responses::DeleteHistoryResponse::InternalServerError(Json(
+            let err = unsafe { delete.unwrap_err_unchecked() };
+            responses::DeleteHistoryResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            ))
+        }
+    }
+
+    /// Read incidents, most recent first
+    #[oai(method = "get", path = "/incidents", tag = APITags::Incident)]
+    async fn read_incidents(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Query(check_id): Query<Option<ObjectId>>,
+        Query(status): Query<Option<IncidentStatus>>,
+    ) -> responses::ReadIncidentsResponse {
+        let filter = IncidentFilter {
+            project_id: Some(caller.project_id),
+            check_id,
+            status,
+            ..Default::default()
+        };
+        find_incidents(store, &filter).await
+    }
+
+    /// Read one incident
+    #[oai(method = "get", path = "/incidents/:incident_id", tag = APITags::Incident)]
+    async fn read_incident(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(incident_id): Path<ObjectId>,
+    ) -> responses::ReadIncidentResponse {
+        match store.incident(incident_id).await {
+            Ok(Some(incident)) if incident.project_id == caller.project_id => {
+                responses::ReadIncidentResponse::Success(Json(incident))
+            }
+            Ok(_) => responses::ReadIncidentResponse::NotFound(Json(Error::not_found(format!(
+                "Incident not found with id '{incident_id}'"
+            )))),
+            Err(err) => responses::ReadIncidentResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Post an update on an incident, shown on the status pages of its check.
+    ///
+    /// Updates only inform, the incident is resolved once the check recovers
+    #[oai(
+        method = "post",
+        path = "/incidents/:incident_id/updates",
+        tag = APITags::Incident
+    )]
+    async fn create_incident_update(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(incident_id): Path<ObjectId>,
+        Json(new_update): Json<NewIncidentUpdate>,
+    ) -> responses::CreateIncidentUpdateResponse {
+        if new_update.message.trim().is_empty() {
+            return responses::CreateIncidentUpdateResponse::BadRequest(Json(Error::bad_request(
+                "Incident updates need a message.".to_string(),
+            )));
+        }
+
+        let not_found = || {
+            responses::CreateIncidentUpdateResponse::NotFound(Json(Error::not_found(format!(
+                "Incident not found with id '{incident_id}'"
+            ))))
+        };
+        match store.incident(incident_id).await {
+            Ok(Some(incident)) if incident.project_id == caller.project_id => (),
+            Ok(_) => return not_found(),
+            Err(err) => {
+                return responses::CreateIncidentUpdateResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        }
+
+        let update = IncidentUpdate {
+            status: new_update.status,
+            message: new_update.message,
+            created_at: Utc::now(),
+        };
+        match store.add_incident_update(incident_id, &update).await {
+            Ok(Some(incident)) => responses::CreateIncidentUpdateResponse::Success(Json(incident)),
+            Ok(None) => not_found(),
+            Err(err) => responses::CreateIncidentUpdateResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Read check incidents, most recent first
+    #[oai(method = "get", path = "/:check_id/incidents", tag = APITags::Incident)]
+    async fn read_check_incidents(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(check_id): Path<ObjectId>,
+    ) -> responses::ReadIncidentsResponse {
+        let filter = IncidentFilter {
+            project_id: Some(caller.project_id),
+            check_id: Some(check_id),
+            ..Default::default()
+        };
+        find_incidents(store, &filter).await
+    }
+
+    /// Read the pings received by a heartbeat check, most recent first
+    #[oai(method = "get", path = "/:check_id/pings", tag = APITags::Heartbeat)]
+    async fn read_pings(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(check_id): Path<ObjectId>,
+        Query(signal): Query<Option<HeartbeatSignal>>,
+        /// Maximum number of pings returned, defaults to 100
+        Query(limit): Query<Option<u32>>,
+    ) -> responses::ReadPingsResponse {
+        match owned_check(store, caller, check_id).await {
+            Ok(Some(_)) => (),
+            Ok(None) => {
+                return responses::ReadPingsResponse::NotFound(Json(Error::not_found(format!(
+                    "Check not found with id '{check_id}'"
+                ))))
+            }
+            Err(err) => {
+                return responses::ReadPingsResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        }
+
+        let pings = store
+            .find_pings(check_id, signal, limit.unwrap_or(100))
+            .await;
+        match pings {
+            Ok(pings) => responses::ReadPingsResponse::Success(Json(pings)),
+            Err(err) => responses::ReadPingsResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Issue a new badge token for the check, the badges can then be embedded without an API key.
+    ///
+    /// A token issued before stops working
+    #[oai(method = "post", path = "/:check_id/badge-token", tag = APITags::Badge)]
+    async fn create_badge_token(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(check_id): Path<ObjectId>,
+    ) -> responses::CreateBadgeTokenResponse {
+        let not_found = || {
+            responses::CreateBadgeTokenResponse::NotFound(Json(Error::not_found(format!(
+                "Check not found with id '{check_id}'"
+            ))))
+        };
+        match owned_check(store, caller, check_id).await {
+            Ok(Some(_)) => (),
+            Ok(None) => return not_found(),
+            Err(err) => {
+                return responses::CreateBadgeTokenResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        }
+
+        let token = badges::generate_token();
+        match store
+            .update_check(
+                check_id,
+                &CheckFields {
+                    badge_token: Some(Some(token.clone())),
+                    ..Default::default()
+                },
+            )
+            .await
+        {
+            Ok(Some(_)) => responses::CreateBadgeTokenResponse::Success(Json(BadgeToken {
+                status_url: format!("{}?token={token}", badges::status_path(check_id)),
+                uptime_url: format!("{}?token={token}", badges::uptime_path(check_id)),
+                token,
+            })),
+            Ok(None) => not_found(),
+            Err(err) => responses::CreateBadgeTokenResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Revoke the badge token of the check, its badges are only shown with an API key again
+    #[oai(method = "delete", path = "/:check_id/badge-token", tag = APITags::Badge)]
+    async fn delete_badge_token(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(check_id): Path<ObjectId>,
+    ) -> responses::DeleteBadgeTokenResponse {
+        let not_found = || {
+            responses::DeleteBadgeTokenResponse::NotFound(Json(Error::not_found(format!(
+                "No badge token for the check with id '{check_id}'"
+            ))))
+        };
+        match owned_check(store, caller, check_id).await {
+            Ok(Some(check)) if check.badge_token.is_some() => (),
+            Ok(_) => return not_found(),
+            Err(err) => {
+                return responses::DeleteBadgeTokenResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        }
+
+        match store
+            .update_check(
+                check_id,
+                &CheckFields {
+                    badge_token: Some(None),
+                    ..Default::default()
+                },
+            )
+            .await
+        {
+            Ok(Some(_)) => responses::DeleteBadgeTokenResponse::Success,
+            Ok(None) => not_found(),
+            Err(err) => responses::DeleteBadgeTokenResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Read maintenance windows
+    #[oai(method = "get", path = "/maintenance", tag = APITags::Maintenance)]
+    async fn read_maintenance_windows(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        /// Only windows covering this check, by id or by tag
+        Query(check_id): Query<Option<ObjectId>>,
+        /// Only windows open, or closed, right now
+        Query(open): Query<Option<bool>>,
+    ) -> responses::ReadMaintenanceWindowsResponse {
+        let windows = match check_id {
+            Some(check_id) => {
+                let check = owned_check(store, caller, check_id).await;
+                match check {
+                    Ok(Some(check)) => store.maintenance_windows_for(&check).await,
+                    Ok(None) => {
+                        return responses::ReadMaintenanceWindowsResponse::NotFound(Json(
+                            Error::not_found(format!("Check not found with id '{check_id}'")),
+                        ))
+                    }
+                    Err(err) => Err(err),
+                }
+            }
+            None => store.maintenance_windows(caller.project_id).await,
+        };
+
+        let now = Utc::now();
+        match windows {
+            Ok(windows) => responses::ReadMaintenanceWindowsResponse::Success(Json(
+                windows
+                    .into_iter()
+                    .filter(|window| open.is_none_or(|open| window.is_open(now) == open))
+                    .collect(),
+            )),
+            Err(err) => responses::ReadMaintenanceWindowsResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Read one maintenance window
+    #[oai(method = "get", path = "/maintenance/:window_id", tag = APITags::Maintenance)]
+    async fn read_maintenance_window(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(window_id): Path<ObjectId>,
+    ) -> responses::ReadMaintenanceWindowResponse {
+        match store.maintenance_window(window_id).await {
+            Ok(Some(window)) if window.project_id == caller.project_id => {
+                responses::ReadMaintenanceWindowResponse::Success(Json(window))
+            }
+            Ok(_) => responses::ReadMaintenanceWindowResponse::NotFound(Json(Error::not_found(
+                format!("Maintenance window not found with id '{window_id}'"),
+            ))),
+            Err(err) => responses::ReadMaintenanceWindowResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Create maintenance window
+    #[oai(method = "post", path = "/maintenance", tag = APITags::Maintenance)]
+    async fn create_maintenance_window(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Json(new_window): Json<NewMaintenanceWindow>,
+    ) -> responses::CreateMaintenanceWindowResponse {
+        let window = MaintenanceWindow::from_new(new_window, caller.project_id);
+
+        if let Err(err) = window.validate() {
+            return responses::CreateMaintenanceWindowResponse::BadRequest(Json(
+                Error::bad_request(err),
+            ));
+        }
+
+        if let Err(err) = ensure_checks_exist(store, caller, &window.checks).await {
+            return responses::CreateMaintenanceWindowResponse::BadRequest(Json(
+                Error::bad_request(err),
+            ));
+        }
+
+        let result = store.insert_maintenance_window(&window).await;
+        result.map_or_else(
+            |e| {
+                responses::CreateMaintenanceWindowResponse::BadRequest(Json(Error::bad_request(
+                    e.to_string(),
+                )))
+            },
+            |_| responses::CreateMaintenanceWindowResponse::Success(Json(window)),
+        )
+    }
+
+    /// Update maintenance window
+    #[oai(method = "put", path = "/maintenance/:window_id", tag = APITags::Maintenance)]
+    async fn update_maintenance_window(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(window_id): Path<ObjectId>,
+        Json(update): Json<UpdateMaintenanceWindow>,
+    ) -> responses::UpdateMaintenanceWindowResponse {
+        if let Some(ref checks) = update.checks {
+            if let Err(err) = ensure_checks_exist(store, caller, checks).await {
+                return responses::UpdateMaintenanceWindowResponse::BadRequest(Json(
+                    Error::bad_request(err),
+                ));
+            }
+        }
+        let fields = MaintenanceWindowFields {
+            name: update.name,
+            starts_at: update.starts_at,
+            ends_at: update.ends_at,
+            recurrence: update.recurrence,
+            duration_secs: update.duration_secs,
+            checks: update.checks,
+            tags: update.tags,
+            mode: update.mode,
+            updated_at: Some(Utc::now()),
+        };
+
+        // Whether the window is valid depends on every field, validate it as it will be saved
+        let current = match store.maintenance_window(window_id).await {
+            Ok(Some(current)) if current.project_id == caller.project_id => current,
+            Ok(_) => {
+                return responses::UpdateMaintenanceWindowResponse::NotFound(Json(
+                    Error::not_found(format!(
+                        "Maintenance window not found with id '{window_id}'"
+                    )),
+                ))
+            }
+            Err(err) => {
+                return responses::UpdateMaintenanceWindowResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        };
+        let outcome = store::merge(&current, &fields)
+            .map_err(|err| err.to_string())
+            .and_then(|window| window.validate());
+        if let Err(err) = outcome {
+            return responses::UpdateMaintenanceWindowResponse::BadRequest(Json(
+                Error::bad_request(err),
+            ));
+        }
+
+        match store.update_maintenance_window(window_id, &fields).await {
+            Ok(Some(_)) => responses::UpdateMaintenanceWindowResponse::Success,
+            Ok(None) => {
+                responses::UpdateMaintenanceWindowResponse::NotFound(Json(Error::not_found(
+                    format!("Maintenance window not found with id '{window_id}'"),
+                )))
+            }
+            Err(err) => responses::UpdateMaintenanceWindowResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Delete maintenance window
+    #[oai(method = "delete", path = "/maintenance/:window_id", tag = APITags::Maintenance)]
+    async fn delete_maintenance_window(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(window_id): Path<ObjectId>,
+    ) -> responses::DeleteMaintenanceWindowResponse {
+        match store.maintenance_window(window_id).await {
+            Ok(Some(window)) if window.project_id == caller.project_id => (),
+            Ok(_) => {
+                return responses::DeleteMaintenanceWindowResponse::NotFound(Json(
+                    Error::not_found(format!(
+                        "Maintenance window not found with id '{window_id}'"
+                    )),
+                ))
+            }
+            Err(err) => {
+                return responses::DeleteMaintenanceWindowResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        }
+
+        match store.delete_maintenance_window(window_id).await {
+            Ok(true) => responses::DeleteMaintenanceWindowResponse::Success,
+            Ok(false) => {
+                responses::DeleteMaintenanceWindowResponse::NotFound(Json(Error::not_found(
+                    format!("Maintenance window not found with id '{window_id}'"),
+                )))
+            }
+            Err(err) => responses::DeleteMaintenanceWindowResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Read all notification channels
+    #[oai(method = "get", path = "/channels", tag = APITags::Channel)]
+    async fn read_channels(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+    ) -> responses::ReadChannelsResponse {
+        match store.channels(caller.project_id).await {
+            Ok(channels) => responses::ReadChannelsResponse::Success(Json(
+                channels
+                    .into_iter()
+                    .map(NotificationChannel::redacted)
+                    .collect(),
+            )),
+            Err(err) => responses::ReadChannelsResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Read one notification channel
+    #[oai(method = "get", path = "/channels/:channel_id", tag = APITags::Channel)]
+    async fn read_channel(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(channel_id): Path<ObjectId>,
+    ) -> responses::ReadChannelResponse {
+        match store.channel(channel_id).await {
+            Ok(Some(channel)) if channel.project_id == caller.project_id => {
+                responses::ReadChannelResponse::Success(Json(channel.redacted()))
+            }
+            Ok(_) => responses::ReadChannelResponse::NotFound(Json(Error::not_found(format!(
+                "Channel not found with id '{channel_id}'"
+            )))),
+            Err(err) => responses::ReadChannelResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Create new notification channel
+    #[oai(method = "post", path = "/channels", tag = APITags::Channel)]
+    async fn create_channel(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Json(new_channel): Json<NewNotificationChannel>,
+    ) -> responses::CreateChannelResponse {
+        let mut channel = NotificationChannel::from_new(new_channel, caller.project_id);
+        channel.config.ensure_secret();
+
+        if let Err(err) = channel.config.validate() {
+            return responses::CreateChannelResponse::BadRequest(Json(Error::bad_request(err)));
+        }
+
+        let result = store.insert_channel(&channel).await;
+        result.map_or_else(
+            |e| {
+                responses::CreateChannelResponse::BadRequest(Json(Error::bad_request(
+                    e.to_string(),
+                )))
+            },
+            |_| responses::CreateChannelResponse::Success(Json(channel)),
+        )
+    }
+
+    /// Update notification channel
+    #[oai(method = "put", path = "/channels/:channel_id", tag = APITags::Channel)]
+    async fn update_channel(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(channel_id): Path<ObjectId>,
+        Json(update): Json<UpdateNotificationChannel>,
+    ) -> responses::UpdateChannelResponse {
+        let current = match store.channel(channel_id).await {
+            Ok(Some(channel)) if channel.project_id == caller.project_id => channel,
+            Ok(_) => {
+                return responses::UpdateChannelResponse::NotFound(Json(Error::not_found(format!(
+                    "Channel not found with id '{channel_id}'"
+                ))))
+            }
+            Err(err) => {
+                return responses::UpdateChannelResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        };
+
+        let mut fields = ChannelFields {
+            name: update.name,
+            updated_at: Some(Utc::now()),
+            ..Default::default()
+        };
+        if let Some(mut config) = update.config {
+            config.keep_credentials(&current.config);
+            config.ensure_secret();
+            if let Err(err) = config.validate() {
+                return responses::UpdateChannelResponse::BadRequest(Json(Error::bad_request(err)));
+            }
+            fields.config = Some(config);
+        }
+
+        match store.update_channel(channel_id, &fields).await {
+            Ok(Some(_)) => responses::UpdateChannelResponse::Success,
+            Ok(None) => responses::UpdateChannelResponse::NotFound(Json(Error::not_found(
+                format!("Channel not found with id '{channel_id}'"),
+            ))),
+            Err(err) => responses::UpdateChannelResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Delete notification channel, checks using it stop referencing it
+    #[oai(method = "delete", path = "/channels/:channel_id", tag = APITags::Channel)]
+    async fn delete_channel(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Data(scheduler): Data<&Scheduler>,
+        Path(channel_id): Path<ObjectId>,
+    ) -> responses::DeleteChannelResponse {
+        match store.channel(channel_id).await {
+            Ok(Some(channel)) if channel.project_id == caller.project_id => (),
+            Ok(_) => {
+                return responses::DeleteChannelResponse::NotFound(Json(Error::not_found(format!(
+                    "Channel not found with id '{channel_id}'"
+                ))))
+            }
+            Err(err) => {
+                return responses::DeleteChannelResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        }
+
+        match store.delete_channel(channel_id).await {
+            Ok(true) => (),
+            Ok(false) => {
+                return responses::DeleteChannelResponse::NotFound(Json(Error::not_found(format!(
+                    "Channel not found with id '{channel_id}'"
+                ))))
+            }
+            Err(err) => {
+                return responses::DeleteChannelResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        }
+
+        match store.remove_channel_from_checks(channel_id).await {
+            Ok(affected) => {
+                for check in affected {
+                    scheduler.schedule(check);
+                }
+                responses::DeleteChannelResponse::Success
+            }
+            Err(err) => responses::DeleteChannelResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Read webhook deliveries, most recent first
+    #[oai(method = "get", path = "/deliveries", tag = APITags::Delivery)]
+    #[allow(clippy::too_many_arguments)]
+    async fn read_deliveries(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Query(channel_id): Query<Option<ObjectId>>,
+        Query(check_id): Query<Option<ObjectId>>,
+        Query(status): Query<Option<DeliveryStatus>>,
+        /// Maximum number of deliveries returned, defaults to 100
+        Query(limit): Query<Option<u32>>,
+    ) -> responses::ReadDeliveriesResponse {
+        let filter = DeliveryFilter {
+            project_id: Some(caller.project_id),
+            channel_id,
+            check_id,
+            status,
+        };
+        match store.find_deliveries(&filter, limit.unwrap_or(100)).await {
+            Ok(deliveries) => responses::ReadDeliveriesResponse::Success(Json(deliveries)),
+            Err(err) => responses::ReadDeliveriesResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Read one webhook delivery, with every attempt made
+    #[oai(method = "get", path = "/deliveries/:delivery_id", tag = APITags::Delivery)]
+    async fn read_delivery(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(delivery_id): Path<ObjectId>,
+    ) -> responses::ReadDeliveryResponse {
+        match store.delivery(delivery_id).await {
+            Ok(Some(delivery)) if delivery.project_id == caller.project_id => {
+                responses::ReadDeliveryResponse::Success(Json(delivery))
+            }
+            Ok(_) => responses::ReadDeliveryResponse::NotFound(Json(Error::not_found(format!(
+                "Delivery not found with id '{delivery_id}'"
+            )))),
+            Err(err) => responses::ReadDeliveryResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Read the API keys of the project, or of every project for operators, oldest first.
+    /// Keys themselves are never returned
+    #[oai(method = "get", path = "/api-keys", tag = APITags::ApiKey)]
+    async fn read_api_keys(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+    ) -> responses::ReadApiKeysResponse {
+        let project_id = (caller.scope < ApiKeyScope::Operator).then_some(caller.project_id);
+        match store.api_keys(project_id).await {
+            Ok(api_keys) => responses::ReadApiKeysResponse::Success(Json(api_keys)),
+            Err(err) => responses::ReadApiKeysResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Mint an API key, for the project of the caller unless an operator names another one.
+    /// The key is only returned now, keep it safe
+    #[oai(method = "post", path = "/api-keys", tag = APITags::ApiKey)]
+    async fn create_api_key(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Json(new_key): Json<NewApiKey>,
+    ) -> responses::CreateApiKeyResponse {
+        if new_key.name.trim().is_empty() {
+            return responses::CreateApiKeyResponse::BadRequest(Json(Error::bad_request(
+                "API keys need a name.".to_string(),
+            )));
+        }
+
+        if new_key.scope > caller.scope {
+            return responses::CreateApiKeyResponse::Forbidden(Json(Error::forbidden(
+                "Keys cannot be given a scope above the scope of the key minting them.".to_string(),
+            )));
+        }
+
+        let project_id = new_key.project_id.unwrap_or(caller.project_id);
+        if project_id != caller.project_id && caller.scope < ApiKeyScope::Operator {
+            return responses::CreateApiKeyResponse::Forbidden(Json(Error::forbidden(
+                "Only operators mint keys for other projects.".to_string(),
+            )));
+        }
+        match store.project(project_id).await {
+            Ok(Some(_)) => (),
+            Ok(None) => {
+                return responses::CreateApiKeyResponse::BadRequest(Json(Error::bad_request(
+                    format!("Project not found with id '{project_id}'"),
+                )))
+            }
+            Err(err) => {
+                return responses::CreateApiKeyResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        }
+
+        if let Some(user_id) = new_key.user_id {
+            match store.user(user_id).await {
+                Ok(Some(user)) if user.project_id != project_id => {
+                    return responses::CreateApiKeyResponse::BadRequest(Json(Error::bad_request(
+                        format!("User not found with id '{user_id}' in the key's project"),
+                    )))
+                }
+                Ok(Some(user)) if new_key.scope > user.scope => {
+                    return responses::CreateApiKeyResponse::Forbidden(Json(Error::forbidden(
+                        "Keys cannot be given a scope above the scope of their user.".to_string(),
+                    )))
+                }
+                Ok(Some(_)) => (),
+                Ok(None) => {
+                    return responses::CreateApiKeyResponse::BadRequest(Json(Error::bad_request(
+                        format!("User not found with id '{user_id}'"),
+                    )))
+                }
+                Err(err) => {
+                    return responses::CreateApiKeyResponse::InternalServerError(Json(
+                        Error::internal_server_error(err.to_string()),
+                    ))
+                }
+            }
+        }
+
+        let (api_key, key) = ApiKey::mint(project_id, new_key.name, new_key.scope, new_key.user_id);
+        match store.insert_api_key(&api_key).await {
+            Ok(()) => responses::CreateApiKeyResponse::Success(Json(MintedApiKey { api_key, key })),
+            Err(err) => responses::CreateApiKeyResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Revoke an API key of the project, or of any project for operators, whose scope is not
+    /// above the caller's. Requests made with it are refused from now on
+    #[oai(method = "delete", path = "/api-keys/:key_id", tag = APITags::ApiKey)]
+    async fn revoke_api_key(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(key_id): Path<ObjectId>,
+    ) -> responses::RevokeApiKeyResponse {
+        let not_found = || {
+            responses::RevokeApiKeyResponse::NotFound(Json(Error::not_found(format!(
+                "API key not found with id '{key_id}'"
+            ))))
+        };
+        match store.api_key(key_id).await {
+            Ok(Some(api_key)) if !visible_key(caller, &api_key) => return not_found(),
+            Ok(Some(api_key)) if api_key.scope > caller.scope => {
+                return responses::RevokeApiKeyResponse::Forbidden(Json(Error::forbidden(
+                    "Keys with a scope above the scope of the caller cannot be revoked by it."
+                        .to_string(),
+                )))
+            }
+            Ok(Some(api_key)) if api_key.revoked_at.is_some() => {
+                return responses::RevokeApiKeyResponse::Success
+            }
+            Ok(Some(_)) => (),
+            Ok(None) => return not_found(),
+            Err(err) => {
+                return responses::RevokeApiKeyResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        }
+
+        let fields = ApiKeyFields {
+            revoked_at: Some(Utc::now()),
+            ..Default::default()
+        };
+        match store.update_api_key(key_id, &fields).await {
+            Ok(Some(_)) => responses::RevokeApiKeyResponse::Success,
+            Ok(None) => not_found(),
+            Err(err) => responses::RevokeApiKeyResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Read the users of the project, or of every project for operators, oldest first
+    #[oai(method = "get", path = "/users", tag = APITags::User)]
+    async fn read_users(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+    ) -> responses::ReadUsersResponse {
+        let project_id = (caller.scope < ApiKeyScope::Operator).then_some(caller.project_id);
+        match store.users(project_id).await {
+            Ok(users) => responses::ReadUsersResponse::Success(Json(users)),
+            Err(err) => responses::ReadUsersResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Add a user to the project of the caller unless an operator names another one. Keys are
+    /// minted for them with `user_id`
+    #[oai(method = "post", path = "/users", tag = APITags::User)]
+    async fn create_user(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Json(new_user): Json<NewUser>,
+    ) -> responses::CreateUserResponse {
+        if new_user.name.trim().is_empty() {
+            return responses::CreateUserResponse::BadRequest(Json(Error::bad_request(
+                "Users need a name.".to_string(),
+            )));
+        }
+        if let Err(err) = new_user.email.trim().parse::<Address>() {
+            return responses::CreateUserResponse::BadRequest(Json(Error::bad_request(format!(
+                "Invalid email address '{}': {err}",
+                new_user.email
+            ))));
+        }
+
+        if new_user.scope > caller.scope {
+            return responses::CreateUserResponse::Forbidden(Json(Error::forbidden(
+                "Users cannot be given a scope above the scope of the key adding them.".to_string(),
+            )));
+        }
+
+        let project_id = new_user.project_id.unwrap_or(caller.project_id);
+        if project_id != caller.project_id && caller.scope < ApiKeyScope::Operator {
+            return responses::CreateUserResponse::Forbidden(Json(Error::forbidden(
+                "Only operators add users to other projects.".to_string(),
+            )));
+        }
+        match store.project(project_id).await {
+            Ok(Some(_)) => (),
+            Ok(None) => {
+                return responses::CreateUserResponse::BadRequest(Json(Error::bad_request(
+                    format!("Project not found with id '{project_id}'"),
+                )))
+            }
+            Err(err) => {
+                return responses::CreateUserResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        }
+
+        let user = User::from_new(new_user, project_id);
+        match store.users(Some(project_id)).await {
+            Ok(users) if users.iter().any(|other| other.email == user.email) => {
+                return responses::CreateUserResponse::BadRequest(Json(Error::bad_request(
+                    format!("The project already has a user with email '{}'", user.email),
+                )))
+            }
+            Ok(_) => (),
+            Err(err) => {
+                return responses::CreateUserResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        }
+
+        match store.insert_user(&user).await {
+            Ok(()) => responses::CreateUserResponse::Success(Json(user)),
+            Err(err) => responses::CreateUserResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Remove a user of the project, or of any project for operators, whose scope is not above
+    /// the caller's. The keys minted for them are revoked
+    #[oai(method = "delete", path = "/users/:user_id", tag = APITags::User)]
+    async fn delete_user(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(user_id): Path<ObjectId>,
+    ) -> responses::DeleteUserResponse {
+        let not_found = || {
+            responses::DeleteUserResponse::NotFound(Json(Error::not_found(format!(
+                "User not found with id '{user_id}'"
+            ))))
+        };
+        match store.user(user_id).await {
+            Ok(Some(user)) if !visible_user(caller, &user) => return not_found(),
+            Ok(Some(user)) if user.scope > caller.scope => {
+                return responses::DeleteUserResponse::Forbidden(Json(Error::forbidden(
+                    "Users with a scope above the scope of the caller cannot be removed by it."
+                        .to_string(),
+                )))
+            }
+            Ok(Some(_)) => (),
+            Ok(None) => return not_found(),
+            Err(err) => {
+                return responses::DeleteUserResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        }
+
+        match store.delete_user(user_id).await {
+            Ok(true) => responses::DeleteUserResponse::Success,
+            Ok(false) => not_found(),
+            Err(err) => responses::DeleteUserResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Read projects, every one of them for operators and only their own for other keys
+    #[oai(method = "get", path = "/projects", tag = APITags::Project)]
+    async fn read_projects(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+    ) -> responses::ReadProjectsResponse {
+        let projects = if caller.scope < ApiKeyScope::Operator {
+            store
+                .project(caller.project_id)
+                .await
+                .map(|project| project.into_iter().collect())
+        } else {
+            store.projects().await
+        };
+        match projects {
+            Ok(projects) => responses::ReadProjectsResponse::Success(Json(projects)),
+            Err(err) => responses::ReadProjectsResponse::InternalServerError(Json(
                 Error::internal_server_error(err.to_string()),
-            ))
+            )),
+        }
+    }
+
+    /// Read one project
+    #[oai(method = "get", path = "/projects/:project_id", tag = APITags::Project)]
+    async fn read_project(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(project_id): Path<ObjectId>,
+    ) -> responses::ReadProjectResponse {
+        let visible = project_id == caller.project_id || caller.scope >= ApiKeyScope::Operator;
+        match store.project(project_id).await {
+            Ok(Some(project)) if visible => responses::ReadProjectResponse::Success(Json(project)),
+            Ok(_) => responses::ReadProjectResponse::NotFound(Json(Error::not_found(format!(
+                "Project not found with id '{project_id}'"
+            )))),
+            Err(err) => responses::ReadProjectResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Create a project, only operators can. Keys for it are minted with `project_id`
+    #[oai(method = "post", path = "/projects", tag = APITags::Project)]
+    async fn create_project(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Json(new_project): Json<NewProject>,
+    ) -> responses::CreateProjectResponse {
+        if new_project.name.trim().is_empty() {
+            return responses::CreateProjectResponse::BadRequest(Json(Error::bad_request(
+                "Projects need a name.".to_string(),
+            )));
+        }
+
+        let project = Project::from_new(new_project);
+        match store.insert_project(&project).await {
+            Ok(()) => responses::CreateProjectResponse::Success(Json(project)),
+            Err(err) => responses::CreateProjectResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Rename a project or replace its quota, only operators can. Checks it already has are kept
+    #[oai(method = "put", path = "/projects/:project_id", tag = APITags::Project)]
+    async fn update_project(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Path(project_id): Path<ObjectId>,
+        Json(update): Json<UpdateProject>,
+    ) -> responses::UpdateProjectResponse {
+        if update
+            .name
+            .as_ref()
+            .is_some_and(|name| name.trim().is_empty())
+        {
+            return responses::UpdateProjectResponse::BadRequest(Json(Error::bad_request(
+                "Projects need a name.".to_string(),
+            )));
+        }
+        let fields = ProjectFields {
+            name: update.name,
+            quota: update.quota,
+            updated_at: Some(Utc::now()),
+        };
+
+        match store.update_project(project_id, &fields).await {
+            Ok(Some(_)) => responses::UpdateProjectResponse::Success,
+            Ok(None) => responses::UpdateProjectResponse::NotFound(Json(Error::not_found(
+                format!("Project not found with id '{project_id}'"),
+            ))),
+            Err(err) => responses::UpdateProjectResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Read the status pages of the project
+    #[oai(method = "get", path = "/status-pages", tag = APITags::StatusPage)]
+    async fn read_status_pages(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+    ) -> responses::ReadStatusPagesResponse {
+        match store.status_pages(caller.project_id).await {
+            Ok(pages) => responses::ReadStatusPagesResponse::Success(Json(pages)),
+            Err(err) => responses::ReadStatusPagesResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Read one status page
+    #[oai(method = "get", path = "/status-pages/:page_id", tag = APITags::StatusPage)]
+    async fn read_status_page(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Path(page_id): Path<ObjectId>,
+    ) -> responses::ReadStatusPageResponse {
+        match store.status_page(page_id).await {
+            Ok(Some(page)) if page.project_id == caller.project_id => {
+                responses::ReadStatusPageResponse::Success(Json(page))
+            }
+            Ok(_) => responses::ReadStatusPageResponse::NotFound(Json(Error::not_found(format!(
+                "Status page not found with id '{page_id}'"
+            )))),
+            Err(err) => responses::ReadStatusPageResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Create a status page, served publicly at `/status/<slug>` and `/status/<slug>/json`
+    #[oai(method = "post", path = "/status-pages", tag = APITags::StatusPage)]
+    async fn create_status_page(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Json(new_page): Json<NewStatusPage>,
+    ) -> responses::CreateStatusPageResponse {
+        let page = StatusPage::from_new(new_page, caller.project_id);
+
+        if let Err(err) = validate_status_page(store, caller, &page).await {
+            return responses::CreateStatusPageResponse::BadRequest(Json(Error::bad_request(err)));
+        }
+
+        match store.insert_status_page(&page).await {
+            Ok(()) => responses::CreateStatusPageResponse::Success(Json(page)),
+            Err(err) => responses::CreateStatusPageResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Update status page
+    #[oai(method = "put", path = "/status-pages/:page_id", tag = APITags::StatusPage)]
+    async fn update_status_page(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Data(pages): Data<&PageCache>,
+        Path(page_id): Path<ObjectId>,
+        Json(update): Json<UpdateStatusPage>,
+    ) -> responses::UpdateStatusPageResponse {
+        let fields = StatusPageFields {
+            slug: update.slug,
+            title: update.title,
+            description: update.description,
+            components: update.components,
+            branding: update.branding,
+            updated_at: Some(Utc::now()),
+        };
+
+        // Whether the page is valid depends on every field, validate it as it will be saved
+        let current = match store.status_page(page_id).await {
+            Ok(Some(current)) if current.project_id == caller.project_id => current,
+            Ok(_) => {
+                return responses::UpdateStatusPageResponse::NotFound(Json(Error::not_found(
+                    format!("Status page not found with id '{page_id}'"),
+                )))
+            }
+            Err(err) => {
+                return responses::UpdateStatusPageResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        };
+        let page = match store::merge(&current, &fields) {
+            Ok(page) => page,
+            Err(err) => {
+                return responses::UpdateStatusPageResponse::BadRequest(Json(Error::bad_request(
+                    err.to_string(),
+                )))
+            }
+        };
+        if let Err(err) = validate_status_page(store, caller, &page).await {
+            return responses::UpdateStatusPageResponse::BadRequest(Json(Error::bad_request(err)));
+        }
+
+        match store.update_status_page(page_id, &fields).await {
+            Ok(Some(_)) => {
+                pages.invalidate(&current.slug);
+                responses::UpdateStatusPageResponse::Success
+            }
+            Ok(None) => responses::UpdateStatusPageResponse::NotFound(Json(Error::not_found(
+                format!("Status page not found with id '{page_id}'"),
+            ))),
+            Err(err) => responses::UpdateStatusPageResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+
+    /// Delete status page, its public URL stops working
+    #[oai(method = "delete", path = "/status-pages/:page_id", tag = APITags::StatusPage)]
+    async fn delete_status_page(
+        &self,
+        _auth: ApiKeyAuth,
+        Data(store): Data<&Storage>,
+        Data(caller): Data<&ApiKey>,
+        Data(pages): Data<&PageCache>,
+        Path(page_id): Path<ObjectId>,
+    ) -> responses::DeleteStatusPageResponse {
+        let not_found = || {
+            responses::DeleteStatusPageResponse::NotFound(Json(Error::not_found(format!(
+                "Status page not found with id '{page_id}'"
+            ))))
+        };
+        let page = match store.status_page(page_id).await {
+            Ok(Some(page)) if page.project_id == caller.project_id => page,
+            Ok(_) => return not_found(),
+            Err(err) => {
+                return responses::DeleteStatusPageResponse::InternalServerError(Json(
+                    Error::internal_server_error(err.to_string()),
+                ))
+            }
+        };
+
+        match store.delete_status_page(page_id).await {
+            Ok(true) => {
+                pages.invalidate(&page.slug);
+                responses::DeleteStatusPageResponse::Success
+            }
+            Ok(false) => not_found(),
+            Err(err) => responses::DeleteStatusPageResponse::InternalServerError(Json(
+                Error::internal_server_error(err.to_string()),
+            )),
+        }
+    }
+}
+
+async fn find_incidents(
+    store: &Storage,
+    filter: &IncidentFilter,
+) -> responses::ReadIncidentsResponse {
+    match store.find_incidents(filter).await {
+        Ok(incidents) => responses::ReadIncidentsResponse::Success(Json(incidents)),
+        Err(err) => responses::ReadIncidentsResponse::InternalServerError(Json(
+            Error::internal_server_error(err.to_string()),
+        )),
+    }
+}
+
+fn validate_sla_target(target: f64) -> Result<(), String> {
+    if target > 0.0 && target <= 100.0 {
+        Ok(())
+    } else {
+        Err("SLA target must be a percentage greater than 0 and up to 100.".to_string())
+    }
+}
+
+/// The check when it exists and belongs to the project of the caller
+async fn owned_check(
+    store: &Storage,
+    caller: &ApiKey,
+    check_id: ObjectId,
+) -> store::Result<Option<Check>> {
+    let check = store.check(check_id).await?;
+    Ok(check.filter(|check| check.project_id == caller.project_id))
+}
+
+/// Operators see the keys of every project, other keys only those of their own
+fn visible_key(caller: &ApiKey, api_key: &ApiKey) -> bool {
+    api_key.project_id == caller.project_id || caller.scope >= ApiKeyScope::Operator
+}
+
+/// Operators see the users of every project, other keys only those of their own
+fn visible_user(caller: &ApiKey, user: &User) -> bool {
+    user.project_id == caller.project_id || caller.scope >= ApiKeyScope::Operator
+}
+
+/// Limits of the project of the caller, nothing is limited when the project is missing
+async fn project_quota(store: &Storage, caller: &ApiKey) -> store::Result<ProjectQuota> {
+    let project = store.project(caller.project_id).await?;
+    Ok(project.map(|project| project.quota).unwrap_or_default())
+}
+
+/// Fail with a readable message if the page is invalid, its slug is taken or any of its checks
+/// does not exist in the project of the caller
+async fn validate_status_page(
+    store: &Storage,
+    caller: &ApiKey,
+    page: &StatusPage,
+) -> Result<(), String> {
+    page.validate()?;
+
+    match store.status_page_by_slug(&page.slug).await {
+        Ok(Some(other)) if other._id != page._id => {
+            return Err(format!("Slug '{}' is already taken.", page.slug))
         }
+        Ok(_) => (),
+        Err(err) => return Err(err.to_string()),
+    }
+
+    let checks: Vec<ObjectId> = page
+        .components
+        .iter()
+        .flat_map(|component| component.checks.iter().copied())
+        .collect();
+    ensure_checks_exist(store, caller, &checks).await
+}
+
+/// Fail with a readable message if any of the channels does not exist in the project of the caller
+async fn ensure_channels_exist(
+    store: &Storage,
+    caller: &ApiKey,
+    channels: &[ObjectId],
+) -> Result<(), String> {
+    if channels.is_empty() {
+        return Ok(());
+    }
+
+    let existing: Vec<ObjectId> = match store.channels_by_id(channels).await {
+        Ok(existing) => existing
+            .iter()
+            .filter(|channel| channel.project_id == caller.project_id)
+            .map(|channel| channel._id)
+            .collect(),
+        Err(err) => return Err(err.to_string()),
+    };
+    match channels.iter().find(|id| !existing.contains(id)) {
+        Some(missing) => Err(format!("Channel not found with id '{missing}'")),
+        None => Ok(()),
+    }
+}
+
+async fn ensure_checks_exist(
+    store: &Storage,
+    caller: &ApiKey,
+    checks: &[ObjectId],
+) -> Result<(), String> {
+    if checks.is_empty() {
+        return Ok(());
+    }
+
+    let existing = match store.existing_checks(caller.project_id, checks).await {
+        Ok(existing) => existing,
+        Err(err) => return Err(err.to_string()),
+    };
+    match checks.iter().find(|id| !existing.contains(id)) {
+        Some(missing) => Err(format!("Check not found with id '{missing}'")),
+        None => Ok(()),
     }
 }
@@ -0,0 +1,497 @@
+use regex::Regex;
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+use serde_json_path::JsonPath;
+
+use crate::models::{Assertion, AssertionResult, Comparison};
+
+/// What assertions are evaluated against
+pub(crate) struct Response<'a> {
+    pub(crate) status_code: u16,
+    pub(crate) headers: &'a HeaderMap,
+    pub(crate) body: &'a [u8],
+    pub(crate) latency_ms: u64,
+}
+
+impl Assertion {
+    /// Make sure patterns, paths and schemas compile before the check is saved
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        match self {
+            Assertion::StatusCode(assertion) => match (assertion.min, assertion.max) {
+                (Some(min), Some(max)) if min > max => {
+                    Err(format!("Status code range is empty: {min} > {max}"))
+                }
+                _ => Ok(()),
+            },
+            Assertion::JsonPath(assertion) => {
+                JsonPath::parse(&assertion.path)
+                    .map_err(|err| format!("Invalid JSONPath '{}': {}", assertion.path, err))?;
+                validate_comparison(&assertion.comparison, &assertion.value)
+            }
+            Assertion::JsonPointer(assertion) => {
+                if !assertion.pointer.is_empty() && !assertion.pointer.starts_with('/') {
+                    return Err(format!(
+                        "Invalid JSON Pointer '{}': it must start with '/'",
+                        assertion.pointer
+                    ));
+                }
+                validate_comparison(&assertion.comparison, &assertion.value)
+            }
+            Assertion::JsonSchema(assertion) => jsonschema::validator_for(&assertion.schema)
+                .map(|_| ())
+                .map_err(|err| format!("Invalid JSON Schema: {err}")),
+            Assertion::BodyContains(_) | Assertion::MaxLatency(_) => Ok(()),
+            Assertion::BodyMatches(assertion) => compile(&assertion.pattern).map(|_| ()),
+            Assertion::Header(assertion) => {
+                if let Some(ref pattern) = assertion.pattern {
+                    compile(pattern)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn compile(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|err| format!("Invalid pattern '{pattern}': {err}"))
+}
+
+fn validate_comparison(comparison: &Comparison, value: &Option<Value>) -> Result<(), String> {
+    match (comparison, value) {
+        (Comparison::Exists | Comparison::NotExists, _) | (_, Some(_)) => Ok(()),
+        (_, None) => Err("A value is required to compare against.".to_string()),
+    }
+}
+
+pub(crate) fn has_status_code_assertion(assertions: &[Assertion]) -> bool {
+    assertions
+        .iter()
+        .any(|assertion| matches!(assertion, Assertion::StatusCode(_)))
+}
+
+pub(crate) fn evaluate(assertions: &[Assertion], response: &Response) -> Vec<AssertionResult> {
+    // Only parse the body once, and only if someone needs it as JSON
+    let needs_json = assertions.iter().any(|assertion| {
+        matches!(
+            assertion,
+            Assertion::JsonPath(_) | Assertion::JsonPointer(_) | Assertion::JsonSchema(_)
+        )
+    });
+    let json = if needs_json {
+        Some(serde_json::from_slice::<Value>(response.body).map_err(|err| err.to_string()))
+    } else {
+        None
+    };
+
+    assertions
+        .iter()
+        .map(|assertion| {
+            let outcome = evaluate_one(assertion, response, json.as_ref());
+            AssertionResult {
+                assertion: assertion.clone(),
+                passed: outcome.is_ok(),
+                details: outcome.err(),
+            }
+        })
+        .collect()
+}
+
+fn evaluate_one(
+    assertion: &Assertion,
+    response: &Response,
+    json: Option<&Result<Value, String>>,
+) -> Result<(), String> {
+    let json = || match json {
+        Some(Ok(json)) => Ok(json),
+        Some(Err(err)) => Err(format!("Response body is not valid JSON: {err}")),
+        None => Err("Response body was not parsed".to_string()),
+    };
+
+    match assertion {
+        Assertion::StatusCode(assertion) => {
+            let code = response.status_code;
+            let in_range = assertion.min.is_none_or(|min| code >= min)
+                && assertion.max.is_none_or(|max| code <= max);
+            let in_set = assertion
+                .codes
+                .as_ref()
+                .is_none_or(|codes| codes.contains(&code));
+            if in_range && in_set {
+                Ok(())
+            } else {
+                Err(format!("Unexpected status code '{code}'"))
+            }
+        }
+        Assertion::JsonPath(assertion) => {
+            let path = JsonPath::parse(&assertion.path).map_err(|err| err.to_string())?;
+            let json = json()?;
+            let nodes = path.query(json);
+            compare(
+                &assertion.path,
+                nodes.first(),
+                &assertion.comparison,
+                &assertion.value,
+            )
+        }
+        Assertion::JsonPointer(assertion) => {
+            let json = json()?;
+            compare(
+                &assertion.pointer,
+                json.pointer(&assertion.pointer),
+                &assertion.comparison,
+                &assertion.value,
+            )
+        }
+        Assertion::JsonSchema(assertion) => {
+            let validator =
+                jsonschema::validator_for(&assertion.schema).map_err(|err| err.to_string())?;
+            let json = json()?;
+            let errors: Vec<String> = validator
+                .iter_errors(json)
+                .map(|err| format!("{} at '{}'", err, err.instance_path()))
+                .collect();
+            if errors.is_empty() {
+                Ok(())
+            } else {
+                Err(format!(
+                    "Response body does not match the schema: {}",
+                    errors.join(", ")
+                ))
+            }
+        }
+        Assertion::BodyContains(assertion) => {
+            if String::from_utf8_lossy(response.body).contains(&assertion.substring) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "Response body does not contain '{}'",
+                    assertion.substring
+                ))
+            }
+        }
+        Assertion::BodyMatches(assertion) => {
+            let regex = compile(&assertion.pattern)?;
+            if regex.is_match(&String::from_utf8_lossy(response.body)) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "Response body does not match '{}'",
+                    assertion.pattern
+                ))
+            }
+        }
+        Assertion::Header(assertion) => {
+            let Some(header) = response.headers.get(&assertion.name) else {
+                return Err(format!("Header '{}' is missing", assertion.name));
+            };
+            let header = header
+                .to_str()
+                .map_err(|_| format!("Header '{}' is not valid text", assertion.name))?;
+            if let Some(ref value) = assertion.value {
+                if header != value {
+                    return Err(format!(
+                        "Header '{}' is '{}', expected '{}'",
+                        assertion.name, header, value
+                    ));
+                }
+            }
+            if let Some(ref pattern) = assertion.pattern {
+                if !compile(pattern)?.is_match(header) {
+                    return Err(format!(
+                        "Header '{}' is '{}', which does not match '{}'",
+                        assertion.name, header, pattern
+                    ));
+                }
+            }
+            Ok(())
+        }
+        Assertion::MaxLatency(assertion) => {
+            if response.latency_ms <= assertion.ms {
+                Ok(())
+            } else {
+                Err(format!(
+                    "Response took {} ms, more than the allowed {} ms",
+                    response.latency_ms, assertion.ms
+                ))
+            }
+        }
+    }
+}
+
+fn compare(
+    location: &str,
+    actual: Option<&Value>,
+    comparison: &Comparison,
+    expected: &Option<Value>,
+) -> Result<(), String> {
+    let passed = match (comparison, actual, expected) {
+        (Comparison::Exists, actual, _) => actual.is_some(),
+        (Comparison::NotExists, actual, _) => actual.is_none(),
+        (_, None, _) => return Err(format!("Nothing found at '{location}'")),
+        (_, Some(_), None) => return Err("A value is required to compare against".to_string()),
+        (Comparison::Equals, Some(actual), Some(expected)) => actual == expected,
+        (Comparison::NotEquals, Some(actual), Some(expected)) => actual != expected,
+        (Comparison::GreaterThan, Some(actual), Some(expected)) => {
+            matches!((actual.as_f64(), expected.as_f64()), (Some(a), Some(e)) if a > e)
+        }
+        (Comparison::LessThan, Some(actual), Some(expected)) => {
+            matches!((actual.as_f64(), expected.as_f64()), (Some(a), Some(e)) if a < e)
+        }
+        (Comparison::Contains, Some(actual), Some(expected)) => match (actual, expected) {
+            (Value::String(actual), Value::String(expected)) => actual.contains(expected.as_str()),
+            (Value::Array(actual), expected) => actual.contains(expected),
+            _ => false,
+        },
+    };
+
+    if passed {
+        return Ok(());
+    }
+    let actual = actual.map_or_else(|| "nothing".to_string(), Value::to_string);
+    let expected = match comparison {
+        Comparison::Exists => "to exist".to_string(),
+        Comparison::NotExists => "not to exist".to_string(),
+        _ => {
+            let operator = match comparison {
+                Comparison::NotEquals => "not equal to",
+                Comparison::GreaterThan => "greater than",
+                Comparison::LessThan => "less than",
+                Comparison::Contains => "containing",
+                _ => "equal to",
+            };
+            let value = expected.as_ref().map_or_else(String::new, Value::to_string);
+            format!("to be {operator} {value}")
+        }
+    };
+    Err(format!(
+        "Value at '{location}' is {actual}, expected it {expected}"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+    use serde_json::{json, Value};
+
+    use super::{evaluate, Response};
+    use crate::models::Assertion;
+
+    const BODY: &[u8] =
+        br#"{"status": "up", "version": "1.4.2", "checks": [{"name": "db", "ms": 12}]}"#;
+
+    fn assertion(definition: Value) -> Assertion {
+        serde_json::from_value(definition).unwrap()
+    }
+
+    /// Outcome of the assertion against a 200 JSON response that took 120 ms
+    fn outcome(assertion: &Assertion) -> Result<(), String> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let response = Response {
+            status_code: 200,
+            headers: &headers,
+            body: BODY,
+            latency_ms: 120,
+        };
+        let result = evaluate(std::slice::from_ref(assertion), &response).remove(0);
+        assert_eq!(result.passed, result.details.is_none());
+        result.details.map_or(Ok(()), Err)
+    }
+
+    #[test]
+    fn json_path() {
+        let passing = assertion(json!({
+            "type": "JsonPath",
+            "path": "$.checks[0].ms",
+            "comparison": "LessThan",
+            "value": 50,
+        }));
+        assert_eq!(passing.validate(), Ok(()));
+        assert_eq!(outcome(&passing), Ok(()));
+
+        let failing = assertion(json!({
+            "type": "JsonPath",
+            "path": "$.status",
+            "comparison": "Equals",
+            "value": "down",
+        }));
+        assert_eq!(
+            outcome(&failing),
+            Err(r#"Value at '$.status' is "up", expected it to be equal to "down""#.to_string())
+        );
+        let missing =
+            assertion(json!({"type": "JsonPath", "path": "$.uptime", "comparison": "Exists"}));
+        assert!(outcome(&missing).is_err());
+
+        let invalid = assertion(json!({"type": "JsonPath", "path": "$[", "comparison": "Exists"}));
+        assert!(invalid
+            .validate()
+            .unwrap_err()
+            .starts_with("Invalid JSONPath '$['"));
+        let no_value =
+            assertion(json!({"type": "JsonPath", "path": "$.status", "comparison": "Equals"}));
+        assert!(no_value.validate().is_err());
+    }
+
+    #[test]
+    fn json_pointer() {
+        let passing = assertion(json!({
+            "type": "JsonPointer",
+            "pointer": "/checks/0/name",
+            "comparison": "Equals",
+            "value": "db",
+        }));
+        assert_eq!(passing.validate(), Ok(()));
+        assert_eq!(outcome(&passing), Ok(()));
+
+        let failing = assertion(json!({
+            "type": "JsonPointer",
+            "pointer": "/checks/1",
+            "comparison": "Equals",
+            "value": {},
+        }));
+        assert_eq!(
+            outcome(&failing),
+            Err("Nothing found at '/checks/1'".to_string())
+        );
+
+        let invalid =
+            assertion(json!({"type": "JsonPointer", "pointer": "status", "comparison": "Exists"}));
+        assert!(invalid
+            .validate()
+            .unwrap_err()
+            .contains("must start with '/'"));
+    }
+
+    #[test]
+    fn json_schema() {
+        let passing = assertion(json!({"type": "JsonSchema", "schema": {
+            "type": "object",
+            "required": ["status", "version"],
+            "properties": {"status": {"enum": ["up", "degraded"]}},
+        }}));
+        assert_eq!(passing.validate(), Ok(()));
+        assert_eq!(outcome(&passing), Ok(()));
+
+        let failing = assertion(json!({"type": "JsonSchema", "schema": {
+            "properties": {"version": {"type": "integer"}},
+        }}));
+        let error = outcome(&failing).unwrap_err();
+        assert!(
+            error.starts_with("Response body does not match the schema"),
+            "{error}"
+        );
+        assert!(error.contains("/version"), "{error}");
+
+        let invalid = assertion(json!({"type": "JsonSchema", "schema": {"type": "nothing"}}));
+        assert!(invalid
+            .validate()
+            .unwrap_err()
+            .starts_with("Invalid JSON Schema"));
+    }
+
+    #[test]
+    fn body_matches() {
+        let passing =
+            assertion(json!({"type": "BodyMatches", "pattern": r#""version": "1\.\d+\.\d+""#}));
+        assert_eq!(passing.validate(), Ok(()));
+        assert_eq!(outcome(&passing), Ok(()));
+
+        let failing = assertion(json!({"type": "BodyMatches", "pattern": "maintenance"}));
+        assert_eq!(
+            outcome(&failing),
+            Err("Response body does not match 'maintenance'".to_string())
+        );
+
+        let invalid = assertion(json!({"type": "BodyMatches", "pattern": "(unclosed"}));
+        assert!(invalid
+            .validate()
+            .unwrap_err()
+            .starts_with("Invalid pattern '(unclosed'"));
+    }
+
+    #[test]
+    fn header() {
+        let passing = assertion(json!({
+            "type": "Header",
+            "name": "content-type",
+            "value": "application/json",
+            "pattern": "^application/",
+        }));
+        assert_eq!(passing.validate(), Ok(()));
+        assert_eq!(outcome(&passing), Ok(()));
+
+        let failing =
+            assertion(json!({"type": "Header", "name": "content-type", "pattern": "html"}));
+        assert_eq!(
+            outcome(&failing),
+            Err(
+                "Header 'content-type' is 'application/json', which does not match 'html'"
+                    .to_string()
+            )
+        );
+        let missing = assertion(json!({"type": "Header", "name": "etag"}));
+        assert_eq!(
+            outcome(&missing),
+            Err("Header 'etag' is missing".to_string())
+        );
+
+        let invalid = assertion(json!({"type": "Header", "name": "etag", "pattern": "*"}));
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn status_code() {
+        let passing = assertion(json!({"type": "StatusCode", "min": 200, "max": 299}));
+        assert_eq!(passing.validate(), Ok(()));
+        assert_eq!(outcome(&passing), Ok(()));
+
+        let failing = assertion(json!({"type": "StatusCode", "codes": [201, 204]}));
+        assert_eq!(
+            outcome(&failing),
+            Err("Unexpected status code '200'".to_string())
+        );
+
+        let invalid = assertion(json!({"type": "StatusCode", "min": 500, "max": 400}));
+        assert_eq!(
+            invalid.validate(),
+            Err("Status code range is empty: 500 > 400".to_string())
+        );
+    }
+
+    #[test]
+    fn max_latency() {
+        let passing = assertion(json!({"type": "MaxLatency", "ms": 120}));
+        assert_eq!(passing.validate(), Ok(()));
+        assert_eq!(outcome(&passing), Ok(()));
+
+        let failing = assertion(json!({"type": "MaxLatency", "ms": 100}));
+        assert_eq!(
+            outcome(&failing),
+            Err("Response took 120 ms, more than the allowed 100 ms".to_string())
+        );
+
+        // Every bound is accepted, negative ones are already refused when reading the definition
+        let invalid = serde_json::from_value::<Assertion>(json!({"type": "MaxLatency", "ms": -1}));
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn json_assertions_fail_on_a_body_that_is_not_json() {
+        let assertion =
+            assertion(json!({"type": "JsonPointer", "pointer": "", "comparison": "Exists"}));
+        let headers = HeaderMap::new();
+        let response = Response {
+            status_code: 200,
+            headers: &headers,
+            body: b"<html></html>",
+            latency_ms: 0,
+        };
+        let result = evaluate(&[assertion], &response).remove(0);
+        assert!(!result.passed);
+        assert!(result
+            .details
+            .unwrap()
+            .starts_with("Response body is not valid JSON"));
+    }
+}
@@ -0,0 +1,136 @@
+use bson::oid::ObjectId;
+use chrono::Utc;
+use poem::http::Method;
+use poem_openapi::{auth::Bearer, SecurityScheme};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::models::{ApiKey, ApiKeyScope};
+use crate::projects::DEFAULT_PROJECT_ID;
+use crate::store::{ApiKeyFields, Storage, Store};
+
+const KEY_PREFIX: &str = "um_";
+/// Characters of a key kept in the clear to tell it apart
+const PREFIX_LENGTH: usize = 11;
+/// Shortest key accepted from the configuration
+const MIN_ADMIN_KEY_LENGTH: usize = 16;
+
+/// API key sent as `Authorization: Bearer <key>`, checked by `middlewares::authenticate`
+#[derive(SecurityScheme)]
+#[oai(ty = "bearer", bearer_format = "API key")]
+// Only declares the scheme, the middleware already validated the key
+pub(crate) struct ApiKeyAuth(#[allow(dead_code)] Bearer);
+
+pub(crate) fn generate_key() -> String {
+    format!("{KEY_PREFIX}{}", hex::encode(rand::random::<[u8; 32]>()))
+}
+
+/// Keys are random, a plain hash is enough to keep them from being read back
+pub(crate) fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+impl ApiKey {
+    fn from_key(
+        project_id: ObjectId,
+        name: String,
+        scope: ApiKeyScope,
+        user_id: Option<ObjectId>,
+        key: &str,
+        prefix: String,
+    ) -> Self {
+        Self {
+            _id: ObjectId::new(),
+            project_id,
+            name,
+            scope,
+            prefix,
+            hash: hash_key(key),
+            user_id,
+            created_at: Utc::now(),
+            revoked_at: None,
+        }
+    }
+
+    /// A new key, returned along with the key itself which is not kept
+    pub(crate) fn mint(
+        project_id: ObjectId,
+        name: String,
+        scope: ApiKeyScope,
+        user_id: Option<ObjectId>,
+    ) -> (Self, String) {
+        let key = generate_key();
+        let prefix = key.chars().take(PREFIX_LENGTH).collect();
+        (
+            Self::from_key(project_id, name, scope, user_id, &key, prefix),
+            key,
+        )
+    }
+}
+
+/// Scope needed to call `method` on `path`
+pub(crate) fn required_scope(method: &Method, path: &str) -> ApiKeyScope {
+    let reads = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS);
+    if path.starts_with("/projects") && !reads {
+        ApiKeyScope::Operator
+    } else if path.starts_with("/api-keys") || path.starts_with("/users") {
+        ApiKeyScope::Admin
+    } else if reads {
+        ApiKeyScope::Read
+    } else {
+        ApiKeyScope::Write
+    }
+}
+
+/// Keep the admin key of the configuration so the first projects and keys can be created with it.
+///
+/// It is an operator key of the default project. It is only stored once, revoking it keeps it
+/// refused across restarts.
+pub(crate) async fn bootstrap(store: &Storage, config: &Config) {
+    let Some(ref key) = config.admin_api_key else {
+        match store.api_keys(None).await {
+            Ok(keys) if keys.is_empty() => {
+                warn!("No API key exists, set ADMIN_API_KEY to call the API and mint keys")
+            }
+            Ok(_) => (),
+            Err(err) => warn!("Error reading API keys: {err}"),
+        }
+        return;
+    };
+    assert!(
+        key.len() >= MIN_ADMIN_KEY_LENGTH,
+        "⚠️ ADMIN_API_KEY must be at least {MIN_ADMIN_KEY_LENGTH} characters long"
+    );
+
+    match store.api_key_by_hash(&hash_key(key)).await {
+        // Stored before projects existed, as an admin key
+        Ok(Some(api_key)) if api_key.scope < ApiKeyScope::Operator => {
+            let fields = ApiKeyFields {
+                scope: Some(ApiKeyScope::Operator),
+                ..Default::default()
+            };
+            match store.update_api_key(api_key._id, &fields).await {
+                Ok(_) => info!("Made the admin key of the configuration an operator key"),
+                Err(err) => warn!("Error updating the admin key of the configuration: {err}"),
+            }
+        }
+        Ok(Some(_)) => (),
+        Ok(None) => {
+            // Any part of a key picked by hand could be most of it, none is shown
+            let api_key = ApiKey::from_key(
+                DEFAULT_PROJECT_ID,
+                "ADMIN_API_KEY".to_string(),
+                ApiKeyScope::Operator,
+                None,
+                key,
+                String::new(),
+            );
+            match store.insert_api_key(&api_key).await {
+                Ok(()) => info!("Stored the admin key of the configuration"),
+                Err(err) => warn!("Error storing the admin key of the configuration: {err}"),
+            }
+        }
+        Err(err) => warn!("Error reading API keys: {err}"),
+    }
+}
@@ -0,0 +1,215 @@
+use bson::oid::ObjectId;
+use chrono::Utc;
+use poem::http::header::{AUTHORIZATION, CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH, VARY};
+use poem::http::StatusCode;
+use poem::web::{Data, Path, Query};
+use poem::{handler, Request, Response};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::auth;
+use crate::maintenance;
+use crate::models::{Check, CheckState, StatsWindow};
+use crate::stats;
+use crate::store::{Storage, Store, StoreError};
+
+/// The state changes with every run, the uptime slowly
+const STATUS_MAX_AGE_SECS: u32 = 60;
+const UPTIME_MAX_AGE_SECS: u32 = 300;
+
+const LABEL_COLOR: &str = "#555";
+const GREEN: &str = "#4c1";
+const YELLOW_GREEN: &str = "#97ca00";
+const YELLOW: &str = "#dfb317";
+const RED: &str = "#e05d44";
+const BLUE: &str = "#007ec6";
+const GREY: &str = "#9f9f9f";
+
+pub(crate) fn generate_token() -> String {
+    hex::encode(rand::random::<[u8; 16]>())
+}
+
+pub(crate) fn status_path(check_id: ObjectId) -> String {
+    format!("/badge/{check_id}/status.svg")
+}
+
+pub(crate) fn uptime_path(check_id: ObjectId) -> String {
+    format!("/badge/{check_id}/uptime.svg")
+}
+
+#[derive(Deserialize)]
+pub(crate) struct BadgeQuery {
+    /// Badge token of the check, not needed with an API key of its project
+    token: Option<String>,
+    /// Uptime badges only, defaults to the last 24 hours
+    window: Option<StatsWindow>,
+}
+
+/// Width of the text in Verdana 11px, close enough for the short texts of badges
+fn text_width(text: &str) -> usize {
+    text.chars()
+        .map(|char| match char {
+            'i' | 'l' | '.' | ',' | ':' | '|' | '!' | '\'' | ' ' => 4,
+            'm' | 'w' | 'M' | 'W' | '%' => 10,
+            char if char.is_ascii_uppercase() || char.is_ascii_digit() => 7,
+            _ => 6,
+        })
+        .sum()
+}
+
+/// Flat badge in the style of shields.io, `label` on grey and `message` on `color`
+fn render(label: &str, message: &str, color: &str) -> String {
+    let label_width = text_width(label) + 10;
+    let message_width = text_width(message) + 10;
+    let width = label_width + message_width;
+    // Text is positioned at ten times the scale, as shields does, to keep it sharp
+    let label_x = label_width * 5;
+    let message_x = (label_width * 2 + message_width) * 5;
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}"><title>{label}: {message}</title><linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient><clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath><g clip-path="url(#r)"><rect width="{label_width}" height="20" fill="{LABEL_COLOR}"/><rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/><rect width="{width}" height="20" fill="url(#s)"/></g><g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="110"><text x="{label_x}" y="150" fill="#010101" fill-opacity=".3" transform="scale(.1)">{label}</text><text x="{label_x}" y="140" transform="scale(.1)">{label}</text><text x="{message_x}" y="150" fill="#010101" fill-opacity=".3" transform="scale(.1)">{message}</text><text x="{message_x}" y="140" transform="scale(.1)">{message}</text></g></svg>"##
+    )
+}
+
+fn state_message(check: &Check, in_maintenance: bool) -> (&'static str, &'static str) {
+    if in_maintenance {
+        return ("maintenance", BLUE);
+    }
+    match check.current_state {
+        CheckState::Up => ("up", GREEN),
+        CheckState::Degraded => ("degraded", YELLOW),
+        CheckState::Down => ("down", RED),
+        CheckState::Unknown => ("unknown", GREY),
+    }
+}
+
+fn uptime_color(uptime_percentage: f64) -> &'static str {
+    if uptime_percentage >= 99.9 {
+        GREEN
+    } else if uptime_percentage >= 99.0 {
+        YELLOW_GREEN
+    } else if uptime_percentage >= 95.0 {
+        YELLOW
+    } else {
+        RED
+    }
+}
+
+fn internal_error(err: StoreError) -> poem::Error {
+    poem::Error::from_string(err.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// The check, if the request may see its badges, and whether it may be cached publicly.
+///
+/// Badges are shown to whoever has the badge token of the check or an API key of its project,
+/// to anyone else the check does not exist
+async fn visible_check(
+    store: &Storage,
+    req: &Request,
+    check_id: &str,
+    token: Option<&str>,
+) -> poem::Result<(Check, bool)> {
+    let not_found = || poem::Error::from_string("No such badge", StatusCode::NOT_FOUND);
+    let check_id = ObjectId::parse_str(check_id).map_err(|_| not_found())?;
+    let check = store
+        .check(check_id)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(not_found)?;
+
+    if let (Some(token), Some(badge_token)) = (token, &check.badge_token) {
+        if token == badge_token {
+            return Ok((check, true));
+        }
+    }
+
+    let Some(key) = req
+        .header(AUTHORIZATION)
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return Err(not_found());
+    };
+    match store.api_key_by_hash(&auth::hash_key(key.trim())).await {
+        Ok(Some(api_key))
+            if api_key.revoked_at.is_none() && api_key.project_id == check.project_id =>
+        {
+            Ok((check, false))
+        }
+        Ok(_) => Err(not_found()),
+        Err(err) => Err(internal_error(err)),
+    }
+}
+
+/// Badges are cached for `max_age` seconds, and revalidated with their hash
+fn respond(req: &Request, svg: String, public: bool, max_age: u32) -> Response {
+    let etag = format!("\"{}\"", &hex::encode(Sha256::digest(svg.as_bytes()))[..16]);
+    // Badges seen with an API key must not be kept by shared caches
+    let cache_control = if public {
+        format!("public, max-age={max_age}")
+    } else {
+        format!("private, max-age={max_age}")
+    };
+    let builder = Response::builder()
+        .header(CACHE_CONTROL, cache_control)
+        .header(VARY, "Authorization")
+        .header(ETAG, &etag);
+
+    let matches = req
+        .header(IF_NONE_MATCH)
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+    if matches {
+        return builder.status(StatusCode::NOT_MODIFIED).finish();
+    }
+    builder
+        .header(CONTENT_TYPE, "image/svg+xml; charset=utf-8")
+        .body(svg)
+}
+
+/// Badge with the current state of the check
+#[handler]
+pub(crate) async fn status(
+    req: &Request,
+    Path(check_id): Path<String>,
+    Query(query): Query<BadgeQuery>,
+    Data(store): Data<&Storage>,
+) -> poem::Result<Response> {
+    let (check, public) = visible_check(store, req, &check_id, query.token.as_deref()).await?;
+    let in_maintenance = maintenance::open_window(store, &check)
+        .await
+        .map_err(internal_error)?
+        .is_some();
+
+    let (message, color) = state_message(&check, in_maintenance);
+    let svg = render("status", message, color);
+    Ok(respond(req, svg, public, STATUS_MAX_AGE_SECS))
+}
+
+/// Badge with the uptime of the check over the window, computed as its stats are
+#[handler]
+pub(crate) async fn uptime(
+    req: &Request,
+    Path(check_id): Path<String>,
+    Query(query): Query<BadgeQuery>,
+    Data(store): Data<&Storage>,
+) -> poem::Result<Response> {
+    let window = query.window.unwrap_or_default();
+    let Some(duration) = window.duration() else {
+        return Err(poem::Error::from_string(
+            "Badges cover the last 24h, 7d or 30d",
+            StatusCode::BAD_REQUEST,
+        ));
+    };
+    let (check, public) = visible_check(store, req, &check_id, query.token.as_deref()).await?;
+
+    let now = Utc::now();
+    let stats = stats::read(store, &check, now - duration, now)
+        .await
+        .map_err(internal_error)?;
+    let label = match window {
+        StatsWindow::Day => "uptime 24h",
+        StatsWindow::Week => "uptime 7d",
+        StatsWindow::Month | StatsWindow::Custom => "uptime 30d",
+    };
+    let message = format!("{:.2}%", stats.uptime_percentage);
+    let svg = render(label, &message, uptime_color(stats.uptime_percentage));
+    Ok(respond(req, svg, public, UPTIME_MAX_AGE_SECS))
+}
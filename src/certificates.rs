@@ -0,0 +1,216 @@
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{
+    ClientConfig, DigitallySignedStruct, ProtocolVersion, RootCertStore, SignatureScheme,
+};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::models::{CertificateInfo, TlsInfo};
+
+static PROVIDER: LazyLock<Arc<CryptoProvider>> =
+    LazyLock::new(|| Arc::new(ring::default_provider()));
+
+static ROOTS: LazyLock<Arc<RootCertStore>> = LazyLock::new(|| {
+    Arc::new(RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    })
+});
+
+/// What the server presented during the handshake, and whether webpki accepted it
+#[derive(Debug)]
+struct Captured {
+    chain: Vec<CertificateDer<'static>>,
+    error: Option<String>,
+}
+
+/// Accepts any certificate so broken ones can be inspected too, the real
+/// verification outcome is captured instead of aborting the handshake.
+#[derive(Debug)]
+struct CapturingVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    captured: Arc<Mutex<Option<Captured>>>,
+}
+
+impl ServerCertVerifier for CapturingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        );
+        let chain = std::iter::once(end_entity)
+            .chain(intermediates)
+            .map(|cert| cert.clone().into_owned())
+            .collect();
+        *self.captured.lock().unwrap() = Some(Captured {
+            chain,
+            error: verified.err().map(|err| err.to_string()),
+        });
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// Open a TLS connection to `host` and describe the certificate it presents
+pub(crate) async fn inspect(host: &str, port: u16, timeout: Duration) -> Result<TlsInfo, String> {
+    let server_name =
+        ServerName::try_from(host.to_string()).map_err(|err| format!("Invalid host: {err}"))?;
+    let inner = WebPkiServerVerifier::builder_with_provider(ROOTS.clone(), PROVIDER.clone())
+        .build()
+        .map_err(|err| err.to_string())?;
+    let captured = Arc::new(Mutex::new(None));
+    let verifier = CapturingVerifier {
+        inner,
+        captured: captured.clone(),
+    };
+    let config = ClientConfig::builder_with_provider(PROVIDER.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|err| err.to_string())?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+
+    let handshake = async {
+        let stream = TcpStream::connect((host, port)).await?;
+        TlsConnector::from(Arc::new(config))
+            .connect(server_name.clone(), stream)
+            .await
+    };
+    let stream = tokio::time::timeout(timeout, handshake)
+        .await
+        .map_err(|_| "TLS handshake timed out".to_string())?
+        .map_err(|err| format!("TLS handshake failed: {err}"))?;
+    let protocol = stream
+        .get_ref()
+        .1
+        .protocol_version()
+        .map(|version| match version {
+            ProtocolVersion::TLSv1_3 => "TLSv1.3".to_string(),
+            ProtocolVersion::TLSv1_2 => "TLSv1.2".to_string(),
+            version => format!("{version:?}"),
+        });
+
+    let Some(captured) = captured.lock().unwrap().take() else {
+        return Err("Server did not present a certificate".to_string());
+    };
+    let hostname_matches = captured.chain.first().is_some_and(|leaf| {
+        webpki::EndEntityCert::try_from(leaf)
+            .and_then(|cert| cert.verify_is_valid_for_subject_name(&server_name))
+            .is_ok()
+    });
+    let chain: Vec<CertificateInfo> = captured
+        .chain
+        .iter()
+        .filter_map(|cert| describe(cert))
+        .collect();
+    let expires_at = chain.first().map(|leaf| leaf.not_after);
+
+    Ok(TlsInfo {
+        protocol,
+        expires_at,
+        days_remaining: expires_at.map(|at| (at - Utc::now()).num_days()),
+        chain,
+        trusted: captured.error.is_none(),
+        hostname_matches,
+        error: captured.error,
+        problems: vec![],
+    })
+}
+
+fn describe(der: &CertificateDer<'_>) -> Option<CertificateInfo> {
+    let (_, cert) = X509Certificate::from_der(der).ok()?;
+    let sans = match cert.subject_alternative_name() {
+        Ok(Some(extension)) => extension
+            .value
+            .general_names
+            .iter()
+            .filter_map(|name| match name {
+                GeneralName::DNSName(name) => Some(name.to_string()),
+                GeneralName::IPAddress(bytes) => ip_address(bytes),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    };
+    let validity = cert.validity();
+    Some(CertificateInfo {
+        subject: cert.subject().to_string(),
+        issuer: cert.issuer().to_string(),
+        serial: cert.raw_serial_as_string(),
+        not_before: DateTime::from_timestamp(validity.not_before.timestamp(), 0)?,
+        not_after: DateTime::from_timestamp(validity.not_after.timestamp(), 0)?,
+        sans,
+    })
+}
+
+fn ip_address(bytes: &[u8]) -> Option<String> {
+    match bytes.len() {
+        4 => <[u8; 4]>::try_from(bytes)
+            .ok()
+            .map(|octets| std::net::Ipv4Addr::from(octets).to_string()),
+        16 => <[u8; 16]>::try_from(bytes)
+            .ok()
+            .map(|octets| std::net::Ipv6Addr::from(octets).to_string()),
+        _ => None,
+    }
+}
+
+impl TlsInfo {
+    /// Fill `problems` with whatever is wrong with the certificate
+    pub(crate) fn evaluate(&mut self, expiry_days: u32) {
+        let mut problems = vec![];
+        if let Some(ref error) = self.error {
+            problems.push(format!("Certificate is not valid: {error}"));
+        }
+        if !self.hostname_matches {
+            problems.push("Certificate does not match the host name".to_string());
+        }
+        match self.days_remaining {
+            Some(days) if days < 0 => problems.push("Certificate has expired".to_string()),
+            Some(days) if days < expiry_days as i64 => {
+                problems.push(format!("Certificate expires in {days} days"))
+            }
+            _ => {}
+        }
+        self.problems = problems;
+    }
+}
@@ -2,6 +2,8 @@ use dotenv::dotenv;
 use envconfig::Envconfig;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Longest retention honored, about 68 years: the most MongoDB expiration indexes take
+const MAX_RETENTION_DAYS: u64 = i32::MAX as u64 / (24 * 60 * 60);
 
 #[derive(Envconfig, Clone)]
 pub(crate) struct Config {
@@ -14,12 +16,47 @@ pub(crate) struct Config {
     #[envconfig(from = "LOG_LEVEL", default = "info")]
     pub(crate) log_level: String,
 
+    /// Where data is kept: `mongo`, `sqlite`, or `sqlite::memory` for an SQLite database
+    /// only held in memory and lost on restart
+    #[envconfig(from = "STORE", default = "mongo")]
+    pub(crate) store: String,
+
+    /// File the `sqlite` store writes to
+    #[envconfig(from = "SQLITE_PATH", default = "uptime-monitor.db")]
+    pub(crate) sqlite_path: String,
+
+    /// Admin API key accepted from the start, to mint the first keys with
+    #[envconfig(from = "ADMIN_API_KEY")]
+    pub(crate) admin_api_key: Option<String>,
+
     #[envconfig(from = "DB_URI", default = "mongodb://localhost:27017")]
     pub(crate) db_uri: String,
 
     #[envconfig(from = "DB_NAME", default = "uptime-monitor")]
     pub(crate) db_name: String,
 
+    #[envconfig(from = "MAX_CONCURRENT_CHECKS", default = "64")]
+    pub(crate) max_concurrent_checks: usize,
+
+    #[envconfig(from = "MAX_CONCURRENT_CHECKS_PER_HOST", default = "4")]
+    pub(crate) max_concurrent_checks_per_host: usize,
+
+    /// Days before expiry certificates are reported, checks can override it
+    #[envconfig(from = "CERTIFICATE_EXPIRY_DAYS", default = "14")]
+    pub(crate) certificate_expiry_days: u32,
+
+    /// Days raw history entries are kept, 0 keeps them forever, capped at 68 years
+    #[envconfig(from = "HISTORY_RETENTION_DAYS", default = "30")]
+    pub(crate) history_retention_days: u64,
+
+    /// Days one minute rollups are kept, 0 keeps them forever, capped at 68 years
+    #[envconfig(from = "MINUTE_ROLLUP_RETENTION_DAYS", default = "90")]
+    pub(crate) minute_rollup_retention_days: u64,
+
+    /// Days one hour rollups are kept, 0 keeps them forever, capped at 68 years
+    #[envconfig(from = "HOUR_ROLLUP_RETENTION_DAYS", default = "365")]
+    pub(crate) hour_rollup_retention_days: u64,
+
     #[envconfig(from = "VERSION", default = "1.0.0")]
     pub(crate) version: String,
 }
@@ -31,6 +68,13 @@ impl Config {
         let version_parts: Vec<&str> = VERSION.split(".").collect();
         assert_eq!(version_parts.len(), 3, "⚠️ version is malformed");
         config.version = format!("{}.{}", version_parts[0], version_parts[1]);
+        for retention in [
+            &mut config.history_retention_days,
+            &mut config.minute_rollup_retention_days,
+            &mut config.hour_rollup_retention_days,
+        ] {
+            *retention = (*retention).min(MAX_RETENTION_DAYS);
+        }
         config
     }
 }
@@ -1,8 +1,27 @@
+use std::time::Duration;
+
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
+use bson::{doc, Document};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use tracing::{info, warn};
+
 use crate::config::Config;
-use mongodb::{Client, Database};
+use crate::deliveries;
+use crate::metrics;
+use crate::models::{
+    ApiKey, ChannelConfig, Check, CheckHistory, HeartbeatPing, Incident, MaintenanceWindow,
+    NewNotificationChannel, NotificationChannel, Rollup, StatusPage, WebhookChannel,
+};
+use crate::projects;
+use crate::store::{MongoStore, SqliteStore, Storage};
+use crate::timestamps;
+use mongodb::event::command::CommandEvent;
+use mongodb::event::EventHandler;
+use mongodb::options::{ClientOptions, IndexOptions};
+use mongodb::{Client, Database, IndexModel};
 
 pub(crate) fn log(config: &Config) {
     let level_filter = match config.log_level.as_str() {
@@ -22,12 +41,300 @@ pub(crate) fn log(config: &Config) {
         .init();
 }
 
-pub(crate) async fn db(config: &Config) -> Database {
-    let client = Client::with_uri_str(&config.db_uri)
+/// Open the store picked by the configuration, preparing MongoDB when it is the one
+pub(crate) async fn store(config: &Config) -> Storage {
+    match config.store.as_str() {
+        "mongo" => {
+            let db = db(config).await;
+            migrate(&db).await;
+            convert_dates(&db).await;
+            indexes(&db, config).await;
+            Storage::Mongo(MongoStore::new(db))
+        }
+        "sqlite" => Storage::Sqlite(
+            SqliteStore::open(&config.sqlite_path).unwrap_or_else(|err| {
+                panic!(
+                    "Unable to open SQLite database {}: {err}",
+                    config.sqlite_path
+                )
+            }),
+        ),
+        "sqlite::memory" => Storage::Sqlite(
+            SqliteStore::in_memory()
+                .unwrap_or_else(|err| panic!("Unable to create in-memory database: {err}")),
+        ),
+        store => panic!("Unknown store '{store}', expected 'mongo', 'sqlite' or 'sqlite::memory'"),
+    }
+}
+
+async fn db(config: &Config) -> Database {
+    let mut options = ClientOptions::parse(&config.db_uri)
         .await
-        .expect(&format!("Invalid connection URI: {}", config.db_uri));
-    let db = client.database(&config.db_name);
+        .unwrap_or_else(|_| panic!("Invalid connection URI: {}", config.db_uri));
+    options.command_event_handler = Some(EventHandler::callback(|event| {
+        if let CommandEvent::Failed(event) = event {
+            metrics::count_mongo_error(&event.command_name);
+        }
+    }));
+    let client = Client::with_options(options)
+        .unwrap_or_else(|_| panic!("Invalid connection URI: {}", config.db_uri));
+    client.database(&config.db_name)
+}
+
+/// Checks used to embed a single webhook URL, move each one to a webhook channel
+async fn migrate(db: &Database) {
+    let checks = db.collection::<Document>("checks");
+    let channels = db.collection::<NotificationChannel>("notification_channels");
+
+    let legacy: Vec<Document> = match checks.find(doc! {"hook": {"$type": "string"}}).await {
+        Ok(cursor) => cursor.try_collect().await.unwrap_or_default(),
+        Err(err) => {
+            warn!("Error fetching checks with a legacy hook: {err}");
+            return;
+        }
+    };
+
+    for check in legacy {
+        let (Ok(check_id), Ok(hook)) = (check.get_object_id("_id"), check.get_str("hook")) else {
+            continue;
+        };
+        let project_id = check
+            .get_object_id("project_id")
+            .unwrap_or(projects::DEFAULT_PROJECT_ID);
+        let channel = NotificationChannel::from_new(
+            NewNotificationChannel {
+                name: format!("Webhook {hook}"),
+                config: ChannelConfig::Webhook(WebhookChannel {
+                    url: hook.to_string(),
+                    headers: None,
+                    secret: Some(deliveries::generate_secret()),
+                    template: None,
+                }),
+            },
+            project_id,
+        );
+        if let Err(err) = channels.insert_one(&channel).await {
+            warn!("Error creating channel for the hook of check '{check_id}': {err}");
+            continue;
+        }
+        let update = checks
+            .update_one(
+                doc! {"_id": check_id},
+                doc! {
+                    "$addToSet": {"channels": channel._id},
+                    "$unset": {"hook": ""},
+                },
+            )
+            .await;
+        match update {
+            Ok(_) => info!(
+                "Moved the hook of check '{check_id}' to channel '{}'",
+                channel._id
+            ),
+            Err(err) => warn!("Error moving the hook of check '{check_id}': {err}"),
+        }
+    }
+}
+
+/// Fields MongoDB filters, sorts or expires on, per collection
+const DATE_FIELDS: [(&str, &[&str]); 11] = [
+    ("checks", &["created_at", "updated_at", "last_run_at"]),
+    ("checks_history", &["created_at"]),
+    ("checks_rollups", &["bucket", "expires_at"]),
+    ("incidents", &["started_at", "resolved_at"]),
+    ("notification_channels", &["created_at"]),
+    ("webhook_deliveries", &["next_attempt_at", "created_at"]),
+    ("heartbeat_pings", &["received_at"]),
+    (
+        "maintenance_windows",
+        &["starts_at", "ends_at", "created_at"],
+    ),
+    ("api_keys", &["created_at"]),
+    ("projects", &["created_at"]),
+    ("status_pages", &["created_at"]),
+];
+
+/// Dates used to be stored as RFC 3339 strings, which MongoDB neither compares as dates nor
+/// expires. Store those it relies on as dates, the others are read either way
+async fn convert_dates(db: &Database) {
+    for (name, fields) in DATE_FIELDS {
+        let collection = db.collection::<Document>(name);
+        let filter = doc! {
+            "$or": fields
+                .iter()
+                .map(|field| doc! {*field: {"$type": "string"}})
+                .collect::<Vec<_>>(),
+        };
+        let documents: Vec<Document> = match collection.find(filter).await {
+            Ok(cursor) => cursor.try_collect().await.unwrap_or_default(),
+            Err(err) => {
+                warn!("Error fetching {name} with dates to convert: {err}");
+                continue;
+            }
+        };
+
+        let mut converted = 0;
+        for document in documents {
+            let Some(id) = document.get("_id").cloned() else {
+                continue;
+            };
+            let mut fields_set = doc! {};
+            for field in fields {
+                let Ok(at) = document.get_str(field) else {
+                    continue;
+                };
+                match DateTime::parse_from_rfc3339(at) {
+                    Ok(at) => {
+                        fields_set.insert(*field, timestamps::to_bson(at.with_timezone(&Utc)));
+                    }
+                    Err(err) => warn!("Invalid date '{at}' in {name} '{id}': {err}"),
+                }
+            }
+            if fields_set.is_empty() {
+                continue;
+            }
+            match collection
+                .update_one(doc! {"_id": id.clone()}, doc! {"$set": fields_set})
+                .await
+            {
+                Ok(_) => converted += 1,
+                Err(err) => warn!("Error converting the dates of {name} '{id}': {err}"),
+            }
+        }
+        if converted > 0 {
+            info!("Stored the dates of {converted} {name} as dates");
+        }
+    }
+}
+
+/// Create the indexes queries rely on, existing ones are left untouched
+async fn indexes(db: &Database, config: &Config) {
+    let history = db.collection::<CheckHistory>("checks_history");
+    let index = IndexModel::builder()
+        .keys(doc! {"check_id": 1, "created_at": 1})
+        .build();
+    if let Err(err) = history.create_index(index).await {
+        warn!("Error creating the checks history index: {err}");
+    }
+
+    let checks = db.collection::<Check>("checks");
+    let index = IndexModel::builder().keys(doc! {"tags": 1}).build();
+    if let Err(err) = checks.create_index(index).await {
+        warn!("Error creating the checks tags index: {err}");
+    }
+    let index = IndexModel::builder().keys(doc! {"project_id": 1}).build();
+    if let Err(err) = checks.create_index(index).await {
+        warn!("Error creating the checks project index: {err}");
+    }
+
+    let incidents = db.collection::<Incident>("incidents");
+    let index = IndexModel::builder()
+        .keys(doc! {"project_id": 1, "started_at": -1})
+        .build();
+    if let Err(err) = incidents.create_index(index).await {
+        warn!("Error creating the incidents project index: {err}");
+    }
+
+    // Pings find their check by token
+    let index = IndexModel::builder()
+        .keys(doc! {"heartbeat.token": 1})
+        .options(IndexOptions::builder().unique(true).sparse(true).build())
+        .build();
+    if let Err(err) = checks.create_index(index).await {
+        warn!("Error creating the checks heartbeat index: {err}");
+    }
+
+    let pings = db.collection::<HeartbeatPing>("heartbeat_pings");
+    let index = IndexModel::builder()
+        .keys(doc! {"check_id": 1, "received_at": 1})
+        .build();
+    if let Err(err) = pings.create_index(index).await {
+        warn!("Error creating the heartbeat pings index: {err}");
+    }
+
+    let windows = db.collection::<MaintenanceWindow>("maintenance_windows");
+    for keys in [doc! {"checks": 1}, doc! {"tags": 1}] {
+        let index = IndexModel::builder().keys(keys).build();
+        if let Err(err) = windows.create_index(index).await {
+            warn!("Error creating the maintenance windows index: {err}");
+        }
+    }
+
+    // Requests find their key by hash
+    let api_keys = db.collection::<ApiKey>("api_keys");
+    let index = IndexModel::builder()
+        .keys(doc! {"hash": 1})
+        .options(IndexOptions::builder().unique(true).build())
+        .build();
+    if let Err(err) = api_keys.create_index(index).await {
+        warn!("Error creating the API keys index: {err}");
+    }
+
+    // Status pages are found by slug, which is part of their public URL
+    let status_pages = db.collection::<StatusPage>("status_pages");
+    let index = IndexModel::builder()
+        .keys(doc! {"slug": 1})
+        .options(IndexOptions::builder().unique(true).build())
+        .build();
+    if let Err(err) = status_pages.create_index(index).await {
+        warn!("Error creating the status pages index: {err}");
+    }
+
+    let rollups = db.collection::<Rollup>("checks_rollups");
+    let index = IndexModel::builder()
+        .keys(doc! {"check_id": 1, "resolution": 1, "bucket": 1})
+        .options(IndexOptions::builder().unique(true).build())
+        .build();
+    if let Err(err) = rollups.create_index(index).await {
+        warn!("Error creating the checks rollups index: {err}");
+    }
+    let index = IndexModel::builder()
+        .keys(doc! {"expires_at": 1})
+        .options(IndexOptions::builder().expire_after(Duration::ZERO).build())
+        .build();
+    if let Err(err) = rollups.create_index(index).await {
+        warn!("Error creating the checks rollups expiration index: {err}");
+    }
+
+    retain_history(db, config.history_retention_days).await;
+}
+
+/// Let MongoDB remove raw history entries once they are older than `days`
+async fn retain_history(db: &Database, days: u64) {
+    const INDEX: &str = "created_at_ttl";
+    let history = db.collection::<CheckHistory>("checks_history");
+    if days == 0 {
+        // Keeping history forever, drop the expiration set by a previous configuration
+        if history.drop_index(INDEX).await.is_ok() {
+            info!("History retention disabled");
+        }
+        return;
+    }
 
+    // Retention is capped when configured, saturate rather than overflow anyway
+    let expire_after = Duration::from_secs(days.saturating_mul(24 * 60 * 60));
+    let index = IndexModel::builder()
+        .keys(doc! {"created_at": 1})
+        .options(
+            IndexOptions::builder()
+                .name(INDEX.to_string())
+                .expire_after(expire_after)
+                .build(),
+        )
+        .build();
+    if history.create_index(index).await.is_ok() {
+        return;
+    }
 
-    db
+    // The index exists with another retention, update it in place
+    let update = db
+        .run_command(doc! {
+            "collMod": "checks_history",
+            "index": {"name": INDEX, "expireAfterSeconds": expire_after.as_secs() as i64},
+        })
+        .await;
+    match update {
+        Ok(_) => info!("History retention set to {days} days"),
+        Err(err) => warn!("Error setting the history retention: {err}"),
+    }
 }
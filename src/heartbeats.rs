@@ -0,0 +1,243 @@
+use bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use poem::http::StatusCode;
+use poem::web::{Data, Path};
+use poem::{handler, Body, Request};
+use tracing::warn;
+
+use crate::models::{Check, CheckKind, Heartbeat, HeartbeatPing, HeartbeatSignal};
+use crate::scheduler::Scheduler;
+use crate::store::{CheckFields, Storage, Store, StoreError};
+
+const DEFAULT_GRACE_SECS: u64 = 60;
+/// Longest a heartbeat may be late or a started job may run, 30 days
+const MAX_GRACE_SECS: u64 = 30 * 24 * 60 * 60;
+/// Largest ping body accepted
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+/// Part of the ping body kept in the log
+const MAX_PAYLOAD_BYTES: usize = 10 * 1024;
+
+pub(crate) fn generate_token() -> String {
+    hex::encode(rand::random::<[u8; 16]>())
+}
+
+pub(crate) fn ping_path(token: &str) -> String {
+    format!("/heartbeat/{token}")
+}
+
+impl Check {
+    /// Heartbeat checks are pinged at a URL of their own, issue one if the check has none
+    pub(crate) fn ensure_heartbeat(&mut self) {
+        if self.kind != CheckKind::Heartbeat {
+            self.heartbeat = None;
+            return;
+        }
+        if self.heartbeat.is_none() {
+            let token = generate_token();
+            self.url = ping_path(&token);
+            self.heartbeat = Some(Heartbeat {
+                token,
+                last_ping_at: None,
+                last_signal: None,
+                started_at: None,
+                duration_ms: None,
+            });
+        }
+    }
+}
+
+impl Heartbeat {
+    fn record(&mut self, signal: &HeartbeatSignal, at: DateTime<Utc>) {
+        self.last_ping_at = Some(at);
+        self.last_signal = Some(signal.clone());
+        if *signal == HeartbeatSignal::Start {
+            self.started_at = Some(at);
+            self.duration_ms = None;
+        } else {
+            self.duration_ms = self
+                .started_at
+                .take()
+                .and_then(|started_at| (at - started_at).to_std().ok())
+                .map(|duration| duration.as_millis() as u64);
+        }
+    }
+}
+
+pub(crate) fn validate_grace(grace_secs: Option<u64>) -> Result<(), String> {
+    match grace_secs {
+        Some(grace_secs) if grace_secs > MAX_GRACE_SECS => Err(format!(
+            "Grace time must be at most {MAX_GRACE_SECS} seconds."
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn grace(check: &Check) -> chrono::Duration {
+    let grace_secs = check
+        .grace_secs
+        .unwrap_or(DEFAULT_GRACE_SECS)
+        .min(MAX_GRACE_SECS);
+    chrono::Duration::seconds(grace_secs as i64)
+}
+
+/// Moment the check fails unless it hears from the job again.
+///
+/// A started job has the grace time to finish, otherwise the next ping is
+/// expected one period after the last one, or after the check was created.
+pub(crate) fn deadline(check: &Check, heartbeat: &Heartbeat) -> Option<DateTime<Utc>> {
+    match heartbeat.started_at {
+        Some(started_at) => started_at.checked_add_signed(grace(check)),
+        None => check
+            .frequency
+            .next_after(heartbeat.last_ping_at.unwrap_or(check.created_at))
+            .and_then(|expected_at| expected_at.checked_add_signed(grace(check))),
+    }
+}
+
+/// Heartbeat checks are evaluated right after a ping and once the deadline
+/// passes, then once per period for as long as the job stays silent
+pub(crate) fn next_evaluation(check: &Check, heartbeat: &Heartbeat) -> Option<DateTime<Utc>> {
+    let pinged_since_last_run = heartbeat
+        .last_ping_at
+        .is_some_and(|ping_at| check.last_run_at.is_none_or(|run_at| ping_at > run_at));
+    if pinged_since_last_run && heartbeat.started_at.is_none() {
+        return Some(Utc::now());
+    }
+
+    let deadline = deadline(check, heartbeat)?;
+    match check.last_run_at {
+        Some(last_run_at) if last_run_at >= deadline => check.frequency.next_after(last_run_at),
+        _ => Some(deadline),
+    }
+}
+
+/// Whether the job is on time and did not report a failure
+pub(crate) fn evaluate(check: &Check, heartbeat: &Heartbeat) -> Result<(), String> {
+    let now = Utc::now();
+    match deadline(check, heartbeat) {
+        Some(deadline) if now >= deadline => {
+            Err(match (heartbeat.started_at, heartbeat.last_ping_at) {
+                (Some(started_at), _) => format!(
+                    "Job started at {} and did not finish within {} seconds",
+                    started_at.to_rfc3339(),
+                    grace(check).num_seconds()
+                ),
+                (None, Some(last_ping_at)) => {
+                    format!("No ping received since {}", last_ping_at.to_rfc3339())
+                }
+                (None, None) => "No ping received yet".to_string(),
+            })
+        }
+        _ if heartbeat.last_signal == Some(HeartbeatSignal::Fail) => Err(format!(
+            "Job reported a failure at {}",
+            heartbeat.last_ping_at.unwrap_or(now).to_rfc3339()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Record that the job completed
+#[handler]
+pub(crate) async fn complete(
+    request: &Request,
+    Path(token): Path<String>,
+    Data(store): Data<&Storage>,
+    Data(scheduler): Data<&Scheduler>,
+    body: Body,
+) -> poem::Result<&'static str> {
+    record_ping(
+        request,
+        &token,
+        HeartbeatSignal::Success,
+        store,
+        scheduler,
+        body,
+    )
+    .await
+}
+
+/// Record that the job started, completed or failed
+#[handler]
+pub(crate) async fn report(
+    request: &Request,
+    Path((token, signal)): Path<(String, String)>,
+    Data(store): Data<&Storage>,
+    Data(scheduler): Data<&Scheduler>,
+    body: Body,
+) -> poem::Result<&'static str> {
+    let signal = match signal.as_str() {
+        "start" => HeartbeatSignal::Start,
+        "success" => HeartbeatSignal::Success,
+        "fail" => HeartbeatSignal::Fail,
+        _ => {
+            return Err(poem::Error::from_string(
+                format!("Unknown signal '{signal}', expected 'start', 'success' or 'fail'"),
+                StatusCode::NOT_FOUND,
+            ))
+        }
+    };
+    record_ping(request, &token, signal, store, scheduler, body).await
+}
+
+async fn record_ping(
+    request: &Request,
+    token: &str,
+    signal: HeartbeatSignal,
+    store: &Storage,
+    scheduler: &Scheduler,
+    body: Body,
+) -> poem::Result<&'static str> {
+    let body = body.into_bytes_limit(MAX_BODY_BYTES).await?;
+    let internal_error = |err: StoreError| {
+        poem::Error::from_string(err.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+    };
+
+    let check = store.check_by_token(token).await.map_err(internal_error)?;
+    let Some(mut check) =
+        check.filter(|check| check.kind == CheckKind::Heartbeat && check.heartbeat.is_some())
+    else {
+        return Err(poem::Error::from_string(
+            "No heartbeat check found with this token",
+            StatusCode::NOT_FOUND,
+        ));
+    };
+    let now = Utc::now();
+    if let Some(ref mut heartbeat) = check.heartbeat {
+        heartbeat.record(&signal, now);
+    }
+    store
+        .update_check(
+            check._id,
+            &CheckFields {
+                heartbeat: Some(check.heartbeat.clone()),
+                ..Default::default()
+            },
+        )
+        .await
+        .map_err(internal_error)?;
+
+    let truncated = body.len() > MAX_PAYLOAD_BYTES;
+    let payload = String::from_utf8_lossy(&body[..body.len().min(MAX_PAYLOAD_BYTES)]);
+    let ping = HeartbeatPing {
+        _id: ObjectId::new(),
+        check_id: check._id,
+        signal,
+        payload: (!payload.is_empty()).then(|| payload.into_owned()),
+        truncated,
+        duration_ms: check
+            .heartbeat
+            .as_ref()
+            .and_then(|heartbeat| heartbeat.duration_ms),
+        remote_addr: Some(request.remote_addr().to_string()),
+        user_agent: request
+            .header("User-Agent")
+            .map(|user_agent| user_agent.to_string()),
+        received_at: now,
+    };
+    if let Err(err) = store.insert_ping(&ping).await {
+        warn!("Error saving ping for check '{}': {err}", check._id);
+    }
+
+    scheduler.schedule(check);
+    Ok("OK")
+}
@@ -1,15 +1,34 @@
 mod api;
+mod assertions;
+mod auth;
+mod badges;
+mod certificates;
 mod config;
+mod deliveries;
 mod dependencies;
+mod heartbeats;
+mod maintenance;
+mod metrics;
 mod middlewares;
 mod models;
 mod monitor;
+mod notifiers;
+mod pagination;
+mod projects;
+mod rollups;
+mod scheduler;
+mod stats;
+mod status_page;
+mod store;
+mod timestamps;
 
-use poem::{handler, listener::TcpListener, middleware::AddData, EndpointExt, Route};
+use poem::{handler, listener::TcpListener, middleware::AddData, post, EndpointExt, Route};
 use poem_openapi::OpenApiService;
 
 use api::MonitorAPI;
 use config::Config;
+use scheduler::Scheduler;
+use status_page::PageCache;
 use tokio::{fs::File, io::AsyncReadExt};
 
 #[handler]
@@ -23,7 +42,7 @@ async fn favicon_handler() -> Vec<u8> {
                 Err(_) => vec![],
             }
         }
-        Err(_) => return vec![],
+        Err(_) => vec![],
     }
 }
 
@@ -34,10 +53,15 @@ async fn main() -> Result<(), std::io::Error> {
 
     // Init dependencies
     dependencies::log(&config);
-    let db = dependencies::db(&config).await;
+    let store = dependencies::store(&config).await;
+    projects::bootstrap(&store).await;
+    auth::bootstrap(&store, &config).await;
 
     // Spawn monitor process
-    tokio::spawn(monitor::start(db.clone()));
+    let (scheduler, commands) = Scheduler::new();
+    tokio::spawn(monitor::start(store.clone(), config.clone(), commands));
+    tokio::spawn(deliveries::start(store.clone()));
+    tokio::spawn(rollups::start(store.clone(), config.clone()));
 
     // Setup service
     let api_service = OpenApiService::new(MonitorAPI, "Uptime Monitor 📢 ", config.version);
@@ -45,11 +69,27 @@ async fn main() -> Result<(), std::io::Error> {
     let redoc = api_service.redoc();
     let app = Route::new()
         .nest("/favicon.ico", favicon_handler)
-        .nest("/", api_service)
+        .at("/metrics", metrics::export)
+        .at(
+            "/heartbeat/:token",
+            post(heartbeats::complete).get(heartbeats::complete),
+        )
+        .at(
+            "/heartbeat/:token/:signal",
+            post(heartbeats::report).get(heartbeats::report),
+        )
+        .at("/status/:slug", status_page::page_html)
+        .at("/status/:slug/json", status_page::page_json)
+        .at("/status-assets/:name", status_page::asset)
+        .at("/badge/:check_id/status.svg", badges::status)
+        .at("/badge/:check_id/uptime.svg", badges::uptime)
+        .nest("/", api_service.around(middlewares::authenticate))
         .nest("/docs", swagger)
         .nest("/redoc", redoc)
         .around(middlewares::log)
-        .with(AddData::new(db));
+        .with(AddData::new(store))
+        .with(AddData::new(scheduler))
+        .with(AddData::new(PageCache::default()));
 
     // Start server
     let address = format!("{}:{}", config.addr, config.port);
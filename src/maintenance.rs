@@ -0,0 +1,170 @@
+use chrono::{DateTime, Utc};
+
+use crate::models::{Check, MaintenanceWindow};
+use crate::store::{self, Storage, Store};
+
+/// Occurrences computed for a single range, so an every-minute window over years stays cheap
+const MAX_OCCURRENCES: usize = 10_000;
+/// Longest a recurring window stays open, a year
+const MAX_DURATION_SECS: u64 = 366 * 24 * 60 * 60;
+
+impl MaintenanceWindow {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.checks.is_empty() && self.tags.is_empty() {
+            return Err("A maintenance window needs checks or tags to cover.".to_string());
+        }
+        match self.recurrence {
+            Some(ref recurrence) => {
+                recurrence.schedule()?;
+                match self.duration_secs {
+                    None | Some(0) => Err("Recurring windows need a duration.".to_string()),
+                    Some(duration_secs) if duration_secs > MAX_DURATION_SECS => Err(format!(
+                        "Recurring windows last at most {MAX_DURATION_SECS} seconds."
+                    )),
+                    Some(_) => Ok(()),
+                }
+            }
+            None if self.duration_secs.is_some() => {
+                Err("One-off windows end at `ends_at`, not after a duration.".to_string())
+            }
+            None => match self.ends_at {
+                Some(ends_at) if ends_at > self.starts_at => Ok(()),
+                Some(_) => Err("The window must start before it ends.".to_string()),
+                None => Err("One-off windows need `ends_at`.".to_string()),
+            },
+        }
+    }
+
+    /// Periods the window is open that overlap `from..to`, in order
+    pub(crate) fn occurrences(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+        let (Some(recurrence), Some(duration_secs)) = (&self.recurrence, self.duration_secs) else {
+            return match self.ends_at {
+                Some(ends_at) if self.starts_at < to && ends_at > from => {
+                    vec![(self.starts_at, ends_at)]
+                }
+                _ => vec![],
+            };
+        };
+        let Ok((schedule, timezone)) = recurrence.schedule() else {
+            return vec![];
+        };
+
+        let duration = chrono::Duration::seconds(duration_secs.min(MAX_DURATION_SECS) as i64);
+        // Windows opened up to a duration before `from` are still open at `from`,
+        // the schedule only yields moments strictly after the one given
+        let earliest = |at: DateTime<Utc>, before| {
+            at.checked_sub_signed(before)
+                .unwrap_or(DateTime::<Utc>::MIN_UTC)
+        };
+        let after =
+            earliest(from, duration).max(earliest(self.starts_at, chrono::Duration::seconds(1)));
+        schedule
+            .after(&after.with_timezone(&timezone))
+            .map(|opens_at| opens_at.with_timezone(&Utc))
+            .take_while(|opens_at| {
+                *opens_at < to && self.ends_at.is_none_or(|ends_at| *opens_at <= ends_at)
+            })
+            .take(MAX_OCCURRENCES)
+            .map(|opens_at| {
+                let closes_at = opens_at
+                    .checked_add_signed(duration)
+                    .unwrap_or(DateTime::<Utc>::MAX_UTC);
+                (opens_at, closes_at)
+            })
+            .filter(|(_, closes_at)| *closes_at > from)
+            .collect()
+    }
+
+    pub(crate) fn is_open(&self, at: DateTime<Utc>) -> bool {
+        let until = at
+            .checked_add_signed(chrono::Duration::milliseconds(1))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+        !self.occurrences(at, until).is_empty()
+    }
+}
+
+/// Window the check is in right now, if any
+pub(crate) async fn open_window(
+    store: &Storage,
+    check: &Check,
+) -> store::Result<Option<MaintenanceWindow>> {
+    let now = Utc::now();
+    Ok(store
+        .maintenance_windows_for(check)
+        .await?
+        .into_iter()
+        .find(|window| window.is_open(now)))
+}
+
+/// Periods `from..to` the check spent in maintenance, clipped to the range and merged
+pub(crate) fn periods(
+    windows: &[MaintenanceWindow],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let mut periods: Vec<_> = windows
+        .iter()
+        .flat_map(|window| window.occurrences(from, to))
+        .map(|(opens_at, closes_at)| (opens_at.max(from), closes_at.min(to)))
+        .collect();
+    periods.sort();
+
+    let mut merged: Vec<(DateTime<Utc>, DateTime<Utc>)> = vec![];
+    for (opens_at, closes_at) in periods {
+        match merged.last_mut() {
+            Some((_, last_closes_at)) if opens_at <= *last_closes_at => {
+                *last_closes_at = (*last_closes_at).max(closes_at);
+            }
+            _ => merged.push((opens_at, closes_at)),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::oid::ObjectId;
+    use chrono::{Duration, Utc};
+
+    use super::MAX_DURATION_SECS;
+    use crate::models::{CronFrequency, MaintenanceMode, MaintenanceWindow};
+
+    fn every_minute(duration_secs: u64) -> MaintenanceWindow {
+        MaintenanceWindow {
+            _id: ObjectId::new(),
+            project_id: ObjectId::new(),
+            name: "Deploys".to_string(),
+            starts_at: Utc::now() - Duration::days(1),
+            ends_at: None,
+            recurrence: Some(CronFrequency {
+                cron: "* * * * *".to_string(),
+                timezone: None,
+            }),
+            duration_secs: Some(duration_secs),
+            checks: vec![ObjectId::new()],
+            tags: vec![],
+            mode: MaintenanceMode::Record,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn durations_are_bounded() {
+        assert!(every_minute(60).validate().is_ok());
+        assert!(every_minute(0).validate().is_err());
+        assert!(every_minute(MAX_DURATION_SECS + 1).validate().is_err());
+    }
+
+    #[test]
+    fn huge_durations_do_not_overflow() {
+        let window = every_minute(u64::MAX);
+        let now = Utc::now();
+        assert!(window.is_open(now));
+        assert!(!window.occurrences(now, now + Duration::hours(1)).is_empty());
+    }
+}
@@ -0,0 +1,229 @@
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use bson::oid::ObjectId;
+use poem::http::header::CONTENT_TYPE;
+use poem::{handler, Response};
+use prometheus::{
+    register_counter_vec, register_gauge_vec, register_histogram, register_histogram_vec,
+    register_int_counter, CounterVec, Encoder, GaugeVec, Histogram, HistogramVec, IntCounter,
+    TextEncoder,
+};
+
+use crate::models::{Check, CheckHistory, CheckState, Status};
+
+/// Buckets for durations going from a few milliseconds up to the default check timeout
+const DURATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+static CHECK_UP: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "uptime_check_up",
+        "Whether the check is up (1) or down (0)",
+        &["check_id"]
+    )
+    .unwrap()
+});
+
+static CHECK_LATENCY: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "uptime_check_latency_seconds",
+        "Total response time of the last execution",
+        &["check_id"]
+    )
+    .unwrap()
+});
+
+static CHECK_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "uptime_check_duration_seconds",
+        "Total response time of every execution",
+        &["check_id"],
+        DURATION_BUCKETS.to_vec()
+    )
+    .unwrap()
+});
+
+static CHECK_LAST_RUN: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "uptime_check_last_run_timestamp_seconds",
+        "Moment the check last ran, as a Unix timestamp",
+        &["check_id"]
+    )
+    .unwrap()
+});
+
+static CHECK_CONSECUTIVE_FAILURES: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "uptime_check_consecutive_failures",
+        "Failed executions in a row",
+        &["check_id"]
+    )
+    .unwrap()
+});
+
+static CHECK_CERTIFICATE_EXPIRY: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "uptime_check_certificate_expiry_timestamp_seconds",
+        "Moment the certificate of the check expires, as a Unix timestamp",
+        &["check_id"]
+    )
+    .unwrap()
+});
+
+static CHECKS_EXECUTED: LazyLock<CounterVec> = LazyLock::new(|| {
+    register_counter_vec!(
+        "uptime_checks_executed_total",
+        "Check executions, by outcome",
+        &["status"]
+    )
+    .unwrap()
+});
+
+static SCHEDULER_LAG: LazyLock<Histogram> = LazyLock::new(|| {
+    register_histogram!(
+        "uptime_scheduler_lag_seconds",
+        "Delay between the moment a check is due and the moment it starts",
+        DURATION_BUCKETS.to_vec()
+    )
+    .unwrap()
+});
+
+static WEBHOOK_DELIVERY_FAILURES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "uptime_webhook_delivery_failures_total",
+        "Webhook delivery attempts that failed"
+    )
+    .unwrap()
+});
+
+static MONGO_ERRORS: LazyLock<CounterVec> = LazyLock::new(|| {
+    register_counter_vec!(
+        "uptime_mongo_errors_total",
+        "MongoDB commands that failed, by command",
+        &["command"]
+    )
+    .unwrap()
+});
+
+static HTTP_REQUESTS: LazyLock<CounterVec> = LazyLock::new(|| {
+    register_counter_vec!(
+        "uptime_http_requests_total",
+        "API requests, by method, matched route and response status",
+        &["method", "path", "status"]
+    )
+    .unwrap()
+});
+
+static HTTP_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "uptime_http_request_duration_seconds",
+        "Time spent answering API requests, by method and matched route",
+        &["method", "path"],
+        DURATION_BUCKETS.to_vec()
+    )
+    .unwrap()
+});
+
+/// Export the current state of a check, without counting an execution
+pub(crate) fn observe_check(check: &Check) {
+    let check_id = check._id.to_hex();
+    let labels = [check_id.as_str()];
+    match check.current_state {
+        CheckState::Up | CheckState::Degraded => CHECK_UP.with_label_values(&labels).set(1.0),
+        CheckState::Down => CHECK_UP.with_label_values(&labels).set(0.0),
+        // Not known yet, leave the series out rather than reporting it down
+        CheckState::Unknown => {}
+    }
+    if let Some(last_run_at) = check.last_run_at {
+        CHECK_LAST_RUN
+            .with_label_values(&labels)
+            .set(last_run_at.timestamp() as f64);
+    }
+    CHECK_CONSECUTIVE_FAILURES
+        .with_label_values(&labels)
+        .set(check.consecutive_failures as f64);
+}
+
+/// Count an execution of the check and record its latency and certificate expiry
+pub(crate) fn observe_execution(check: &Check, history: &CheckHistory) {
+    let status = match history.status {
+        Status::Ok => "ok",
+        Status::Error => "error",
+        Status::Maintenance => "maintenance",
+    };
+    CHECKS_EXECUTED.with_label_values(&[status]).inc();
+
+    let check_id = check._id.to_hex();
+    if let Some(expires_at) = history.tls.as_ref().and_then(|tls| tls.expires_at) {
+        CHECK_CERTIFICATE_EXPIRY
+            .with_label_values(&[check_id.as_str()])
+            .set(expires_at.timestamp() as f64);
+    }
+    if let Some(ref latency) = history.latency {
+        let seconds = latency.total_ms as f64 / 1000.0;
+        CHECK_LATENCY
+            .with_label_values(&[check_id.as_str()])
+            .set(seconds);
+        CHECK_DURATION
+            .with_label_values(&[check_id.as_str()])
+            .observe(seconds);
+    }
+}
+
+/// Drop the series of a check that is no longer monitored
+pub(crate) fn forget_check(check_id: &ObjectId) {
+    let check_id = check_id.to_hex();
+    let labels = [check_id.as_str()];
+    let _ = CHECK_UP.remove_label_values(&labels);
+    let _ = CHECK_LATENCY.remove_label_values(&labels);
+    let _ = CHECK_DURATION.remove_label_values(&labels);
+    let _ = CHECK_LAST_RUN.remove_label_values(&labels);
+    let _ = CHECK_CONSECUTIVE_FAILURES.remove_label_values(&labels);
+    let _ = CHECK_CERTIFICATE_EXPIRY.remove_label_values(&labels);
+}
+
+pub(crate) fn observe_scheduler_lag(lag: Duration) {
+    SCHEDULER_LAG.observe(lag.as_secs_f64());
+}
+
+pub(crate) fn count_webhook_delivery_failure() {
+    WEBHOOK_DELIVERY_FAILURES.inc();
+}
+
+pub(crate) fn count_mongo_error(command: &str) {
+    MONGO_ERRORS.with_label_values(&[command]).inc();
+}
+
+/// Route label of requests that matched no route, so unknown paths share one series
+pub(crate) const UNMATCHED_ROUTE: &str = "other";
+
+pub(crate) fn observe_request(method: &str, route: &str, status: u16, duration: Duration) {
+    // Clients may send any method, only the standard ones get their own series
+    let method = match method {
+        "GET" | "HEAD" | "POST" | "PUT" | "PATCH" | "DELETE" | "OPTIONS" => method,
+        _ => UNMATCHED_ROUTE,
+    };
+    HTTP_REQUESTS
+        .with_label_values(&[method, route, status.to_string().as_str()])
+        .inc();
+    HTTP_REQUEST_DURATION
+        .with_label_values(&[method, route])
+        .observe(duration.as_secs_f64());
+}
+
+/// Every metric in the Prometheus text format
+#[handler]
+pub(crate) fn export() -> Response {
+    let encoder = TextEncoder::new();
+    let mut buffer = vec![];
+    if let Err(err) = encoder.encode(&prometheus::gather(), &mut buffer) {
+        return Response::builder()
+            .status(poem::http::StatusCode::INTERNAL_SERVER_ERROR)
+            .body(err.to_string());
+    }
+    Response::builder()
+        .header(CONTENT_TYPE, encoder.format_type())
+        .body(buffer)
+}
@@ -1,6 +1,16 @@
+use std::time::Instant;
+
+use poem::http::{header, StatusCode};
+use poem::web::Json;
+use poem::PathPattern;
 use poem::{Endpoint, IntoResponse, Request, Response};
 use tracing::{info, span, Level};
 
+use crate::auth;
+use crate::metrics;
+use crate::models::Error;
+use crate::store::{Storage, Store};
+
 pub(crate) async fn log<E: Endpoint>(next: E, req: Request) -> poem::Result<Response> {
     let span = span!(Level::INFO, "request-span");
     let _guard = span.enter();
@@ -8,18 +18,73 @@ pub(crate) async fn log<E: Endpoint>(next: E, req: Request) -> poem::Result<Resp
     let request = format!("Request: {}", req.uri().path());
     info!(request);
 
+    let method = req.method().to_string();
+    let start = Instant::now();
     let res = next.call(req).await;
-    let (response, res) = match res {
+    // Requests are labelled with the route they matched, whatever the path they came with
+    let (response, status, route, res) = match res {
         Ok(resp) => {
             let resp = resp.into_response();
             let res_info = format!("Response: {}", resp.status());
-            (res_info, Ok(resp))
+            let route = resp.data::<PathPattern>().map(|pattern| pattern.0.clone());
+            (res_info, resp.status(), route, Ok(resp))
         }
         Err(err) => {
             let res_info = format!("An error has ocurrred: {err}");
-            (res_info, Err(err))
+            let route = err.data::<PathPattern>().map(|pattern| pattern.0.clone());
+            (res_info, err.status(), route, Err(err))
         }
     };
+    // Paths only matching the prefix of a nested service come with an empty route
+    let route = route.filter(|route| !route.is_empty());
+    metrics::observe_request(
+        &method,
+        route.as_deref().unwrap_or(metrics::UNMATCHED_ROUTE),
+        status.as_u16(),
+        start.elapsed(),
+    );
     info!(response);
     res
-}
\ No newline at end of file
+}
+
+/// Let the request through when it carries a key with the scope it needs, handlers get the key
+/// to know which project they act on
+pub(crate) async fn authenticate<E: Endpoint>(next: E, mut req: Request) -> poem::Result<Response> {
+    let unauthorized = |detail: &str| {
+        Json(Error::unauthorized(detail.to_string()))
+            .with_status(StatusCode::UNAUTHORIZED)
+            .with_header(header::WWW_AUTHENTICATE, "Bearer")
+            .into_response()
+    };
+
+    let Some(key) = req
+        .header(header::AUTHORIZATION)
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return Ok(unauthorized("An API key is required, as a bearer token."));
+    };
+    let Some(store) = req.data::<Storage>() else {
+        return Err(poem::Error::from_status(StatusCode::INTERNAL_SERVER_ERROR));
+    };
+    let api_key = match store.api_key_by_hash(&auth::hash_key(key.trim())).await {
+        Ok(Some(api_key)) if api_key.revoked_at.is_none() => api_key,
+        Ok(_) => return Ok(unauthorized("The API key is unknown or was revoked.")),
+        Err(err) => {
+            return Ok(Json(Error::internal_server_error(err.to_string()))
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR)
+                .into_response())
+        }
+    };
+
+    let required = auth::required_scope(req.method(), req.uri().path());
+    if api_key.scope < required {
+        return Ok(Json(Error::forbidden(format!(
+            "The API key has the {:?} scope, this request needs {required:?}.",
+            api_key.scope
+        )))
+        .with_status(StatusCode::FORBIDDEN)
+        .into_response());
+    }
+    req.set_data(api_key);
+    next.call(req).await.map(IntoResponse::into_response)
+}
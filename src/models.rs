@@ -1,61 +1,542 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
 use bson::oid::ObjectId;
-use chrono::{DateTime, Utc};
-use poem_openapi::{Enum, Object};
+use chrono::{DateTime, DurationRound, Utc};
+use chrono_tz::Tz;
+use poem::http::{HeaderName, HeaderValue};
+use poem_openapi::{Enum, Object, Union};
 use serde::{Deserialize, Serialize};
 
+/// Runs a cron expression is looked ahead for its shortest period
+const PERIODS_SAMPLED: usize = 100;
+/// Longest interval between two runs, a year
+pub(crate) const MAX_INTERVAL_SECS: u64 = 366 * 24 * 60 * 60;
+
 #[derive(Serialize, Deserialize, Clone, Enum, PartialEq, Eq)]
-pub(crate) enum Frequency {
+pub(crate) enum FrequencyPreset {
     Hourly,
     Daily,
     Weekly,
 }
 
-impl ToString for Frequency {
-    fn to_string(&self) -> String {
+/// Run the check every `seconds` seconds
+#[derive(Serialize, Deserialize, Clone, Object, PartialEq, Eq)]
+pub(crate) struct IntervalFrequency {
+    pub(crate) seconds: u64,
+}
+
+/// Run the check following a cron expression, evaluated in `timezone`
+#[derive(Serialize, Deserialize, Clone, Object, PartialEq, Eq)]
+pub(crate) struct CronFrequency {
+    /// Standard five fields cron expression, a leading seconds field is also accepted
+    pub(crate) cron: String,
+    /// IANA time zone name, defaults to UTC
+    pub(crate) timezone: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Union, PartialEq, Eq)]
+#[serde(untagged)]
+pub(crate) enum Frequency {
+    Preset(FrequencyPreset),
+    Interval(IntervalFrequency),
+    Cron(CronFrequency),
+}
+
+impl CronFrequency {
+    pub(crate) fn schedule(&self) -> Result<(cron::Schedule, Tz), String> {
+        // The cron crate expects a seconds field, standard expressions don't have it
+        let expression = if self.cron.split_whitespace().count() == 5 {
+            format!("0 {}", self.cron)
+        } else {
+            self.cron.clone()
+        };
+        let schedule = cron::Schedule::from_str(&expression)
+            .map_err(|err| format!("Invalid cron expression '{}': {}", self.cron, err))?;
+        let timezone = match self.timezone {
+            Some(ref timezone) => {
+                Tz::from_str(timezone).map_err(|_| format!("Invalid time zone '{timezone}'"))?
+            }
+            None => Tz::UTC,
+        };
+        Ok((schedule, timezone))
+    }
+}
+
+impl Frequency {
+    pub(crate) fn validate(&self) -> Result<(), String> {
         match self {
-            Frequency::Hourly => "Hourly".to_string(),
-            Frequency::Daily => "Daily".to_string(),
-            Frequency::Weekly => "Weekly".to_string(),
+            Frequency::Preset(_) => Ok(()),
+            Frequency::Interval(interval) if interval.seconds == 0 => {
+                Err("Interval must be at least one second.".to_string())
+            }
+            Frequency::Interval(interval) if interval.seconds > MAX_INTERVAL_SECS => Err(format!(
+                "Interval must be at most {MAX_INTERVAL_SECS} seconds."
+            )),
+            Frequency::Interval(_) => Ok(()),
+            Frequency::Cron(cron) => cron.schedule().map(|_| ()),
         }
     }
+
+    /// First moment the check is due strictly after `after`, none when there is no such moment
+    /// or it is out of range
+    pub(crate) fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let period = match self {
+            Frequency::Preset(FrequencyPreset::Hourly) => chrono::Duration::hours(1),
+            Frequency::Preset(FrequencyPreset::Daily) => chrono::Duration::days(1),
+            Frequency::Preset(FrequencyPreset::Weekly) => chrono::Duration::weeks(1),
+            Frequency::Interval(interval) => {
+                chrono::Duration::try_seconds(i64::try_from(interval.seconds).ok()?)?
+            }
+            Frequency::Cron(cron) => {
+                let (schedule, timezone) = cron.schedule().ok()?;
+                return schedule
+                    .after(&after.with_timezone(&timezone))
+                    .next()
+                    .map(|next| next.with_timezone(&Utc));
+            }
+        };
+        after.checked_add_signed(period)
+    }
+
+    /// Shortest time between two runs, looking at the next runs of cron expressions
+    pub(crate) fn shortest_period(&self) -> Option<chrono::Duration> {
+        let mut runs = vec![self.next_after(Utc::now())?];
+        while runs.len() <= PERIODS_SAMPLED {
+            let next = self.next_after(*runs.last()?)?;
+            runs.push(next);
+        }
+        runs.windows(2).map(|pair| pair[1] - pair[0]).min()
+    }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Serialize, Deserialize, Clone, Enum, PartialEq, Eq)]
 pub(crate) enum HTTPMethod {
     HEAD,
     GET,
+    POST,
+    PUT,
+    PATCH,
+    DELETE,
+    OPTIONS,
 }
 
-impl ToString for HTTPMethod {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for HTTPMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            HTTPMethod::HEAD => "HEAD".to_string(),
-            HTTPMethod::GET => "GET".to_string(),
+            HTTPMethod::HEAD => write!(f, "HEAD"),
+            HTTPMethod::GET => write!(f, "GET"),
+            HTTPMethod::POST => write!(f, "POST"),
+            HTTPMethod::PUT => write!(f, "PUT"),
+            HTTPMethod::PATCH => write!(f, "PATCH"),
+            HTTPMethod::DELETE => write!(f, "DELETE"),
+            HTTPMethod::OPTIONS => write!(f, "OPTIONS"),
+        }
+    }
+}
+
+/// How a check probes its target
+#[derive(Serialize, Deserialize, Clone, Enum, PartialEq, Eq, Default)]
+pub(crate) enum CheckKind {
+    /// Send an HTTP request to the URL
+    #[default]
+    Http,
+    /// Open a TCP connection to `host:port`
+    Tcp,
+    /// Resolve a host name
+    Dns,
+    /// Complete a TLS handshake with `host:port`, the port defaults to 443
+    Tls,
+    /// Wait for the job being monitored to ping the check's URL
+    Heartbeat,
+}
+
+impl std::fmt::Display for CheckKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckKind::Http => write!(f, "HTTP"),
+            CheckKind::Tcp => write!(f, "TCP"),
+            CheckKind::Dns => write!(f, "DNS"),
+            CheckKind::Tls => write!(f, "TLS"),
+            CheckKind::Heartbeat => write!(f, "Heartbeat"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Enum, PartialEq, Eq, Default)]
+#[allow(clippy::upper_case_acronyms)]
+pub(crate) enum DnsRecordType {
+    #[default]
+    A,
+    AAAA,
+    CNAME,
+    MX,
+    NS,
+    TXT,
+}
+
+/// What a DNS check asks for and expects back
+#[derive(Serialize, Deserialize, Clone, Object, Default)]
+pub(crate) struct DnsOptions {
+    /// Defaults to `A`
+    pub(crate) record_type: Option<DnsRecordType>,
+    /// Answers that must all be present, such as `93.184.216.34` or `10 mail.example.com`
+    pub(crate) expected: Option<Vec<String>>,
+    /// Name server to ask, as `ip` or `ip:port`, defaults to the system resolver
+    pub(crate) resolver: Option<String>,
+}
+
+/// What a heartbeat ping says about the job
+#[derive(Serialize, Deserialize, Clone, Enum, PartialEq, Eq)]
+pub(crate) enum HeartbeatSignal {
+    /// The job started, it has the grace time to report how it went
+    Start,
+    Success,
+    Fail,
+}
+
+/// Where a heartbeat check is pinged and what it last heard
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct Heartbeat {
+    /// Secret part of the ping URL, `/heartbeat/:token`
+    pub(crate) token: String,
+    #[serde(default, with = "crate::timestamps::optional")]
+    pub(crate) last_ping_at: Option<DateTime<Utc>>,
+    pub(crate) last_signal: Option<HeartbeatSignal>,
+    /// When the running job signaled its start
+    #[serde(default, with = "crate::timestamps::optional")]
+    pub(crate) started_at: Option<DateTime<Utc>>,
+    /// How long the job last ran, from its start signal to its outcome
+    pub(crate) duration_ms: Option<u64>,
+}
+
+/// Badge token of a check, along with the badges it shows
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct BadgeToken {
+    pub(crate) token: String,
+    /// Path of the state badge, with the token
+    pub(crate) status_url: String,
+    /// Path of the uptime badge, with the token. Add `&window=7d` or `&window=30d` for longer
+    /// than the last 24 hours
+    pub(crate) uptime_url: String,
+}
+
+/// A ping received by a heartbeat check
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct HeartbeatPing {
+    pub(crate) _id: ObjectId,
+    pub(crate) check_id: ObjectId,
+    pub(crate) signal: HeartbeatSignal,
+    /// Body of the ping, such as the job's output, cut at 10 KiB
+    pub(crate) payload: Option<String>,
+    pub(crate) truncated: bool,
+    /// Time since the start signal, for outcomes of a started job
+    pub(crate) duration_ms: Option<u64>,
+    pub(crate) remote_addr: Option<String>,
+    pub(crate) user_agent: Option<String>,
+    #[serde(with = "crate::timestamps")]
+    pub(crate) received_at: DateTime<Utc>,
+}
+
+/// Body sent as JSON, with a `application/json` content type
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct JsonBody {
+    pub(crate) content: serde_json::Value,
+}
+
+/// Body sent URL encoded, with a `application/x-www-form-urlencoded` content type
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct FormBody {
+    pub(crate) fields: HashMap<String, String>,
+}
+
+/// Body sent as is
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct RawBody {
+    pub(crate) content: String,
+    pub(crate) content_type: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Union)]
+#[oai(discriminator_name = "type")]
+#[serde(tag = "type")]
+pub(crate) enum RequestBody {
+    Json(JsonBody),
+    Form(FormBody),
+    Raw(RawBody),
+}
+
+/// Make sure every header can actually be sent
+pub(crate) fn validate_headers(headers: &HashMap<String, String>) -> Result<(), String> {
+    for (name, value) in headers {
+        if HeaderName::from_str(name).is_err() {
+            return Err(format!("Invalid header name '{name}'"));
+        }
+        if HeaderValue::from_str(value).is_err() {
+            return Err(format!("Invalid value for header '{name}'"));
         }
     }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Clone, Enum, PartialEq, Eq)]
+pub(crate) enum Comparison {
+    Equals,
+    NotEquals,
+    GreaterThan,
+    LessThan,
+    Contains,
+    Exists,
+    NotExists,
+}
+
+/// Passes when the status code is within `min` and `max`, and one of `codes` if given
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct StatusCodeAssertion {
+    pub(crate) min: Option<u16>,
+    pub(crate) max: Option<u16>,
+    pub(crate) codes: Option<Vec<u16>>,
+}
+
+/// Compares the first value matched by a JSONPath query, e.g. `$.data.status`
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct JsonPathAssertion {
+    pub(crate) path: String,
+    pub(crate) comparison: Comparison,
+    pub(crate) value: Option<serde_json::Value>,
+}
+
+/// Compares the value at a JSON Pointer, e.g. `/data/status`
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct JsonPointerAssertion {
+    pub(crate) pointer: String,
+    pub(crate) comparison: Comparison,
+    pub(crate) value: Option<serde_json::Value>,
+}
+
+/// Validates the response body against a JSON Schema
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct JsonSchemaAssertion {
+    pub(crate) schema: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct BodyContainsAssertion {
+    pub(crate) substring: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct BodyMatchesAssertion {
+    pub(crate) pattern: String,
+}
+
+/// Passes when the header is present and, if given, equals `value` and matches `pattern`
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct HeaderAssertion {
+    pub(crate) name: String,
+    pub(crate) value: Option<String>,
+    pub(crate) pattern: Option<String>,
+}
+
+/// Passes when the whole request took at most `ms` milliseconds
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct MaxLatencyAssertion {
+    pub(crate) ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Union)]
+#[oai(discriminator_name = "type")]
+#[serde(tag = "type")]
+pub(crate) enum Assertion {
+    StatusCode(StatusCodeAssertion),
+    JsonPath(JsonPathAssertion),
+    JsonPointer(JsonPointerAssertion),
+    JsonSchema(JsonSchemaAssertion),
+    BodyContains(BodyContainsAssertion),
+    BodyMatches(BodyMatchesAssertion),
+    Header(HeaderAssertion),
+    MaxLatency(MaxLatencyAssertion),
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct AssertionResult {
+    pub(crate) assertion: Assertion,
+    pub(crate) passed: bool,
+    pub(crate) details: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Enum, PartialEq, Eq)]
+pub(crate) enum Backoff {
+    Fixed,
+    Linear,
+    Exponential,
+}
+
+/// How a failed check is retried before it is recorded as an error
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct RetryPolicy {
+    /// Attempts made after the first one fails
+    pub(crate) retries: u32,
+    pub(crate) backoff: Backoff,
+    /// Delay before the first retry, in milliseconds
+    pub(crate) delay_ms: u64,
+}
+
+impl RetryPolicy {
+    const MAX_RETRIES: u32 = 10;
+    const MAX_DELAY_MS: u64 = 5 * 60 * 1000;
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.retries > Self::MAX_RETRIES {
+            return Err(format!("Retries must be at most {}.", Self::MAX_RETRIES));
+        }
+        Ok(())
+    }
+
+    /// Delay before the given retry, starting at 1
+    pub(crate) fn delay(&self, retry: u32) -> std::time::Duration {
+        let delay_ms = match self.backoff {
+            Backoff::Fixed => self.delay_ms,
+            Backoff::Linear => self.delay_ms.saturating_mul(retry as u64),
+            Backoff::Exponential => self
+                .delay_ms
+                .saturating_mul(2u64.saturating_pow(retry.saturating_sub(1))),
+        };
+        std::time::Duration::from_millis(delay_ms.min(Self::MAX_DELAY_MS))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Enum, PartialEq, Eq)]
+pub(crate) enum CheckState {
+    Up,
+    Down,
+    /// Failing, but not enough times in a row to be considered down yet
+    Degraded,
+    /// Never executed
+    #[default]
+    Unknown,
 }
 
 #[derive(Serialize, Deserialize, Clone, Object)]
 pub(crate) struct Check {
     pub(crate) _id: ObjectId,
+    /// Project the check belongs to
+    #[serde(default = "crate::projects::default_project_id")]
+    pub(crate) project_id: ObjectId,
+    #[serde(default)]
+    pub(crate) kind: CheckKind,
+    /// Human friendly name, shown instead of the URL when set
+    pub(crate) name: Option<String>,
+    /// Free form labels used to group and filter checks
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
     pub(crate) frequency: Frequency,
+    /// URL for HTTP checks, `host:port` for TCP and TLS checks, a host name for DNS checks
+    /// and the ping path for heartbeat checks
     pub(crate) url: String,
     pub(crate) method: HTTPMethod,
+    pub(crate) headers: Option<HashMap<String, String>>,
+    pub(crate) query: Option<HashMap<String, String>>,
+    pub(crate) body: Option<RequestBody>,
     pub(crate) expected_body: Option<serde_json::Value>,
-    pub(crate) hook: Option<String>,
+    pub(crate) dns: Option<DnsOptions>,
+    /// Set on heartbeat checks only
+    pub(crate) heartbeat: Option<Heartbeat>,
+    /// Lets the badges of the check be shown without an API key, as `?token=<badge_token>`
+    pub(crate) badge_token: Option<String>,
+    /// How late a heartbeat may be, and how long a started job may run, defaults to 60 seconds
+    pub(crate) grace_secs: Option<u64>,
+    #[serde(default)]
+    pub(crate) assertions: Vec<Assertion>,
+    /// Notification channels alerted when the check goes down or comes back up
+    #[serde(default)]
+    pub(crate) channels: Vec<ObjectId>,
+    pub(crate) timeout_ms: Option<u64>,
+    pub(crate) retry: Option<RetryPolicy>,
+    /// Consecutive failures before the check is considered down, defaults to 1
+    pub(crate) failure_threshold: Option<u32>,
+    /// Consecutive successes before a down check is considered up again, defaults to 1
+    pub(crate) recovery_threshold: Option<u32>,
+    /// Uptime percentage the check is expected to meet, such as `99.9`
+    pub(crate) sla_target: Option<f64>,
+    /// What happens when the certificate of an HTTPS URL has a problem, defaults to `Warn`
+    pub(crate) certificate_policy: Option<CertificatePolicy>,
+    /// Days before expiry a certificate is reported, defaults to `CERTIFICATE_EXPIRY_DAYS`
+    pub(crate) certificate_expiry_days: Option<u32>,
+    #[serde(default)]
+    pub(crate) current_state: CheckState,
+    #[serde(default)]
+    pub(crate) consecutive_failures: u32,
+    #[serde(default)]
+    pub(crate) consecutive_successes: u32,
+    #[serde(default, with = "crate::timestamps::optional")]
+    pub(crate) last_run_at: Option<DateTime<Utc>>,
+    #[serde(with = "crate::timestamps")]
     pub(crate) created_at: DateTime<Utc>,
+    #[serde(with = "crate::timestamps")]
     pub(crate) updated_at: DateTime<Utc>,
 }
 
 impl Check {
-    pub(crate) fn from_new(new_check: NewCheck) -> Self {
+    /// Count the execution's status towards the thresholds and move the check
+    /// to its new state. Returns the state the check was in before.
+    pub(crate) fn record(&mut self, status: &Status) -> CheckState {
+        let previous = self.current_state.clone();
+        match status {
+            Status::Ok => {
+                self.consecutive_successes += 1;
+                self.consecutive_failures = 0;
+                let recovered = self.consecutive_successes >= self.recovery_threshold.unwrap_or(1);
+                if previous != CheckState::Down || recovered {
+                    self.current_state = CheckState::Up;
+                }
+            }
+            Status::Maintenance => {}
+            Status::Error => {
+                self.consecutive_failures += 1;
+                self.consecutive_successes = 0;
+                if self.consecutive_failures >= self.failure_threshold.unwrap_or(1) {
+                    self.current_state = CheckState::Down;
+                } else if previous != CheckState::Down {
+                    self.current_state = CheckState::Degraded;
+                }
+            }
+        }
+        previous
+    }
+
+    pub(crate) fn from_new(new_check: NewCheck, project_id: ObjectId) -> Self {
         Self {
             _id: ObjectId::new(),
+            project_id,
+            kind: new_check.kind.unwrap_or_default(),
+            name: new_check.name,
+            tags: new_check.tags.unwrap_or_default(),
             frequency: new_check.frequency,
-            url: new_check.url,
-            method: new_check.method,
+            url: new_check.url.unwrap_or_default(),
+            method: new_check.method.unwrap_or(HTTPMethod::GET),
+            headers: new_check.headers,
+            query: new_check.query,
+            body: new_check.body,
             expected_body: new_check.expected_body,
-            hook: new_check.hook,
+            dns: new_check.dns,
+            heartbeat: None,
+            badge_token: None,
+            grace_secs: new_check.grace_secs,
+            assertions: new_check.assertions.unwrap_or_default(),
+            channels: new_check.channels.unwrap_or_default(),
+            timeout_ms: new_check.timeout_ms,
+            retry: new_check.retry,
+            failure_threshold: new_check.failure_threshold,
+            recovery_threshold: new_check.recovery_threshold,
+            sla_target: new_check.sla_target,
+            certificate_policy: new_check.certificate_policy,
+            certificate_expiry_days: new_check.certificate_expiry_days,
+            current_state: CheckState::Unknown,
+            consecutive_failures: 0,
+            consecutive_successes: 0,
+            last_run_at: None,
             created_at: Utc::now(),
             updated_at: Utc::now(),
         }
@@ -66,6 +547,82 @@ impl Check {
 pub(crate) enum Status {
     Ok,
     Error,
+    /// Ran during a maintenance window, it neither counts as up nor as down
+    Maintenance,
+}
+
+/// Timings measured while probing an endpoint, all in milliseconds
+#[derive(Serialize, Deserialize, Clone, Default, Object)]
+pub(crate) struct Latency {
+    /// Time spent resolving the host name
+    pub(crate) dns_ms: Option<u64>,
+    /// Time spent opening a TCP connection to the resolved address, along with the TLS
+    /// handshake of HTTPS requests
+    pub(crate) connect_ms: Option<u64>,
+    /// Time until the response headers were received
+    pub(crate) ttfb_ms: Option<u64>,
+    /// Time until the whole response was received
+    pub(crate) total_ms: u64,
+}
+
+/// Outcome of a single request made while executing a check
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct Attempt {
+    pub(crate) status: Status,
+    pub(crate) details: Option<String>,
+    pub(crate) status_code: Option<u16>,
+    pub(crate) response_size: Option<u64>,
+    pub(crate) latency: Option<Latency>,
+    #[serde(default)]
+    pub(crate) assertions: Vec<AssertionResult>,
+    #[serde(with = "crate::timestamps")]
+    pub(crate) started_at: DateTime<Utc>,
+}
+
+/// What a certificate problem does to a check execution
+#[derive(Serialize, Deserialize, Clone, Enum, PartialEq, Eq, Default)]
+pub(crate) enum CertificatePolicy {
+    /// Do not inspect the certificate
+    Ignore,
+    /// Record the problems on the history entry, the execution still succeeds
+    #[default]
+    Warn,
+    /// Record the problems and fail the execution
+    Fail,
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct CertificateInfo {
+    pub(crate) subject: String,
+    pub(crate) issuer: String,
+    pub(crate) serial: String,
+    #[serde(with = "crate::timestamps")]
+    pub(crate) not_before: DateTime<Utc>,
+    #[serde(with = "crate::timestamps")]
+    pub(crate) not_after: DateTime<Utc>,
+    /// DNS names and IP addresses the certificate is valid for
+    pub(crate) sans: Vec<String>,
+}
+
+/// TLS connection details of an HTTPS URL
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct TlsInfo {
+    /// Negotiated protocol version, such as `TLSv1.3`
+    pub(crate) protocol: Option<String>,
+    /// Certificates sent by the server, the leaf certificate first
+    pub(crate) chain: Vec<CertificateInfo>,
+    /// Expiry of the leaf certificate
+    #[serde(default, with = "crate::timestamps::optional")]
+    pub(crate) expires_at: Option<DateTime<Utc>>,
+    pub(crate) days_remaining: Option<i64>,
+    /// Whether the chain leads to a trusted root and every certificate is in its validity period
+    pub(crate) trusted: bool,
+    pub(crate) hostname_matches: bool,
+    /// Why the certificate was rejected, if it was
+    pub(crate) error: Option<String>,
+    /// Problems found, they fail the execution under the `Fail` policy
+    #[serde(default)]
+    pub(crate) problems: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Object)]
@@ -74,37 +631,238 @@ pub(crate) struct CheckHistory {
     pub(crate) check_id: ObjectId,
     pub(crate) status: Status,
     pub(crate) details: Option<String>,
+    pub(crate) status_code: Option<u16>,
+    pub(crate) response_size: Option<u64>,
+    pub(crate) latency: Option<Latency>,
+    /// Results of the check's assertions on the last attempt
+    #[serde(default)]
+    pub(crate) assertions: Vec<AssertionResult>,
+    /// Every attempt made, the last one decides the entry's status
+    #[serde(default)]
+    pub(crate) attempts: Vec<Attempt>,
+    /// Certificate presented by HTTPS URLs
+    pub(crate) tls: Option<TlsInfo>,
+    /// Maintenance window the check ran in, the attempts keep their own status
+    pub(crate) maintenance_window_id: Option<ObjectId>,
+    #[serde(with = "crate::timestamps")]
     pub(crate) created_at: DateTime<Utc>,
 }
 
 impl CheckHistory {
-    pub(crate) fn new(check_id: ObjectId, status: Status, details: Option<String>) -> Self {
+    pub(crate) fn new(check_id: ObjectId, attempts: Vec<Attempt>) -> Self {
+        let last = attempts.last().cloned();
         Self {
             _id: ObjectId::new(),
             check_id,
-            status,
-            details,
+            status: last.as_ref().map_or(Status::Error, |a| a.status.clone()),
+            details: last.as_ref().and_then(|a| a.details.clone()),
+            status_code: last.as_ref().and_then(|a| a.status_code),
+            response_size: last.as_ref().and_then(|a| a.response_size),
+            latency: last.as_ref().and_then(|a| a.latency.clone()),
+            assertions: last.map(|a| a.assertions).unwrap_or_default(),
+            attempts,
+            tls: None,
+            maintenance_window_id: None,
             created_at: Utc::now(),
         }
     }
 }
 
+/// Granularity history is read at
+#[derive(Serialize, Deserialize, Clone, Copy, Enum, PartialEq, Eq, Hash, Debug)]
+pub(crate) enum Resolution {
+    /// Every execution as it was recorded
+    Raw,
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Resolution {
+    /// Length of a bucket, raw entries have none
+    pub(crate) fn size(&self) -> Option<chrono::Duration> {
+        match self {
+            Resolution::Raw => None,
+            Resolution::Minute => Some(chrono::Duration::minutes(1)),
+            Resolution::Hour => Some(chrono::Duration::hours(1)),
+            Resolution::Day => Some(chrono::Duration::days(1)),
+        }
+    }
+
+    /// Start of the bucket `at` falls in
+    pub(crate) fn floor(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        self.size()
+            .and_then(|size| at.duration_trunc(size).ok())
+            .unwrap_or(at)
+    }
+
+    /// Coarsest resolution that still gives a useful number of points over `span`
+    pub(crate) fn for_span(span: chrono::Duration) -> Self {
+        if span <= chrono::Duration::hours(6) {
+            Resolution::Raw
+        } else if span <= chrono::Duration::days(3) {
+            Resolution::Minute
+        } else if span <= chrono::Duration::days(90) {
+            Resolution::Hour
+        } else {
+            Resolution::Day
+        }
+    }
+}
+
+/// Total response time summary of the executions in a bucket, in milliseconds
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct LatencySummary {
+    pub(crate) min: u64,
+    pub(crate) avg: u64,
+    pub(crate) max: u64,
+    pub(crate) p50: u64,
+    pub(crate) p90: u64,
+    pub(crate) p99: u64,
+}
+
+/// Executions of a check aggregated over one bucket of time
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct Rollup {
+    pub(crate) _id: ObjectId,
+    pub(crate) check_id: ObjectId,
+    pub(crate) resolution: Resolution,
+    /// Start of the bucket
+    #[serde(with = "crate::timestamps")]
+    pub(crate) bucket: DateTime<Utc>,
+    pub(crate) count: u64,
+    pub(crate) failures: u64,
+    pub(crate) latency: Option<LatencySummary>,
+    /// Moment the rollup is removed, missing when it is kept forever
+    #[serde(default, with = "crate::timestamps::optional")]
+    pub(crate) expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Enum, PartialEq, Eq, Default)]
+pub(crate) enum SortOrder {
+    /// Oldest first
+    Asc,
+    /// Most recent first
+    #[default]
+    Desc,
+}
+
+/// Field checks are listed by
+#[derive(Serialize, Deserialize, Clone, Enum, PartialEq, Eq, Default)]
+pub(crate) enum CheckSortField {
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    LastRun,
+}
+
+impl CheckSortField {
+    pub(crate) fn field(&self) -> &'static str {
+        match self {
+            CheckSortField::CreatedAt => "created_at",
+            CheckSortField::UpdatedAt => "updated_at",
+            CheckSortField::LastRun => "last_run_at",
+        }
+    }
+
+    pub(crate) fn value(&self, check: &Check) -> Option<DateTime<Utc>> {
+        match self {
+            CheckSortField::CreatedAt => Some(check.created_at),
+            CheckSortField::UpdatedAt => Some(check.updated_at),
+            CheckSortField::LastRun => check.last_run_at,
+        }
+    }
+}
+
+/// Kind of frequency checks are filtered by, presets match exactly
+#[derive(Serialize, Deserialize, Clone, Enum, PartialEq, Eq)]
+pub(crate) enum FrequencyKind {
+    Hourly,
+    Daily,
+    Weekly,
+    Interval,
+    Cron,
+}
+
+/// One page of checks
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct CheckPage {
+    pub(crate) items: Vec<Check>,
+    /// Pass it as `cursor` to read the next page, missing on the last page
+    pub(crate) next_cursor: Option<String>,
+}
+
+/// One page of check history
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct HistoryPage {
+    pub(crate) resolution: Resolution,
+    /// Raw entries, only filled at the `Raw` resolution
+    pub(crate) items: Vec<CheckHistory>,
+    /// Aggregated entries, filled at every other resolution
+    pub(crate) rollups: Vec<Rollup>,
+    /// Pass it as `cursor` to read the next page, missing on the last page
+    pub(crate) next_cursor: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Object)]
 pub(crate) struct NewCheck {
+    /// Defaults to `Http`
+    pub(crate) kind: Option<CheckKind>,
+    pub(crate) name: Option<String>,
+    pub(crate) tags: Option<Vec<String>>,
     pub(crate) frequency: Frequency,
-    pub(crate) url: String,
-    pub(crate) method: HTTPMethod,
+    /// URL for HTTP checks, `host:port` for TCP and TLS checks and a host name for DNS checks.
+    /// Heartbeat checks are given a ping path instead
+    pub(crate) url: Option<String>,
+    /// Defaults to `GET`
+    pub(crate) method: Option<HTTPMethod>,
+    pub(crate) headers: Option<HashMap<String, String>>,
+    pub(crate) query: Option<HashMap<String, String>>,
+    pub(crate) body: Option<RequestBody>,
     pub(crate) expected_body: Option<serde_json::Value>,
-    pub(crate) hook: Option<String>,
+    pub(crate) dns: Option<DnsOptions>,
+    /// How late a heartbeat may be, and how long a started job may run, defaults to 60 seconds
+    pub(crate) grace_secs: Option<u64>,
+    pub(crate) assertions: Option<Vec<Assertion>>,
+    pub(crate) channels: Option<Vec<ObjectId>>,
+    /// Time allowed for a single attempt, in milliseconds, defaults to 30 seconds
+    pub(crate) timeout_ms: Option<u64>,
+    pub(crate) retry: Option<RetryPolicy>,
+    /// Consecutive failures before the check is considered down, defaults to 1
+    pub(crate) failure_threshold: Option<u32>,
+    /// Consecutive successes before a down check is considered up again, defaults to 1
+    pub(crate) recovery_threshold: Option<u32>,
+    /// Uptime percentage the check is expected to meet, such as `99.9`
+    pub(crate) sla_target: Option<f64>,
+    /// What happens when the certificate of an HTTPS URL has a problem, defaults to `Warn`
+    pub(crate) certificate_policy: Option<CertificatePolicy>,
+    /// Days before expiry a certificate is reported, defaults to `CERTIFICATE_EXPIRY_DAYS`
+    pub(crate) certificate_expiry_days: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Object)]
 pub(crate) struct UpdateCheck {
+    pub(crate) kind: Option<CheckKind>,
+    pub(crate) name: Option<Option<String>>,
+    pub(crate) tags: Option<Vec<String>>,
     pub(crate) frequency: Option<Frequency>,
     pub(crate) url: Option<String>,
     pub(crate) method: Option<HTTPMethod>,
+    pub(crate) headers: Option<Option<HashMap<String, String>>>,
+    pub(crate) query: Option<Option<HashMap<String, String>>>,
+    pub(crate) body: Option<Option<RequestBody>>,
     pub(crate) expected_body: Option<Option<serde_json::Value>>,
-    pub(crate) hook: Option<Option<String>>,
+    pub(crate) dns: Option<Option<DnsOptions>>,
+    pub(crate) grace_secs: Option<Option<u64>>,
+    pub(crate) assertions: Option<Vec<Assertion>>,
+    pub(crate) channels: Option<Vec<ObjectId>>,
+    pub(crate) timeout_ms: Option<Option<u64>>,
+    pub(crate) retry: Option<Option<RetryPolicy>>,
+    pub(crate) failure_threshold: Option<Option<u32>>,
+    pub(crate) recovery_threshold: Option<Option<u32>>,
+    pub(crate) sla_target: Option<Option<f64>>,
+    pub(crate) certificate_policy: Option<Option<CertificatePolicy>>,
+    pub(crate) certificate_expiry_days: Option<Option<u32>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Object)]
@@ -128,6 +886,20 @@ impl Error {
         }
     }
 
+    pub(crate) fn unauthorized(detail: String) -> Self {
+        Self {
+            detail,
+            status: 401,
+        }
+    }
+
+    pub(crate) fn forbidden(detail: String) -> Self {
+        Self {
+            detail,
+            status: 403,
+        }
+    }
+
     pub(crate) fn internal_server_error(detail: String) -> Self {
         Self {
             detail,
@@ -136,21 +908,771 @@ impl Error {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Enum, PartialEq, Eq)]
+pub(crate) enum IncidentStatus {
+    Open,
+    Resolved,
+}
+
+/// A period during which a check kept failing
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct Incident {
+    pub(crate) _id: ObjectId,
+    pub(crate) check_id: ObjectId,
+    /// Project the check belongs to
+    #[serde(default = "crate::projects::default_project_id")]
+    pub(crate) project_id: ObjectId,
+    pub(crate) status: IncidentStatus,
+    /// Details of the failure that opened the incident
+    pub(crate) details: Option<String>,
+    /// Details of the latest failure
+    pub(crate) last_details: Option<String>,
+    /// Number of failed executions during the incident
+    pub(crate) failures: u32,
+    #[serde(with = "crate::timestamps")]
+    pub(crate) started_at: DateTime<Utc>,
+    #[serde(default, with = "crate::timestamps::optional")]
+    pub(crate) resolved_at: Option<DateTime<Utc>>,
+    pub(crate) duration_secs: Option<i64>,
+    /// Updates posted by hand, shown on status pages, oldest first
+    #[serde(default)]
+    pub(crate) updates: Vec<IncidentUpdate>,
+}
+
+/// Stage of an incident as told to the people following it, it does not resolve the incident
+#[derive(Serialize, Deserialize, Clone, Enum, PartialEq, Eq, Debug)]
+pub(crate) enum IncidentUpdateStatus {
+    Investigating,
+    Identified,
+    Monitoring,
+    Resolved,
+}
+
+/// A message about an incident, written for the people following it
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct IncidentUpdate {
+    pub(crate) status: IncidentUpdateStatus,
+    pub(crate) message: String,
+    #[serde(with = "crate::timestamps")]
+    pub(crate) created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct NewIncidentUpdate {
+    pub(crate) status: IncidentUpdateStatus,
+    pub(crate) message: String,
+}
 
+impl Incident {
+    pub(crate) fn open(check: &Check, details: Option<String>) -> Self {
+        Self {
+            _id: ObjectId::new(),
+            check_id: check._id,
+            project_id: check.project_id,
+            status: IncidentStatus::Open,
+            last_details: details.clone(),
+            details,
+            failures: 1,
+            started_at: Utc::now(),
+            resolved_at: None,
+            duration_secs: None,
+            updates: vec![],
+        }
+    }
+
+    pub(crate) fn resolve(&mut self) {
+        let resolved_at = Utc::now();
+        self.status = IncidentStatus::Resolved;
+        self.resolved_at = Some(resolved_at);
+        self.duration_secs = Some((resolved_at - self.started_at).num_seconds());
+    }
+}
+
+/// Period stats are computed over, ending now unless a custom range is given
+#[derive(Serialize, Deserialize, Clone, Enum, PartialEq, Eq, Default)]
+pub(crate) enum StatsWindow {
+    #[default]
+    #[oai(rename = "24h")]
+    #[serde(rename = "24h")]
+    Day,
+    #[oai(rename = "7d")]
+    #[serde(rename = "7d")]
+    Week,
+    #[oai(rename = "30d")]
+    #[serde(rename = "30d")]
+    Month,
+    /// Between `from` and `to`
+    #[oai(rename = "custom")]
+    #[serde(rename = "custom")]
+    Custom,
+}
+
+/// Latency percentiles of the total response time, in milliseconds
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct LatencyPercentiles {
+    pub(crate) p50: u64,
+    pub(crate) p90: u64,
+    pub(crate) p99: u64,
+}
+
+/// How the check did against its SLA target
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct SlaReport {
+    /// Uptime percentage the check is expected to meet
+    pub(crate) target: f64,
+    pub(crate) met: bool,
+    /// Downtime the target allows over the window
+    pub(crate) allowed_downtime_secs: i64,
+    /// Downtime left before the target is missed, negative once it is
+    pub(crate) remaining_downtime_secs: i64,
+}
+
+/// Availability of a check over a window
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct CheckStats {
+    pub(crate) check_id: ObjectId,
+    pub(crate) from: DateTime<Utc>,
+    pub(crate) to: DateTime<Utc>,
+    /// Percentage of the window the check was not down, time before the check existed
+    /// and maintenance windows are left out
+    pub(crate) uptime_percentage: f64,
+    pub(crate) downtime_secs: i64,
+    /// Time spent in maintenance windows
+    pub(crate) maintenance_secs: i64,
+    /// Incidents that were open at some point during the window
+    pub(crate) incidents: u64,
+    /// Mean time to recovery of the incidents resolved during the window
+    pub(crate) mttr_secs: Option<i64>,
+    /// Mean time between failures, the time up divided by the incidents started during the window
+    pub(crate) mtbf_secs: Option<i64>,
+    /// Executions recorded during the window, outside of maintenance
+    pub(crate) samples: u64,
+    pub(crate) failed_samples: u64,
+    pub(crate) latency: Option<LatencyPercentiles>,
+    /// Missing when the check has no SLA target
+    pub(crate) sla: Option<SlaReport>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct SlackChannel {
+    /// Only returned in full when the channel is created, reads only show its origin
+    pub(crate) webhook_url: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct TeamsChannel {
+    /// Only returned in full when the channel is created, reads only show its origin
+    pub(crate) webhook_url: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct DiscordChannel {
+    /// Only returned in full when the channel is created, reads only show its origin
+    pub(crate) webhook_url: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct PagerDutyChannel {
+    /// Integration key of an Events API v2 integration
+    pub(crate) routing_key: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Enum, PartialEq, Eq)]
+pub(crate) enum OpsgenieRegion {
+    US,
+    EU,
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct OpsgenieChannel {
+    pub(crate) api_key: String,
+    /// Defaults to US
+    pub(crate) region: Option<OpsgenieRegion>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct EmailChannel {
+    pub(crate) smtp_host: String,
+    /// Defaults to 587
+    pub(crate) smtp_port: Option<u16>,
+    pub(crate) username: Option<String>,
+    pub(crate) password: Option<String>,
+    /// Use STARTTLS on the connection, defaults to true
+    pub(crate) starttls: Option<bool>,
+    pub(crate) from: String,
+    pub(crate) to: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct WebhookChannel {
+    pub(crate) url: String,
+    /// Values are only returned when the channel is created
+    pub(crate) headers: Option<HashMap<String, String>>,
+    /// Shared secret used to sign deliveries, generated when not provided.
+    /// Only returned when the channel is created.
+    pub(crate) secret: Option<String>,
+    /// Body sent instead of the default JSON payload. Placeholders such as
+    /// `{{check.url}}` or `{{incident.details}}` are replaced by the matching
+    /// field of the payload.
+    pub(crate) template: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Union)]
+#[oai(discriminator_name = "type")]
+#[serde(tag = "type")]
+pub(crate) enum ChannelConfig {
+    Slack(SlackChannel),
+    Teams(TeamsChannel),
+    Discord(DiscordChannel),
+    PagerDuty(PagerDutyChannel),
+    Opsgenie(OpsgenieChannel),
+    Email(EmailChannel),
+    Webhook(WebhookChannel),
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct NotificationChannel {
+    pub(crate) _id: ObjectId,
+    /// Project the channel belongs to
+    #[serde(default = "crate::projects::default_project_id")]
+    pub(crate) project_id: ObjectId,
+    pub(crate) name: String,
+    pub(crate) config: ChannelConfig,
+    #[serde(with = "crate::timestamps")]
+    pub(crate) created_at: DateTime<Utc>,
+    #[serde(with = "crate::timestamps")]
+    pub(crate) updated_at: DateTime<Utc>,
+}
+
+impl NotificationChannel {
+    pub(crate) fn from_new(new_channel: NewNotificationChannel, project_id: ObjectId) -> Self {
+        Self {
+            _id: ObjectId::new(),
+            project_id,
+            name: new_channel.name,
+            config: new_channel.config,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    pub(crate) fn redacted(self) -> Self {
+        Self {
+            config: self.config.redacted(),
+            ..self
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct NewNotificationChannel {
+    pub(crate) name: String,
+    pub(crate) config: ChannelConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct UpdateNotificationChannel {
+    pub(crate) name: Option<String>,
+    pub(crate) config: Option<ChannelConfig>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Enum, PartialEq, Eq)]
+pub(crate) enum DeliveryStatus {
+    /// Waiting for its next attempt
+    Pending,
+    Delivered,
+    /// Gave up after too many attempts
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct DeliveryAttempt {
+    pub(crate) status_code: Option<u16>,
+    pub(crate) latency_ms: u64,
+    pub(crate) error: Option<String>,
+    #[serde(with = "crate::timestamps")]
+    pub(crate) attempted_at: DateTime<Utc>,
+}
+
+/// A payload sent, or still to be sent, to a webhook channel
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct WebhookDelivery {
+    pub(crate) _id: ObjectId,
+    /// Project the channel belongs to
+    #[serde(default = "crate::projects::default_project_id")]
+    pub(crate) project_id: ObjectId,
+    pub(crate) channel_id: ObjectId,
+    pub(crate) check_id: ObjectId,
+    pub(crate) incident_id: ObjectId,
+    pub(crate) status: DeliveryStatus,
+    /// Body sent on every attempt
+    pub(crate) payload: String,
+    pub(crate) content_type: String,
+    pub(crate) attempts: Vec<DeliveryAttempt>,
+    #[serde(default, with = "crate::timestamps::optional")]
+    pub(crate) next_attempt_at: Option<DateTime<Utc>>,
+    #[serde(with = "crate::timestamps")]
+    pub(crate) created_at: DateTime<Utc>,
+}
+
+impl WebhookDelivery {
+    pub(crate) fn new(
+        channel_id: ObjectId,
+        data: &WebhookData,
+        payload: String,
+        content_type: String,
+    ) -> Self {
+        Self {
+            _id: ObjectId::new(),
+            project_id: data.check.project_id,
+            channel_id,
+            check_id: data.check._id,
+            incident_id: data.incident._id,
+            status: DeliveryStatus::Pending,
+            payload,
+            content_type,
+            attempts: vec![],
+            next_attempt_at: Some(Utc::now()),
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// What notifications tell about a check, they often go to third parties. Request headers and
+/// bodies, which may hold credentials, and tokens are left out
+#[derive(Serialize, Deserialize)]
+pub(crate) struct NotifiedCheck {
+    pub(crate) _id: ObjectId,
+    pub(crate) project_id: ObjectId,
+    pub(crate) name: Option<String>,
+    pub(crate) kind: CheckKind,
+    pub(crate) method: HTTPMethod,
+    /// Not set on heartbeat checks, their ping path holds their token
+    pub(crate) url: Option<String>,
+    pub(crate) state: CheckState,
+}
+
+impl From<&Check> for NotifiedCheck {
+    fn from(check: &Check) -> Self {
+        Self {
+            _id: check._id,
+            project_id: check.project_id,
+            name: check.name.clone(),
+            kind: check.kind.clone(),
+            method: check.method.clone(),
+            url: (check.kind != CheckKind::Heartbeat).then(|| check.url.clone()),
+            state: check.current_state.clone(),
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize)]
 pub(crate) struct WebhookData {
-    pub(crate) check: Check,
+    pub(crate) check: NotifiedCheck,
     pub(crate) status: Status,
+    pub(crate) state: CheckState,
     pub(crate) details: Option<String>,
+    pub(crate) incident: Incident,
 }
 
 impl WebhookData {
-    pub(crate) fn new(status: Status, details: Option<String>, check: Check) -> Self {
+    pub(crate) fn new(
+        status: Status,
+        details: Option<String>,
+        check: &Check,
+        incident: Incident,
+    ) -> Self {
         Self {
-            check,
+            state: check.current_state.clone(),
+            check: NotifiedCheck::from(check),
             status,
             details,
+            incident,
+        }
+    }
+}
+
+/// What happens to checks while a maintenance window is open
+#[derive(Serialize, Deserialize, Clone, Enum, PartialEq, Eq, Default)]
+pub(crate) enum MaintenanceMode {
+    /// Checks run and their results are recorded as `Maintenance`
+    #[default]
+    Record,
+    /// Checks do not run at all
+    Skip,
+}
+
+/// Planned downtime, checks in scope do not alert and the time does not count against uptime
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct MaintenanceWindow {
+    pub(crate) _id: ObjectId,
+    /// Project the window belongs to
+    #[serde(default = "crate::projects::default_project_id")]
+    pub(crate) project_id: ObjectId,
+    pub(crate) name: String,
+    /// Start of a one-off window, or first moment a recurring window may open
+    #[serde(with = "crate::timestamps")]
+    pub(crate) starts_at: DateTime<Utc>,
+    /// End of a one-off window, or last moment a recurring window may open
+    #[serde(default, with = "crate::timestamps::optional")]
+    pub(crate) ends_at: Option<DateTime<Utc>>,
+    /// Moments a recurring window opens at
+    pub(crate) recurrence: Option<CronFrequency>,
+    /// How long a recurring window stays open
+    pub(crate) duration_secs: Option<u64>,
+    /// Checks covered by the window
+    #[serde(default)]
+    pub(crate) checks: Vec<ObjectId>,
+    /// Checks with any of these tags are covered by the window
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+    #[serde(default)]
+    pub(crate) mode: MaintenanceMode,
+    #[serde(with = "crate::timestamps")]
+    pub(crate) created_at: DateTime<Utc>,
+    #[serde(with = "crate::timestamps")]
+    pub(crate) updated_at: DateTime<Utc>,
+}
+
+impl MaintenanceWindow {
+    pub(crate) fn from_new(new_window: NewMaintenanceWindow, project_id: ObjectId) -> Self {
+        Self {
+            _id: ObjectId::new(),
+            project_id,
+            name: new_window.name,
+            starts_at: new_window.starts_at,
+            ends_at: new_window.ends_at,
+            recurrence: new_window.recurrence,
+            duration_secs: new_window.duration_secs,
+            checks: new_window.checks.unwrap_or_default(),
+            tags: new_window.tags.unwrap_or_default(),
+            mode: new_window.mode.unwrap_or_default(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
         }
     }
-}
\ No newline at end of file
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct NewMaintenanceWindow {
+    pub(crate) name: String,
+    /// Start of a one-off window, or first moment a recurring window may open
+    pub(crate) starts_at: DateTime<Utc>,
+    /// End of a one-off window, required for them, or last moment a recurring window may open
+    pub(crate) ends_at: Option<DateTime<Utc>>,
+    /// Makes the window recurring, it opens at every moment of the cron expression
+    pub(crate) recurrence: Option<CronFrequency>,
+    /// How long a recurring window stays open, required for them
+    pub(crate) duration_secs: Option<u64>,
+    pub(crate) checks: Option<Vec<ObjectId>>,
+    pub(crate) tags: Option<Vec<String>>,
+    /// Defaults to `Record`
+    pub(crate) mode: Option<MaintenanceMode>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct UpdateMaintenanceWindow {
+    pub(crate) name: Option<String>,
+    pub(crate) starts_at: Option<DateTime<Utc>>,
+    pub(crate) ends_at: Option<Option<DateTime<Utc>>>,
+    pub(crate) recurrence: Option<Option<CronFrequency>>,
+    pub(crate) duration_secs: Option<Option<u64>>,
+    pub(crate) checks: Option<Vec<ObjectId>>,
+    pub(crate) tags: Option<Vec<String>>,
+    pub(crate) mode: Option<MaintenanceMode>,
+}
+
+/// What a key may do, each scope also allows everything the ones before it do
+#[derive(Serialize, Deserialize, Clone, Enum, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub(crate) enum ApiKeyScope {
+    /// Only reads
+    Read,
+    /// Reads, creates, updates and deletes checks and everything attached to them
+    Write,
+    /// Also manages the keys and users of its project
+    Admin,
+    /// Also creates projects, sets their quotas and manages the keys of every project
+    Operator,
+}
+
+/// Key the API is called with, as a bearer token. Only its hash is kept
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct ApiKey {
+    pub(crate) _id: ObjectId,
+    /// Project the key acts on
+    #[serde(default = "crate::projects::default_project_id")]
+    pub(crate) project_id: ObjectId,
+    pub(crate) name: String,
+    pub(crate) scope: ApiKeyScope,
+    /// First characters of the key, enough to tell keys apart
+    pub(crate) prefix: String,
+    /// Hex encoded SHA-256 of the key
+    #[oai(skip)]
+    pub(crate) hash: String,
+    /// User the key acts for, revoked along with them
+    pub(crate) user_id: Option<ObjectId>,
+    #[serde(with = "crate::timestamps")]
+    pub(crate) created_at: DateTime<Utc>,
+    /// Revoked keys are refused
+    #[serde(default, with = "crate::timestamps::optional")]
+    pub(crate) revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct NewApiKey {
+    pub(crate) name: String,
+    /// Cannot be above the scope of the key minting it
+    pub(crate) scope: ApiKeyScope,
+    /// Defaults to the project of the key minting it, only operators mint keys for other projects
+    pub(crate) project_id: Option<ObjectId>,
+    /// User of the key's project the key acts for, its scope cannot be above theirs either
+    pub(crate) user_id: Option<ObjectId>,
+}
+
+/// A key just minted, the only time the key itself is returned
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct MintedApiKey {
+    #[oai(flatten)]
+    #[serde(flatten)]
+    pub(crate) api_key: ApiKey,
+    pub(crate) key: String,
+}
+
+/// Member of a project's team, keys minted for them never go above their scope
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct User {
+    pub(crate) _id: ObjectId,
+    pub(crate) project_id: ObjectId,
+    pub(crate) name: String,
+    pub(crate) email: String,
+    /// Highest scope of the keys minted for the user
+    pub(crate) scope: ApiKeyScope,
+    #[serde(with = "crate::timestamps")]
+    pub(crate) created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct NewUser {
+    pub(crate) name: String,
+    pub(crate) email: String,
+    /// Cannot be above the scope of the key adding the user
+    pub(crate) scope: ApiKeyScope,
+    /// Defaults to the project of the key adding the user, only operators add users to other
+    /// projects
+    pub(crate) project_id: Option<ObjectId>,
+}
+
+impl User {
+    pub(crate) fn from_new(new_user: NewUser, project_id: ObjectId) -> Self {
+        Self {
+            _id: ObjectId::new(),
+            project_id,
+            name: new_user.name,
+            email: new_user.email.trim().to_lowercase(),
+            scope: new_user.scope,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// Limits the checks of a project are held to, nothing is limited when unset
+#[derive(Serialize, Deserialize, Clone, Object, Default)]
+pub(crate) struct ProjectQuota {
+    /// Most checks the project may have
+    pub(crate) max_checks: Option<u32>,
+    /// Shortest time allowed between two runs of a check, heartbeat checks are not held to it
+    pub(crate) min_interval_secs: Option<u64>,
+}
+
+/// A team's own checks, channels, incidents and keys, hidden from every other project
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct Project {
+    pub(crate) _id: ObjectId,
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) quota: ProjectQuota,
+    #[serde(with = "crate::timestamps")]
+    pub(crate) created_at: DateTime<Utc>,
+    #[serde(with = "crate::timestamps")]
+    pub(crate) updated_at: DateTime<Utc>,
+}
+
+impl Project {
+    pub(crate) fn from_new(new_project: NewProject) -> Self {
+        Self {
+            _id: ObjectId::new(),
+            name: new_project.name,
+            quota: new_project.quota.unwrap_or_default(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct NewProject {
+    pub(crate) name: String,
+    pub(crate) quota: Option<ProjectQuota>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct UpdateProject {
+    pub(crate) name: Option<String>,
+    pub(crate) quota: Option<ProjectQuota>,
+}
+
+/// A group of checks shown as one line of a status page
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct StatusComponent {
+    pub(crate) name: String,
+    pub(crate) description: Option<String>,
+    pub(crate) checks: Vec<ObjectId>,
+}
+
+/// Look of a status page
+#[derive(Serialize, Deserialize, Clone, Object, Default)]
+pub(crate) struct StatusBranding {
+    /// Name of an image in `resources/status`, shown above the title
+    pub(crate) logo: Option<String>,
+    /// Hex color of the header and links, such as `#1f6feb`
+    pub(crate) accent_color: Option<String>,
+}
+
+/// Public page showing the state, uptime and incidents of components, at `/status/<slug>`
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct StatusPage {
+    pub(crate) _id: ObjectId,
+    pub(crate) project_id: ObjectId,
+    /// Lowercase letters, digits and dashes
+    pub(crate) slug: String,
+    pub(crate) title: String,
+    pub(crate) description: Option<String>,
+    pub(crate) components: Vec<StatusComponent>,
+    #[serde(default)]
+    pub(crate) branding: StatusBranding,
+    #[serde(with = "crate::timestamps")]
+    pub(crate) created_at: DateTime<Utc>,
+    #[serde(with = "crate::timestamps")]
+    pub(crate) updated_at: DateTime<Utc>,
+}
+
+impl StatusPage {
+    pub(crate) fn from_new(new_page: NewStatusPage, project_id: ObjectId) -> Self {
+        Self {
+            _id: ObjectId::new(),
+            project_id,
+            slug: new_page.slug,
+            title: new_page.title,
+            description: new_page.description,
+            components: new_page.components,
+            branding: new_page.branding.unwrap_or_default(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct NewStatusPage {
+    pub(crate) slug: String,
+    pub(crate) title: String,
+    pub(crate) description: Option<String>,
+    pub(crate) components: Vec<StatusComponent>,
+    pub(crate) branding: Option<StatusBranding>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Object)]
+pub(crate) struct UpdateStatusPage {
+    pub(crate) slug: Option<String>,
+    pub(crate) title: Option<String>,
+    pub(crate) description: Option<Option<String>>,
+    pub(crate) components: Option<Vec<StatusComponent>>,
+    pub(crate) branding: Option<StatusBranding>,
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::oid::ObjectId;
+    use chrono::{DateTime, Utc};
+    use serde_json::{json, Value};
+
+    use super::{
+        Check, Frequency, Incident, IncidentUpdate, IncidentUpdateStatus, IntervalFrequency,
+        Status, WebhookData, MAX_INTERVAL_SECS,
+    };
+
+    fn interval(seconds: u64) -> Frequency {
+        Frequency::Interval(IntervalFrequency { seconds })
+    }
+
+    #[test]
+    fn intervals_are_bounded() {
+        assert!(interval(0).validate().is_err());
+        assert!(interval(MAX_INTERVAL_SECS).validate().is_ok());
+        assert!(interval(MAX_INTERVAL_SECS + 1).validate().is_err());
+        assert!(interval(10_000_000_000_000).validate().is_err());
+    }
+
+    #[test]
+    fn next_run_out_of_range_is_none() {
+        let now = Utc::now();
+        assert_eq!(
+            interval(60).next_after(now),
+            Some(now + chrono::Duration::seconds(60))
+        );
+        assert_eq!(interval(10_000_000_000_000).next_after(now), None);
+        assert_eq!(interval(u64::MAX).next_after(now), None);
+        assert_eq!(interval(60).next_after(DateTime::<Utc>::MAX_UTC), None);
+    }
+
+    fn is_rfc3339(value: &Value) -> bool {
+        value
+            .as_str()
+            .is_some_and(|at| DateTime::parse_from_rfc3339(at).is_ok())
+    }
+
+    #[test]
+    fn webhook_dates_are_rfc3339_strings() {
+        let new_check = json!({"url": "https://example.com", "frequency": {"seconds": 60}});
+        let mut check =
+            Check::from_new(serde_json::from_value(new_check).unwrap(), ObjectId::new());
+        check.last_run_at = Some(Utc::now());
+        let mut incident = Incident::open(&check, None);
+        incident.updates.push(IncidentUpdate {
+            status: IncidentUpdateStatus::Investigating,
+            message: "Looking into it".to_string(),
+            created_at: Utc::now(),
+        });
+        incident.resolve();
+
+        let data = WebhookData::new(Status::Ok, None, &check, incident);
+        let payload: Value = serde_json::from_str(&serde_json::to_string(&data).unwrap()).unwrap();
+        for pointer in [
+            "/incident/started_at",
+            "/incident/resolved_at",
+            "/incident/updates/0/created_at",
+        ] {
+            let at = payload.pointer(pointer).unwrap();
+            assert!(is_rfc3339(at), "{pointer}: {at}");
+        }
+    }
+
+    #[test]
+    fn webhooks_leave_out_request_details_and_tokens() {
+        let new_check = json!({
+            "url": "https://example.com",
+            "frequency": {"seconds": 60},
+            "headers": {"Authorization": "Bearer secret"},
+        });
+        let mut check =
+            Check::from_new(serde_json::from_value(new_check).unwrap(), ObjectId::new());
+        check.badge_token = Some("badge".to_string());
+        let incident = Incident::open(&check, None);
+
+        let data = WebhookData::new(Status::Error, None, &check, incident);
+        let payload = serde_json::to_string(&data).unwrap();
+        assert!(!payload.contains("secret"), "{payload}");
+        assert!(!payload.contains("badge"), "{payload}");
+        assert!(payload.contains("https://example.com"), "{payload}");
+    }
+}
@@ -1,178 +1,1310 @@
-use chrono::Utc;
-use futures::stream::StreamExt;
-use mongodb::Database;
-use mongodb::{bson::doc, Collection};
-use reqwest::Client;
+use bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig};
+use hickory_resolver::name_server::TokioConnectionProvider;
+use hickory_resolver::proto::rr::RecordType;
+use hickory_resolver::{Name, TokioResolver};
+use native_tls::TlsConnector;
+use reqwest::dns::{Addrs, Name as HostName, Resolve, Resolving};
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use reqwest::{Client, Method, RequestBuilder, Url};
 use serde_json::Value;
-use std::time::Duration;
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::task::Poll;
+use std::time::{Duration, Instant};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio::sync::Semaphore;
+use tower_layer::Layer;
+use tower_service::Service;
 use tracing::{error, info, warn};
 
-use crate::models::{Check, CheckHistory, Frequency, HTTPMethod, Status, WebhookData};
+use crate::assertions;
+use crate::certificates;
+use crate::config::Config;
+use crate::heartbeats;
+use crate::maintenance;
+use crate::metrics;
+use crate::models::{
+    Assertion, AssertionResult, Attempt, CertificatePolicy, Check, CheckHistory, CheckKind,
+    CheckState, DnsOptions, DnsRecordType, HTTPMethod, Incident, Latency, MaintenanceMode,
+    MaintenanceWindow, RequestBody, Status, WebhookData,
+};
+use crate::notifiers::{Notifier, NotifyContext};
+use crate::scheduler::{Command, Queue};
+use crate::store::{CheckFields, Storage, Store};
 
-pub(crate) async fn start(db: Database) {
-    let checks_collection = db.collection::<Check>("checks");
-    let history_collection = db.collection::<CheckHistory>("checks_history");
+const DEFAULT_TIMEOUT_MS: u64 = 30_000;
 
-    let client = Client::new();
+/// Everything a check execution needs, cheap to clone into spawned tasks
+#[derive(Clone)]
+struct Context {
+    store: Storage,
+    client: Client,
+    certificate_expiry_days: u32,
+}
+
+/// Caps how many checks run at once, overall and against a single host
+struct Limits {
+    global: Arc<Semaphore>,
+    per_host: Mutex<HashMap<String, Arc<Semaphore>>>,
+    max_per_host: usize,
+}
+
+impl Limits {
+    fn new(config: &Config) -> Self {
+        Self {
+            global: Arc::new(Semaphore::new(config.max_concurrent_checks.max(1))),
+            per_host: Mutex::new(HashMap::new()),
+            max_per_host: config.max_concurrent_checks_per_host.max(1),
+        }
+    }
+
+    fn host_semaphore(&self, check: &Check) -> Arc<Semaphore> {
+        let host = Url::parse(&check.url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_else(|| check.url.clone());
+
+        let mut per_host = self.per_host.lock().unwrap();
+        // Forget hosts nobody is waiting on so the map doesn't grow forever
+        per_host.retain(|_, semaphore| Arc::strong_count(semaphore) > 1);
+        per_host
+            .entry(host)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.max_per_host)))
+            .clone()
+    }
+}
+
+pub(crate) async fn start(
+    store: Storage,
+    config: Config,
+    mut commands: UnboundedReceiver<Command>,
+) {
+    let context = Context {
+        store,
+        client: Client::new(),
+        certificate_expiry_days: config.certificate_expiry_days,
+    };
+    let limits = Arc::new(Limits::new(&config));
+    let (finished_sender, mut finished) = unbounded_channel::<Check>();
     info!("Starting monitor task");
+
+    let mut queue = Queue::default();
+    load_checks(&context.store, &mut queue).await;
+
     loop {
-        fetch_and_execute_checks(&checks_collection, &history_collection, &client).await;
-        tokio::time::sleep(Duration::from_secs(3600)).await; // Sleep for 1 hour
+        let next_due = queue.next_due();
+        let wait_for_next = async {
+            match next_due {
+                Some(run_at) => {
+                    let delay = (run_at - Utc::now()).to_std().unwrap_or_default();
+                    tokio::time::sleep(delay).await
+                }
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            _ = wait_for_next => {
+                let now = Utc::now();
+                if let Some(lag) = next_due.and_then(|run_at| (now - run_at).to_std().ok()) {
+                    metrics::observe_scheduler_lag(lag);
+                }
+                for check in queue.pop_due(now) {
+                    let context = context.clone();
+                    let limits = limits.clone();
+                    let finished_sender = finished_sender.clone();
+                    tokio::spawn(async move {
+                        // Wait on the host first so a busy host doesn't hold global slots
+                        let host = limits.host_semaphore(&check);
+                        let _host_permit = host.acquire_owned().await;
+                        let _permit = limits.global.clone().acquire_owned().await;
+
+                        let check = run_check(check, &context).await;
+                        let _ = finished_sender.send(check);
+                    });
+                }
+            }
+            Some(check) = finished.recv() => queue.finish(check),
+            command = commands.recv() => match command {
+                Some(Command::Schedule(check)) => {
+                    info!("Scheduling check '{}'", check._id);
+                    queue.schedule(*check);
+                }
+                Some(Command::Unschedule(check_id)) => {
+                    info!("Unscheduling check '{}'", check_id);
+                    queue.unschedule(&check_id);
+                    metrics::forget_check(&check_id);
+                }
+                None => {
+                    warn!("Scheduler handle dropped, stopping monitor task");
+                    return;
+                }
+            }
+        }
     }
 }
 
-async fn fetch_and_execute_checks(
-    checks_collection: &Collection<Check>,
-    history_collection: &Collection<CheckHistory>,
-    client: &Client,
-) {
+async fn load_checks(store: &Storage, queue: &mut Queue) {
     info!("Fetching checks from database");
-    let cursor = checks_collection.find(doc! {}).await;
-    if cursor.is_err() {
-        error!("Error fetching checks from database");
+    let checks = match store.checks().await {
+        Ok(checks) => checks,
+        Err(err) => {
+            error!("Error fetching checks from database: {err}");
+            return;
+        }
+    };
+
+    for check in checks {
+        metrics::observe_check(&check);
+        queue.schedule(check);
+    }
+    info!("Scheduled {} checks", queue.len());
+}
+
+async fn run_check(mut check: Check, context: &Context) -> Check {
+    let window = match maintenance::open_window(&context.store, &check).await {
+        Ok(window) => window,
+        Err(err) => {
+            warn!(
+                "Error fetching maintenance windows of check '{}': {err}",
+                check._id
+            );
+            None
+        }
+    };
+    if let Some(MaintenanceWindow {
+        mode: MaintenanceMode::Skip,
+        ref name,
+        ..
+    }) = window
+    {
+        info!(
+            "Skipping check '{}' during maintenance window '{name}'",
+            check._id
+        );
+        save_last_run(&mut check, context).await;
+        return check;
+    }
+
+    info!("Executing check '{}'", check._id);
+    let attempts = execute_with_retries(&check).await;
+
+    let mut check_history = CheckHistory::new(check._id, attempts);
+    inspect_certificate(&check, &mut check_history, context).await;
+    if let Some(ref window) = window {
+        check_history.status = Status::Maintenance;
+        check_history.maintenance_window_id = Some(window._id);
+    }
+    metrics::observe_execution(&check, &check_history);
+    let status = check_history.status.clone();
+    let details = check_history.details.clone();
+
+    let saved = context.store.insert_history(&check_history).await;
+    if saved.is_err() {
+        warn!("Error saving history for check '{}'", check._id);
+    }
+
+    let previous_state = check.record(&status);
+    save_last_run(&mut check, context).await;
+    metrics::observe_check(&check);
+
+    // Incidents and alerts wait for the window to close
+    if window.is_some() {
+        return check;
+    }
+
+    // Only notify when the check is confirmed down or back up
+    let Some(incident) = track_incident(&check, previous_state, &details, context).await else {
+        return check;
+    };
+
+    // Slow channels must not hold the check's concurrency slots
+    let data = WebhookData::new(status, details, &check, incident);
+    let channels = check.channels.clone();
+    let context = context.clone();
+    tokio::spawn(async move { notify(&channels, &data, &context).await });
+    check
+}
+
+async fn save_last_run(check: &mut Check, context: &Context) {
+    let last_run_at = Utc::now();
+    check.last_run_at = Some(last_run_at);
+    let saved = context
+        .store
+        .update_check(
+            check._id,
+            &CheckFields {
+                last_run_at: Some(last_run_at),
+                current_state: Some(check.current_state.clone()),
+                consecutive_failures: Some(check.consecutive_failures),
+                consecutive_successes: Some(check.consecutive_successes),
+                ..Default::default()
+            },
+        )
+        .await;
+    if saved.is_err() {
+        warn!("Error saving last run for check '{}'", check._id);
+    }
+}
+
+/// Where the certificate of a check is served from, if it has one
+fn certificate_target(check: &Check) -> Option<(String, u16)> {
+    match check.kind {
+        CheckKind::Http => {
+            let url = Url::parse(&check.url).ok()?;
+            if url.scheme() != "https" {
+                return None;
+            }
+            let host = url
+                .host_str()?
+                .trim_start_matches('[')
+                .trim_end_matches(']');
+            Some((host.to_string(), url.port_or_known_default()?))
+        }
+        CheckKind::Tls => split_host_port(&check.url, Some(443)).ok(),
+        CheckKind::Tcp | CheckKind::Dns | CheckKind::Heartbeat => None,
+    }
+}
+
+/// Attach the certificate of HTTPS and TLS checks to the entry, failing it if the check's policy says so
+async fn inspect_certificate(check: &Check, history: &mut CheckHistory, context: &Context) {
+    let policy = check.certificate_policy.clone().unwrap_or_default();
+    if policy == CertificatePolicy::Ignore {
         return;
     }
-    let mut cursor = cursor.unwrap();
-
-    while let Some(result) = cursor.next().await {
-        if let Ok(check) = result {
-            let history = history_collection
-                .find_one(doc! {
-                    "check_id": check._id,
-                })
-                .await;
-
-            if history.is_err() {
-                warn!("Error fetching history for check '{}'", check._id);
-                continue;
-            }
-            let history = history.unwrap();
-
-            match (&check.frequency, history) {
-                (Frequency::Hourly, Some(h)) => {
-                    if h.created_at > Utc::now() - chrono::Duration::weeks(1) {
-                        // If previous ping was greater then one week ago, ignore it
-                        info!(
-                            "Skipping check '{}' because it was executed less than one hour ago",
-                            check._id
-                        );
-                        continue;
-                    }
-                }
-                (Frequency::Daily, Some(h)) => {
-                    if h.created_at > Utc::now() - chrono::Duration::days(1) {
-                        // If previous ping was greater then one day ago, ignore it
-                        info!(
-                            "Skipping check '{}' because it was executed less than one day ago",
-                            check._id
-                        );
-                        continue;
-                    }
-                }
-                (Frequency::Weekly, Some(h)) => {
-                    if h.created_at > Utc::now() - chrono::Duration::weeks(1) {
-                        // If previous ping was greater then one week ago, ignore it
-                        info!(
-                            "Skipping check '{}' because it was executed less than one week ago",
-                            check._id
-                        );
-                        continue;
-                    }
-                }
-                _ => (),
+    let Some((host, port)) = certificate_target(check) else {
+        return;
+    };
+
+    let timeout = Duration::from_millis(check.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS));
+    let mut tls = match certificates::inspect(&host, port, timeout).await {
+        Ok(tls) => tls,
+        Err(err) => {
+            warn!(
+                "Error inspecting the certificate of check '{}': {err}",
+                check._id
+            );
+            return;
+        }
+    };
+    tls.evaluate(
+        check
+            .certificate_expiry_days
+            .unwrap_or(context.certificate_expiry_days),
+    );
+
+    if policy == CertificatePolicy::Fail && !tls.problems.is_empty() {
+        let problems = tls.problems.join("; ");
+        history.status = Status::Error;
+        history.details = Some(match history.details.take() {
+            Some(details) => format!("{details}; {problems}"),
+            None => problems,
+        });
+    }
+    history.tls = Some(tls);
+}
+
+/// Send the state change to every channel of the check
+async fn notify(channel_ids: &[ObjectId], data: &WebhookData, context: &Context) {
+    if channel_ids.is_empty() {
+        return;
+    }
+
+    let channels = match context.store.channels_by_id(channel_ids).await {
+        Ok(channels) => channels,
+        Err(err) => {
+            warn!(
+                "Error fetching channels for check '{}': {err}",
+                data.check._id
+            );
+            return;
+        }
+    };
+
+    for channel in channels {
+        let notify_context = NotifyContext {
+            client: &context.client,
+            channel_id: channel._id,
+            store: &context.store,
+        };
+        match channel.config.notify(&notify_context, data).await {
+            Ok(()) => info!(
+                "Notified channel '{}' about check '{}'",
+                channel.name, data.check._id
+            ),
+            Err(err) => warn!(
+                "Error notifying channel '{}' about check '{}': {err}",
+                channel.name, data.check._id
+            ),
+        }
+    }
+}
+
+/// Open, update or resolve the check's incident according to its state
+/// change. Returns the incident when it was just opened or resolved.
+async fn track_incident(
+    check: &Check,
+    previous_state: CheckState,
+    details: &Option<String>,
+    context: &Context,
+) -> Option<Incident> {
+    let was_down = previous_state == CheckState::Down;
+    let is_down = check.current_state == CheckState::Down;
+    if !was_down && !is_down {
+        return None;
+    }
+
+    if !was_down {
+        let mut incident = Incident::open(check, details.clone());
+        incident.failures = check.consecutive_failures;
+        info!(
+            "Opening incident '{}' for check '{}'",
+            incident._id, check._id
+        );
+        if let Err(err) = context.store.insert_incident(&incident).await {
+            warn!("Error saving incident for check '{}': {err}", check._id);
+        }
+        return Some(incident);
+    }
+
+    let mut incident = match context.store.open_incident(check._id).await {
+        Ok(Some(incident)) => incident,
+        Ok(None) => {
+            warn!("Check '{}' is down without an open incident", check._id);
+            return None;
+        }
+        Err(err) => {
+            warn!(
+                "Error fetching open incident for check '{}': {err}",
+                check._id
+            );
+            return None;
+        }
+    };
+
+    if is_down {
+        // Still down, either failing again or not recovered enough times yet
+        if check.consecutive_failures > 0 {
+            incident.last_details = details.clone();
+            incident.failures += 1;
+            if let Err(err) = context.store.save_incident(&incident).await {
+                warn!("Error updating incident '{}': {err}", incident._id);
+            }
+        }
+        return None;
+    }
+
+    incident.resolve();
+    info!(
+        "Resolving incident '{}' for check '{}'",
+        incident._id, check._id
+    );
+    if let Err(err) = context.store.save_incident(&incident).await {
+        warn!("Error resolving incident '{}': {err}", incident._id);
+    }
+    Some(incident)
+}
+
+/// Result of probing a check's endpoint, along with what was measured on the way
+pub(crate) struct CheckResult {
+    pub(crate) outcome: Result<(), String>,
+    pub(crate) status_code: Option<u16>,
+    pub(crate) response_size: Option<u64>,
+    pub(crate) assertions: Vec<AssertionResult>,
+    pub(crate) latency: Latency,
+}
+
+impl CheckResult {
+    fn into_attempt(self, started_at: DateTime<Utc>) -> Attempt {
+        let (status, details) = match self.outcome {
+            Ok(()) => (Status::Ok, None),
+            Err(err) => (Status::Error, Some(err)),
+        };
+        Attempt {
+            status,
+            details,
+            status_code: self.status_code,
+            response_size: self.response_size,
+            latency: Some(self.latency),
+            assertions: self.assertions,
+            started_at,
+        }
+    }
+
+    fn failed(error: String, latency: Latency) -> Self {
+        Self {
+            outcome: Err(error),
+            status_code: None,
+            response_size: None,
+            assertions: vec![],
+            latency,
+        }
+    }
+}
+
+fn elapsed_ms(since: Instant) -> u64 {
+    since.elapsed().as_millis() as u64
+}
+
+/// DNS and connect timings of the connection a request goes through, recorded by
+/// the resolver and the connector of the client built for the request
+#[derive(Clone, Default)]
+struct ConnectionTimings {
+    dns_ms: Arc<Mutex<Option<u64>>>,
+    connect_ms: Arc<Mutex<Option<u64>>>,
+}
+
+/// TLS settings of the clients built for requests, loading the root certificates only once
+static TLS: LazyLock<Result<TlsConnector, String>> =
+    LazyLock::new(|| TlsConnector::new().map_err(|err| err.to_string()));
+
+impl ConnectionTimings {
+    /// Client opening a new connection for each request, so its timings are always measured.
+    /// Only its resolver and connector are its own, the TLS settings are shared.
+    fn client(&self) -> Result<Client, String> {
+        let tls = TLS.clone()?;
+        Client::builder()
+            .use_preconfigured_tls(tls)
+            .dns_resolver(Arc::new(self.clone()))
+            .connector_layer(self.clone())
+            .pool_max_idle_per_host(0)
+            .build()
+            .map_err(|err| err.to_string())
+    }
+
+    fn record(&self, latency: &mut Latency) {
+        latency.dns_ms = *self.dns_ms.lock().unwrap();
+        latency.connect_ms = *self.connect_ms.lock().unwrap();
+    }
+}
+
+impl Resolve for ConnectionTimings {
+    fn resolve(&self, name: HostName) -> Resolving {
+        let dns_ms = self.dns_ms.clone();
+        Box::pin(async move {
+            let start = Instant::now();
+            // The connector sets the port, like the default resolver
+            let addrs = lookup_host((name.as_str(), 0))
+                .await
+                .map(|addrs| addrs.collect::<Vec<_>>());
+            *dns_ms.lock().unwrap() = Some(elapsed_ms(start));
+            let addrs: Addrs = Box::new(addrs?.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+impl<S> Layer<S> for ConnectionTimings {
+    type Service = TimedConnector<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TimedConnector {
+            inner,
+            timings: self.clone(),
+        }
+    }
+}
+
+/// Connector measuring how long connections take to open, the resolution it
+/// goes through is subtracted as it is measured on its own
+#[derive(Clone)]
+struct TimedConnector<S> {
+    inner: S,
+    timings: ConnectionTimings,
+}
+
+impl<S, R> Service<R> for TimedConnector<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        let start = Instant::now();
+        let connecting = self.inner.call(request);
+        let timings = self.timings.clone();
+        Box::pin(async move {
+            let connection = connecting.await?;
+            let dns_ms = timings.dns_ms.lock().unwrap().unwrap_or_default();
+            *timings.connect_ms.lock().unwrap() = Some(elapsed_ms(start).saturating_sub(dns_ms));
+            Ok(connection)
+        })
+    }
+}
+
+/// Execute the check until an attempt succeeds or the retry policy runs out
+async fn execute_with_retries(check: &Check) -> Vec<Attempt> {
+    let timeout = Duration::from_millis(check.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS));
+    let retries = check.retry.as_ref().map_or(0, |retry| retry.retries);
+
+    let mut attempts = vec![];
+    for attempt in 0..=retries {
+        if attempt > 0 {
+            if let Some(ref retry) = check.retry {
+                tokio::time::sleep(retry.delay(attempt)).await;
+            }
+            info!("Retrying check '{}', attempt {}", check._id, attempt + 1);
+        }
+
+        let started_at = Utc::now();
+        let start = Instant::now();
+        let result = match tokio::time::timeout(timeout, check.kind.execute(check)).await {
+            Ok(result) => result,
+            Err(_) => CheckResult::failed(
+                format!("Request timed out after {} ms", timeout.as_millis()),
+                Latency {
+                    total_ms: elapsed_ms(start),
+                    ..Default::default()
+                },
+            ),
+        };
+
+        let succeeded = result.outcome.is_ok();
+        attempts.push(result.into_attempt(started_at));
+        if succeeded {
+            break;
+        }
+    }
+    attempts
+}
+
+fn build_request(check: &Check, url: Url, client: &Client) -> RequestBuilder {
+    let method = match check.method {
+        HTTPMethod::HEAD => Method::HEAD,
+        HTTPMethod::GET => Method::GET,
+        HTTPMethod::POST => Method::POST,
+        HTTPMethod::PUT => Method::PUT,
+        HTTPMethod::PATCH => Method::PATCH,
+        HTTPMethod::DELETE => Method::DELETE,
+        HTTPMethod::OPTIONS => Method::OPTIONS,
+    };
+    let mut request = client.request(method, url);
+
+    if let Some(ref query) = check.query {
+        request = request.query(query);
+    }
+    if let Some(ref headers) = check.headers {
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+    }
+    match check.body {
+        Some(RequestBody::Json(ref body)) => request.json(&body.content),
+        Some(RequestBody::Form(ref body)) => request.form(&body.fields),
+        Some(RequestBody::Raw(ref body)) => {
+            if let Some(ref content_type) = body.content_type {
+                request = request.header(CONTENT_TYPE, content_type);
+            }
+            request.body(body.content.clone())
+        }
+        None => request,
+    }
+}
+
+/// A way of reaching a check's target
+pub(crate) trait Probe {
+    /// Make sure the check's target and options suit the probe before it is saved
+    fn validate(&self, check: &Check) -> Result<(), String>;
+
+    /// Reach the target once
+    async fn execute(&self, check: &Check) -> CheckResult;
+}
+
+struct HttpProbe;
+struct TcpProbe;
+struct DnsProbe;
+struct TlsProbe;
+struct HeartbeatProbe;
+
+impl Probe for HttpProbe {
+    fn validate(&self, check: &Check) -> Result<(), String> {
+        let url =
+            Url::parse(&check.url).map_err(|err| format!("Invalid url '{}': {err}", check.url))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("Unsupported scheme '{}'.", url.scheme()));
+        }
+        if check.dns.is_some() {
+            return Err("DNS options are only allowed on DNS checks.".to_string());
+        }
+        Ok(())
+    }
+
+    async fn execute(&self, check: &Check) -> CheckResult {
+        let start = Instant::now();
+        let mut latency = Latency::default();
+
+        let url = match Url::parse(&check.url) {
+            Ok(url) => url,
+            Err(err) => {
+                return CheckResult::failed(
+                    format!("Malformed url '{}', error '{}'", check.url, err),
+                    latency,
+                )
+            }
+        };
+        let timings = ConnectionTimings::default();
+        let client = match timings.client() {
+            Ok(client) => client,
+            Err(err) => return CheckResult::failed(err, latency),
+        };
+
+        let request_start = Instant::now();
+        let response = build_request(check, url, &client).send().await;
+        timings.record(&mut latency);
+        let response = match response {
+            Ok(resp) => resp,
+            Err(err) => {
+                latency.total_ms = elapsed_ms(start);
+                return CheckResult::failed(
+                    format!(
+                        "Malformed request: {} '{}', error '{}'",
+                        check.method, check.url, err
+                    ),
+                    latency,
+                );
+            }
+        };
+        latency.ttfb_ms = Some(elapsed_ms(request_start));
+
+        let status_code = response.status();
+        let headers = response.headers().clone();
+        let body = match check.method {
+            HTTPMethod::HEAD => Ok(Default::default()),
+            _ => response.bytes().await,
+        };
+        latency.total_ms = elapsed_ms(start);
+
+        let mut result = CheckResult {
+            outcome: Ok(()),
+            status_code: Some(status_code.as_u16()),
+            response_size: None,
+            assertions: vec![],
+            latency,
+        };
+
+        let body = match body {
+            Ok(body) => body,
+            Err(err) => {
+                result.outcome = Err(format!(
+                    "Malformed request: {} '{}', error '{}'",
+                    check.method, check.url, err
+                ));
+                return result;
+            }
+        };
+        result.response_size = Some(body.len() as u64);
+
+        let mut errors = vec![];
+
+        // A status code assertion replaces the default "no error status" rule
+        if status_code.as_u16() > 399 && !assertions::has_status_code_assertion(&check.assertions) {
+            errors.push(format!(
+                "Endpoint returned error status code: '{}'",
+                status_code
+            ));
+        } else if let Some(ref expected_body) = check.expected_body {
+            match serde_json::from_slice::<Value>(&body) {
+                Ok(v) if expected_body == &v => (),
+                Ok(v) => errors.push(format!(
+                    "Endpoint returned unexpected body: {} != {}",
+                    v, expected_body
+                )),
+                Err(err) => errors.push(format!(
+                    "Malformed request: {} '{}', error '{}'",
+                    check.method, check.url, err
+                )),
             };
+        }
 
-            let details = execute_check(&check, client).await;
+        let response = assertions::Response {
+            status_code: status_code.as_u16(),
+            headers: &headers,
+            body: &body,
+            latency_ms: result.latency.total_ms,
+        };
+        result.assertions = assertions::evaluate(&check.assertions, &response);
+        errors.extend(
+            result
+                .assertions
+                .iter()
+                .filter_map(|assertion| assertion.details.clone()),
+        );
 
-            let status = details
-                .as_ref()
-                .map_or_else(|_| Status::Error, |_| Status::Ok);
-            let details = details.err();
+        if !errors.is_empty() {
+            result.outcome = Err(errors.join("; "));
+        }
+        result
+    }
+}
 
-            let check_history = CheckHistory::new(check._id, status.clone(), details.clone());
-            let previous_status = check_history.status.clone();
+/// Checks other than HTTP have no request to customize and no response to assert on
+fn validate_not_http(check: &Check) -> Result<(), String> {
+    if check.headers.is_some()
+        || check.query.is_some()
+        || check.body.is_some()
+        || check.expected_body.is_some()
+    {
+        return Err(format!(
+            "Headers, query parameters and bodies are not allowed on {} checks.",
+            check.kind
+        ));
+    }
+    if check
+        .assertions
+        .iter()
+        .any(|assertion| !matches!(assertion, Assertion::MaxLatency(_)))
+    {
+        return Err(format!(
+            "Only latency assertions are allowed on {} checks.",
+            check.kind
+        ));
+    }
+    if check.kind != CheckKind::Dns && check.dns.is_some() {
+        return Err("DNS options are only allowed on DNS checks.".to_string());
+    }
+    Ok(())
+}
 
-            let result = history_collection.insert_one(check_history).await;
-            if result.is_err() {
-                warn!("Error saving history for check '{}'", check._id);
+/// Split `host:port`, brackets around IPv6 addresses are removed
+pub(crate) fn split_host_port(
+    target: &str,
+    default_port: Option<u16>,
+) -> Result<(String, u16), String> {
+    let invalid = || format!("Invalid target '{target}', expected 'host:port'");
+    let (host, port) = match target.rsplit_once(':') {
+        // A bare IPv6 address has colons but no brackets
+        Some((host, port)) if !host.contains(':') || host.ends_with(']') => {
+            (host, Some(port.parse::<u16>().map_err(|_| invalid())?))
+        }
+        _ => (target, None),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match port.or(default_port) {
+        Some(port) if port > 0 && !host.is_empty() && !host.contains('/') => {
+            Ok((host.to_string(), port))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Wrap up a probe that reached its target, only latency assertions apply
+fn reached(check: &Check, latency: Latency) -> CheckResult {
+    let headers = HeaderMap::new();
+    let response = assertions::Response {
+        status_code: 0,
+        headers: &headers,
+        body: &[],
+        latency_ms: latency.total_ms,
+    };
+    let assertions = assertions::evaluate(&check.assertions, &response);
+    let errors: Vec<String> = assertions
+        .iter()
+        .filter_map(|assertion| assertion.details.clone())
+        .collect();
+    CheckResult {
+        outcome: if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        },
+        status_code: None,
+        response_size: None,
+        assertions,
+        latency,
+    }
+}
+
+impl Probe for TcpProbe {
+    fn validate(&self, check: &Check) -> Result<(), String> {
+        validate_not_http(check)?;
+        split_host_port(&check.url, None).map(|_| ())
+    }
+
+    async fn execute(&self, check: &Check) -> CheckResult {
+        let start = Instant::now();
+        let mut latency = Latency::default();
+        let (host, port) = match split_host_port(&check.url, None) {
+            Ok(target) => target,
+            Err(err) => return CheckResult::failed(err, latency),
+        };
+
+        let addr = lookup_host((host.as_str(), port))
+            .await
+            .map(|mut addrs| addrs.next());
+        latency.dns_ms = Some(elapsed_ms(start));
+        let addr = match addr {
+            Ok(Some(addr)) => addr,
+            Ok(None) => {
+                latency.total_ms = elapsed_ms(start);
+                return CheckResult::failed(format!("No address found for '{host}'"), latency);
+            }
+            Err(err) => {
+                latency.total_ms = elapsed_ms(start);
+                return CheckResult::failed(
+                    format!("Failed to resolve host '{host}': {err}"),
+                    latency,
+                );
             }
+        };
 
-            let data = WebhookData::new(status, details, check);
-            if let Some(ref hook) = data.check.hook {
-                if data.status == Status::Error || previous_status == Status::Error {
-                    let result = client.post(hook).json(&data).send().await;
-                    if result.is_err() {
-                        warn!("Error sending hook for check '{}'", data.check._id);
-                    }
-                } else {
-                    info!(
-                        "Skipping hook for check '{}', because status is OK and previous status was OK as well",
-                        data.check._id
-                    );
-                }
+        let connect_start = Instant::now();
+        let connected = TcpStream::connect(addr).await;
+        latency.total_ms = elapsed_ms(start);
+        match connected {
+            Ok(_) => {
+                latency.connect_ms = Some(elapsed_ms(connect_start));
+                reached(check, latency)
             }
+            Err(err) => CheckResult::failed(
+                format!("Failed to connect to '{}': {err}", check.url),
+                latency,
+            ),
         }
     }
-    info!("Finished executing checks");
 }
 
-async fn execute_check(check: &Check, client: &Client) -> Result<(), String> {
-    match check.method {
-        HTTPMethod::GET => {
-            let response = client.get(&check.url).send().await;
-            match response {
-                Ok(resp) => {
-                    if resp.status().as_u16() > 399 {
-                        return Err(format!(
-                            "Endpoint returned error status code: '{}'",
-                            resp.status()
-                        ));
-                    }
+impl DnsRecordType {
+    fn record_type(&self) -> RecordType {
+        match self {
+            DnsRecordType::A => RecordType::A,
+            DnsRecordType::AAAA => RecordType::AAAA,
+            DnsRecordType::CNAME => RecordType::CNAME,
+            DnsRecordType::MX => RecordType::MX,
+            DnsRecordType::NS => RecordType::NS,
+            DnsRecordType::TXT => RecordType::TXT,
+        }
+    }
+}
 
-                    if let Some(ref expected_body) = check.expected_body {
-                        match resp.json::<Value>().await {
-                            Ok(v) => {
-                                if expected_body == &v {
-                                    Ok(())
-                                } else {
-                                    Err(format!(
-                                        "Endpoint returned unexpected body: {} != {}",
-                                        v, expected_body
-                                    ))
-                                }
-                            }
-                            Err(err) => Err(format!(
-                                "Malformed request: GET '{}', error '{}'",
-                                check.url,
-                                err.to_string()
-                            )),
-                        }
-                    } else {
-                        Ok(())
-                    }
+/// Answers compare without case and without the root label
+fn normalize_answer(answer: &str) -> String {
+    answer.trim().trim_end_matches('.').to_lowercase()
+}
+
+fn parse_resolver(resolver: &str) -> Result<SocketAddr, String> {
+    resolver
+        .parse::<SocketAddr>()
+        .or_else(|_| resolver.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| format!("Invalid resolver '{resolver}', expected 'ip' or 'ip:port'"))
+}
+
+fn build_resolver(options: &DnsOptions) -> Result<TokioResolver, String> {
+    let builder = match options.resolver {
+        Some(ref resolver) => {
+            let addr = parse_resolver(resolver)?;
+            let name_servers =
+                NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true);
+            TokioResolver::builder_with_config(
+                ResolverConfig::from_parts(None, vec![], name_servers),
+                TokioConnectionProvider::default(),
+            )
+        }
+        None => TokioResolver::builder_tokio().map_err(|err| err.to_string())?,
+    };
+    Ok(builder.build())
+}
+
+impl Probe for DnsProbe {
+    fn validate(&self, check: &Check) -> Result<(), String> {
+        validate_not_http(check)?;
+        if check.url.is_empty() || check.url.contains(['/', ':']) {
+            return Err(format!(
+                "Invalid host name '{}', DNS checks expect a bare host name",
+                check.url
+            ));
+        }
+        Name::from_utf8(&check.url)
+            .map_err(|err| format!("Invalid host name '{}': {err}", check.url))?;
+        if let Some(ref options) = check.dns {
+            if let Some(ref resolver) = options.resolver {
+                parse_resolver(resolver)?;
+            }
+            if let Some(ref expected) = options.expected {
+                if expected.iter().any(|answer| answer.trim().is_empty()) {
+                    return Err("Expected answers cannot be empty.".to_string());
                 }
-                Err(err) => Err(err.to_string()),
-            }
-        }
-        HTTPMethod::HEAD => {
-            let response = client.head(&check.url).send().await;
-            match response {
-                Ok(resp) => {
-                    if resp.status().as_u16() > 399 {
-                        Err(format!(
-                            "Endpoint returned error status code: '{}'",
-                            resp.status()
-                        ))
-                    } else {
-                        Ok(())
+            }
+        }
+        Ok(())
+    }
+
+    async fn execute(&self, check: &Check) -> CheckResult {
+        let start = Instant::now();
+        let mut latency = Latency::default();
+        let options = check.dns.clone().unwrap_or_default();
+        let resolver = match build_resolver(&options) {
+            Ok(resolver) => resolver,
+            Err(err) => return CheckResult::failed(err, latency),
+        };
+
+        let record_type = options.record_type.unwrap_or_default().record_type();
+        let lookup = resolver.lookup(check.url.as_str(), record_type).await;
+        latency.dns_ms = Some(elapsed_ms(start));
+        latency.total_ms = elapsed_ms(start);
+        let answers: Vec<String> = match lookup {
+            Ok(lookup) => lookup
+                .record_iter()
+                .filter(|record| record.record_type() == record_type)
+                .map(|record| normalize_answer(&record.data().to_string()))
+                .collect(),
+            Err(err) => {
+                return CheckResult::failed(
+                    format!("Failed to resolve {record_type} '{}': {err}", check.url),
+                    latency,
+                )
+            }
+        };
+
+        let missing: Vec<&String> = options
+            .expected
+            .iter()
+            .flatten()
+            .filter(|expected| !answers.contains(&normalize_answer(expected)))
+            .collect();
+        if !missing.is_empty() {
+            return CheckResult::failed(
+                format!(
+                    "Expected answers {missing:?} not found for {record_type} '{}', got {answers:?}",
+                    check.url
+                ),
+                latency,
+            );
+        }
+        reached(check, latency)
+    }
+}
+
+impl Probe for TlsProbe {
+    fn validate(&self, check: &Check) -> Result<(), String> {
+        validate_not_http(check)?;
+        split_host_port(&check.url, Some(443)).map(|_| ())
+    }
+
+    async fn execute(&self, check: &Check) -> CheckResult {
+        let start = Instant::now();
+        let mut latency = Latency::default();
+        let (host, port) = match split_host_port(&check.url, Some(443)) {
+            Ok(target) => target,
+            Err(err) => return CheckResult::failed(err, latency),
+        };
+
+        let timeout = Duration::from_millis(check.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS));
+        let tls = certificates::inspect(&host, port, timeout).await;
+        latency.total_ms = elapsed_ms(start);
+        match tls {
+            Ok(tls) if !tls.trusted => CheckResult::failed(
+                format!(
+                    "Certificate is not valid: {}",
+                    tls.error.unwrap_or_default()
+                ),
+                latency,
+            ),
+            Ok(tls) if !tls.hostname_matches => CheckResult::failed(
+                "Certificate does not match the host name".to_string(),
+                latency,
+            ),
+            Ok(_) => reached(check, latency),
+            Err(err) => CheckResult::failed(err, latency),
+        }
+    }
+}
+
+impl Probe for HeartbeatProbe {
+    fn validate(&self, check: &Check) -> Result<(), String> {
+        validate_not_http(check)?;
+        let Some(ref heartbeat) = check.heartbeat else {
+            return Err("Heartbeat checks need a ping URL.".to_string());
+        };
+        if check.url != heartbeats::ping_path(&heartbeat.token) {
+            return Err(
+                "The URL of a heartbeat check is its ping path, it cannot be changed.".to_string(),
+            );
+        }
+        if check.retry.is_some() {
+            return Err("Retries are not allowed on heartbeat checks.".to_string());
+        }
+        Ok(())
+    }
+
+    /// Nothing is reached, the pings received so far are evaluated instead.
+    /// The latency is how long the job last ran.
+    async fn execute(&self, check: &Check) -> CheckResult {
+        let Some(ref heartbeat) = check.heartbeat else {
+            return CheckResult::failed(
+                "Heartbeat check has no ping URL".to_string(),
+                Latency::default(),
+            );
+        };
+        let latency = Latency {
+            total_ms: heartbeat.duration_ms.unwrap_or_default(),
+            ..Default::default()
+        };
+        match heartbeats::evaluate(check, heartbeat) {
+            Ok(()) => reached(check, latency),
+            Err(err) => CheckResult::failed(err, latency),
+        }
+    }
+}
+
+impl Probe for CheckKind {
+    fn validate(&self, check: &Check) -> Result<(), String> {
+        match self {
+            CheckKind::Http => HttpProbe.validate(check),
+            CheckKind::Tcp => TcpProbe.validate(check),
+            CheckKind::Dns => DnsProbe.validate(check),
+            CheckKind::Tls => TlsProbe.validate(check),
+            CheckKind::Heartbeat => HeartbeatProbe.validate(check),
+        }
+    }
+
+    async fn execute(&self, check: &Check) -> CheckResult {
+        match self {
+            CheckKind::Http => HttpProbe.execute(check).await,
+            CheckKind::Tcp => TcpProbe.execute(check).await,
+            CheckKind::Dns => DnsProbe.execute(check).await,
+            CheckKind::Tls => TlsProbe.execute(check).await,
+            CheckKind::Heartbeat => HeartbeatProbe.execute(check).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+
+    use bson::oid::ObjectId;
+    use chrono::Utc;
+    use hickory_resolver::proto::op::{Message, MessageType};
+    use hickory_resolver::proto::rr::rdata::A;
+    use hickory_resolver::proto::rr::{RData, Record};
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+    use rustls::ServerConfig;
+    use serde_json::json;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, UdpSocket};
+    use tokio_rustls::TlsAcceptor;
+
+    use super::{DnsProbe, HeartbeatProbe, HttpProbe, Probe, TcpProbe, TlsProbe};
+    use crate::models::{Check, CheckKind, DnsOptions, Heartbeat, HeartbeatSignal};
+
+    /// Self-signed certificate for `localhost`, valid until 2125
+    const CERTIFICATE: &[u8] = include_bytes!("../resources/tests/localhost.crt.der");
+    const PRIVATE_KEY: &[u8] = include_bytes!("../resources/tests/localhost.key.der");
+
+    fn new_check(kind: CheckKind, url: String) -> Check {
+        let new_check = json!({"frequency": {"seconds": 60}});
+        let mut check =
+            Check::from_new(serde_json::from_value(new_check).unwrap(), ObjectId::new());
+        check.kind = kind;
+        check.url = url;
+        check
+    }
+
+    /// Accept connections and answer each with `response` until the test ends
+    async fn serve(response: &'static [u8]) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).await;
+                let _ = stream.write_all(response).await;
+                let _ = stream.shutdown().await;
+            }
+        });
+        port
+    }
+
+    /// Port nothing listens on anymore
+    async fn closed_port() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    /// Complete TLS handshakes with the self-signed `localhost` certificate
+    async fn serve_tls() -> u16 {
+        let config =
+            ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(
+                    vec![CertificateDer::from(CERTIFICATE)],
+                    PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(PRIVATE_KEY)),
+                )
+                .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    if let Ok(mut stream) = acceptor.accept(stream).await {
+                        let _ = stream.shutdown().await;
                     }
+                });
+            }
+        });
+        port
+    }
+
+    /// Name server answering every query with a single A record
+    async fn serve_dns(answer: Ipv4Addr) -> u16 {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = socket.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut buffer = [0; 512];
+            while let Ok((len, peer)) = socket.recv_from(&mut buffer).await {
+                let Ok(query) = Message::from_vec(&buffer[..len]) else {
+                    continue;
+                };
+                let mut response = Message::new();
+                response
+                    .set_id(query.id())
+                    .set_message_type(MessageType::Response)
+                    .set_op_code(query.op_code())
+                    .set_recursion_desired(query.recursion_desired())
+                    .set_recursion_available(true)
+                    .add_queries(query.queries().to_vec());
+                for question in query.queries() {
+                    response.add_answer(Record::from_rdata(
+                        question.name().clone(),
+                        60,
+                        RData::A(A(answer)),
+                    ));
                 }
-                Err(err) => Err(format!(
-                    "Malformed request: HEAD '{}', error '{}'",
-                    check.url,
-                    err.to_string()
-                )),
+                let _ = socket.send_to(&response.to_vec().unwrap(), peer).await;
             }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn http_probe_reads_the_response() {
+        let port =
+            serve(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok").await;
+        let check = new_check(CheckKind::Http, format!("http://localhost:{port}/"));
+        let result = HttpProbe.execute(&check).await;
+        assert_eq!(result.outcome, Ok(()));
+        assert_eq!(result.status_code, Some(200));
+        assert_eq!(result.response_size, Some(2));
+        assert!(result.latency.dns_ms.is_some());
+        assert!(result.latency.connect_ms.is_some());
+
+        let port = serve(
+            b"HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+        )
+        .await;
+        let check = new_check(CheckKind::Http, format!("http://127.0.0.1:{port}/"));
+        let result = HttpProbe.execute(&check).await;
+        assert!(result.outcome.unwrap_err().contains("503"));
+        assert_eq!(result.status_code, Some(503));
+    }
+
+    #[tokio::test]
+    async fn http_probe_verifies_certificates_on_every_request() {
+        let port = serve_tls().await;
+        let check = new_check(CheckKind::Http, format!("https://localhost:{port}/"));
+        // The shared TLS settings are built on the first request and reused on the next
+        for _ in 0..2 {
+            let result = HttpProbe.execute(&check).await;
+            assert!(result.outcome.unwrap_err().starts_with("Malformed request"));
+            assert!(result.latency.dns_ms.is_some());
+            assert_eq!(result.status_code, None);
         }
     }
+
+    #[tokio::test]
+    async fn tcp_probe_connects() {
+        let port = serve(b"").await;
+        let check = new_check(CheckKind::Tcp, format!("127.0.0.1:{port}"));
+        assert_eq!(TcpProbe.validate(&check), Ok(()));
+        let result = TcpProbe.execute(&check).await;
+        assert_eq!(result.outcome, Ok(()));
+        assert!(result.latency.connect_ms.is_some());
+
+        let check = new_check(CheckKind::Tcp, format!("127.0.0.1:{}", closed_port().await));
+        let result = TcpProbe.execute(&check).await;
+        assert!(result.outcome.unwrap_err().starts_with("Failed to connect"));
+    }
+
+    #[tokio::test]
+    async fn dns_probe_compares_answers() {
+        let port = serve_dns(Ipv4Addr::new(192, 0, 2, 10)).await;
+        let mut check = new_check(CheckKind::Dns, "service.test".to_string());
+        check.dns = Some(DnsOptions {
+            expected: Some(vec!["192.0.2.10".to_string()]),
+            resolver: Some(format!("127.0.0.1:{port}")),
+            ..Default::default()
+        });
+        assert_eq!(DnsProbe.validate(&check), Ok(()));
+        let result = DnsProbe.execute(&check).await;
+        assert_eq!(result.outcome, Ok(()));
+        assert!(result.latency.dns_ms.is_some());
+
+        check.dns.as_mut().unwrap().expected = Some(vec!["192.0.2.20".to_string()]);
+        let result = DnsProbe.execute(&check).await;
+        let error = result.outcome.unwrap_err();
+        assert!(error.contains("192.0.2.20"), "{error}");
+        assert!(error.contains("192.0.2.10"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn tls_probe_verifies_the_certificate() {
+        let port = serve_tls().await;
+        let check = new_check(CheckKind::Tls, format!("localhost:{port}"));
+        assert_eq!(TlsProbe.validate(&check), Ok(()));
+        let result = TlsProbe.execute(&check).await;
+        let error = result.outcome.unwrap_err();
+        assert!(error.starts_with("Certificate is not valid"), "{error}");
+
+        let port = serve(b"HTTP/1.1 400 Bad Request\r\n\r\n").await;
+        let check = new_check(CheckKind::Tls, format!("localhost:{port}"));
+        let result = TlsProbe.execute(&check).await;
+        let error = result.outcome.unwrap_err();
+        assert!(error.starts_with("TLS handshake failed"), "{error}");
+
+        let check = new_check(CheckKind::Tls, format!("localhost:{}", closed_port().await));
+        let result = TlsProbe.execute(&check).await;
+        assert!(result.outcome.is_err());
+    }
+
+    #[tokio::test]
+    async fn heartbeat_probe_evaluates_pings() {
+        let token = "0123456789abcdef".to_string();
+        let mut check = new_check(CheckKind::Heartbeat, crate::heartbeats::ping_path(&token));
+        check.heartbeat = Some(Heartbeat {
+            token,
+            last_ping_at: Some(Utc::now()),
+            last_signal: Some(HeartbeatSignal::Success),
+            started_at: None,
+            duration_ms: Some(1500),
+        });
+        assert_eq!(HeartbeatProbe.validate(&check), Ok(()));
+        let result = HeartbeatProbe.execute(&check).await;
+        assert_eq!(result.outcome, Ok(()));
+        assert_eq!(result.latency.total_ms, 1500);
+
+        check.heartbeat.as_mut().unwrap().last_signal = Some(HeartbeatSignal::Fail);
+        let result = HeartbeatProbe.execute(&check).await;
+        assert!(result
+            .outcome
+            .unwrap_err()
+            .starts_with("Job reported a failure"));
+    }
 }
@@ -0,0 +1,505 @@
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use bson::oid::ObjectId;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use regex::{Captures, Regex};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, Url};
+use serde_json::{json, Value};
+use tracing::warn;
+
+use crate::deliveries;
+use crate::models::{
+    validate_headers, ChannelConfig, CheckKind, CheckState, DiscordChannel, EmailChannel,
+    NotifiedCheck, OpsgenieChannel, OpsgenieRegion, PagerDutyChannel, SlackChannel, TeamsChannel,
+    WebhookChannel, WebhookData, WebhookDelivery,
+};
+use crate::store::{Storage, Store};
+
+/// Channels that don't answer in time are given up on, so alerts to other channels aren't held up
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Shown instead of credentials when channels are read
+const REDACTED: &str = "********";
+
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{\s*([A-Za-z0-9_.]+)\s*\}\}").unwrap());
+
+/// Something able to tell people that a check went down or came back up
+pub(crate) trait Notifier {
+    async fn notify(&self, context: &NotifyContext<'_>, data: &WebhookData) -> Result<(), String>;
+}
+
+/// What notifiers may need besides the payload
+pub(crate) struct NotifyContext<'a> {
+    pub(crate) client: &'a Client,
+    pub(crate) channel_id: ObjectId,
+    pub(crate) store: &'a Storage,
+}
+
+fn is_down(data: &WebhookData) -> bool {
+    data.state == CheckState::Down
+}
+
+/// What the check watches, heartbeat checks have no URL to show
+fn target(check: &NotifiedCheck) -> String {
+    match check.url {
+        Some(ref url) => url.clone(),
+        None => check.name.clone().unwrap_or_else(|| check._id.to_hex()),
+    }
+}
+
+fn summary(data: &WebhookData) -> String {
+    let check = &data.check;
+    let target = match check.kind {
+        CheckKind::Http => format!("{} {}", check.method, target(check)),
+        ref kind => format!("{kind} {}", target(check)),
+    };
+    if is_down(data) {
+        format!("🔴 {target} is down")
+    } else {
+        format!("🟢 {target} is back up")
+    }
+}
+
+fn description(data: &WebhookData) -> String {
+    let incident = &data.incident;
+    if is_down(data) {
+        format!(
+            "Down since {}: {}",
+            incident.started_at.to_rfc3339(),
+            incident.details.as_deref().unwrap_or("no details")
+        )
+    } else {
+        format!(
+            "Recovered after {} seconds, {} failed executions",
+            incident.duration_secs.unwrap_or_default(),
+            incident.failures
+        )
+    }
+}
+
+async fn post_json(client: &Client, url: &str, body: &Value) -> Result<(), String> {
+    let response = client
+        .post(url)
+        .timeout(NOTIFY_TIMEOUT)
+        .json(body)
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Channel responded with status '{}'",
+            response.status()
+        ))
+    }
+}
+
+impl Notifier for SlackChannel {
+    async fn notify(&self, context: &NotifyContext<'_>, data: &WebhookData) -> Result<(), String> {
+        let client = context.client;
+        let text = format!("*{}*\n{}", summary(data), description(data));
+        post_json(client, &self.webhook_url, &json!({ "text": text })).await
+    }
+}
+
+impl Notifier for TeamsChannel {
+    async fn notify(&self, context: &NotifyContext<'_>, data: &WebhookData) -> Result<(), String> {
+        let client = context.client;
+        let body = json!({
+            "@type": "MessageCard",
+            "@context": "https://schema.org/extensions",
+            "summary": summary(data),
+            "themeColor": if is_down(data) { "D7263D" } else { "2EB886" },
+            "title": summary(data),
+            "text": description(data),
+        });
+        post_json(client, &self.webhook_url, &body).await
+    }
+}
+
+impl Notifier for DiscordChannel {
+    async fn notify(&self, context: &NotifyContext<'_>, data: &WebhookData) -> Result<(), String> {
+        let client = context.client;
+        let content = format!("**{}**\n{}", summary(data), description(data));
+        post_json(client, &self.webhook_url, &json!({ "content": content })).await
+    }
+}
+
+impl Notifier for PagerDutyChannel {
+    async fn notify(&self, context: &NotifyContext<'_>, data: &WebhookData) -> Result<(), String> {
+        let client = context.client;
+        // The incident id ties the trigger and resolve events together
+        let body = json!({
+            "routing_key": self.routing_key,
+            "event_action": if is_down(data) { "trigger" } else { "resolve" },
+            "dedup_key": data.incident._id.to_hex(),
+            "payload": {
+                "summary": summary(data),
+                "source": target(&data.check),
+                "severity": "critical",
+                "custom_details": {
+                    "details": description(data),
+                    "check_id": data.check._id.to_hex(),
+                },
+            },
+        });
+        post_json(client, "https://events.pagerduty.com/v2/enqueue", &body).await
+    }
+}
+
+impl Notifier for OpsgenieChannel {
+    async fn notify(&self, context: &NotifyContext<'_>, data: &WebhookData) -> Result<(), String> {
+        let client = context.client;
+        let base_url = match self.region {
+            Some(OpsgenieRegion::EU) => "https://api.eu.opsgenie.com/v2/alerts",
+            _ => "https://api.opsgenie.com/v2/alerts",
+        };
+        let alias = data.incident._id.to_hex();
+        let request = if is_down(data) {
+            client.post(base_url).json(&json!({
+                "message": summary(data),
+                "alias": alias,
+                "description": description(data),
+                "source": target(&data.check),
+                "priority": "P1",
+            }))
+        } else {
+            client
+                .post(format!("{base_url}/{alias}/close?identifierType=alias"))
+                .json(&json!({ "note": description(data) }))
+        };
+
+        let response = request
+            .timeout(NOTIFY_TIMEOUT)
+            .header("Authorization", format!("GenieKey {}", self.api_key))
+            .send()
+            .await
+            .map_err(|err| err.to_string())?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Channel responded with status '{}'",
+                response.status()
+            ))
+        }
+    }
+}
+
+impl EmailChannel {
+    fn message(&self, data: &WebhookData) -> Result<Message, String> {
+        let mut builder = Message::builder()
+            .from(parse_mailbox(&self.from)?)
+            .subject(summary(data))
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            builder = builder.to(parse_mailbox(to)?);
+        }
+        builder
+            .body(description(data))
+            .map_err(|err| err.to_string())
+    }
+
+    fn transport(&self) -> Result<AsyncSmtpTransport<Tokio1Executor>, String> {
+        let mut builder = if self.starttls.unwrap_or(true) {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.smtp_host)
+                .map_err(|err| err.to_string())?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.smtp_host)
+        };
+        builder = builder
+            .port(self.smtp_port.unwrap_or(587))
+            .timeout(Some(NOTIFY_TIMEOUT));
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        Ok(builder.build())
+    }
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox, String> {
+    address
+        .parse()
+        .map_err(|err| format!("Invalid email address '{address}': {err}"))
+}
+
+impl Notifier for EmailChannel {
+    async fn notify(&self, _context: &NotifyContext<'_>, data: &WebhookData) -> Result<(), String> {
+        let message = self.message(data)?;
+        self.transport()?
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+}
+
+impl WebhookChannel {
+    /// Replace every `{{path}}` in the template with the payload's field at `path`
+    fn render(&self, template: &str, data: &WebhookData) -> String {
+        let payload = serde_json::to_value(data).unwrap_or_default();
+        PLACEHOLDER
+            .replace_all(template, |captures: &Captures| {
+                let pointer = format!("/{}", captures[1].replace('.', "/"));
+                match payload.pointer(&pointer) {
+                    Some(Value::String(value)) => value.clone(),
+                    Some(Value::Object(value)) if value.contains_key("$oid") => {
+                        value["$oid"].as_str().unwrap_or_default().to_string()
+                    }
+                    Some(Value::Null) | None => String::new(),
+                    Some(value) => value.to_string(),
+                }
+            })
+            .into_owned()
+    }
+}
+
+impl Notifier for WebhookChannel {
+    async fn notify(&self, context: &NotifyContext<'_>, data: &WebhookData) -> Result<(), String> {
+        let payload = match self.template {
+            Some(ref template) => self.render(template, data),
+            None => serde_json::to_string(data).map_err(|err| err.to_string())?,
+        };
+        let content_type = self
+            .headers
+            .iter()
+            .flatten()
+            .find(|(name, _)| name.eq_ignore_ascii_case(CONTENT_TYPE.as_str()))
+            .map_or_else(
+                || "application/json".to_string(),
+                |(_, value)| value.clone(),
+            );
+
+        // Failed deliveries stay pending and are retried in the background
+        let mut delivery = WebhookDelivery::new(context.channel_id, data, payload, content_type);
+        let outcome = deliveries::attempt(&mut delivery, self, context.client).await;
+        if let Err(err) = context.store.insert_delivery(&delivery).await {
+            warn!("Error saving webhook delivery '{}': {err}", delivery._id);
+        }
+        outcome
+    }
+}
+
+impl Notifier for ChannelConfig {
+    async fn notify(&self, context: &NotifyContext<'_>, data: &WebhookData) -> Result<(), String> {
+        match self {
+            ChannelConfig::Slack(channel) => channel.notify(context, data).await,
+            ChannelConfig::Teams(channel) => channel.notify(context, data).await,
+            ChannelConfig::Discord(channel) => channel.notify(context, data).await,
+            ChannelConfig::PagerDuty(channel) => channel.notify(context, data).await,
+            ChannelConfig::Opsgenie(channel) => channel.notify(context, data).await,
+            ChannelConfig::Email(channel) => channel.notify(context, data).await,
+            ChannelConfig::Webhook(channel) => channel.notify(context, data).await,
+        }
+    }
+}
+
+/// Where a chat webhook URL points, the rest of it is the credential
+fn redacted_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(url) => format!("{}/{REDACTED}", url.origin().ascii_serialization()),
+        Err(_) => REDACTED.to_string(),
+    }
+}
+
+fn validate_url(url: &str) -> Result<(), String> {
+    Url::parse(url)
+        .map(|_| ())
+        .map_err(|err| format!("Invalid url '{url}': {err}"))
+}
+
+impl ChannelConfig {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        match self {
+            ChannelConfig::Slack(SlackChannel { webhook_url })
+            | ChannelConfig::Teams(TeamsChannel { webhook_url })
+            | ChannelConfig::Discord(DiscordChannel { webhook_url }) => validate_url(webhook_url),
+            ChannelConfig::PagerDuty(channel) if channel.routing_key.is_empty() => {
+                Err("Routing key is required.".to_string())
+            }
+            ChannelConfig::Opsgenie(channel) if channel.api_key.is_empty() => {
+                Err("API key is required.".to_string())
+            }
+            ChannelConfig::PagerDuty(_) | ChannelConfig::Opsgenie(_) => Ok(()),
+            ChannelConfig::Email(channel) => {
+                if channel.to.is_empty() {
+                    return Err("At least one recipient is required.".to_string());
+                }
+                parse_mailbox(&channel.from)?;
+                for to in &channel.to {
+                    parse_mailbox(to)?;
+                }
+                Ok(())
+            }
+            ChannelConfig::Webhook(channel) => {
+                validate_url(&channel.url)?;
+                match channel.headers {
+                    Some(ref headers) => validate_headers(headers),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// The channel as shown on reads, credentials are only ever shown when created
+    pub(crate) fn redacted(mut self) -> Self {
+        let redact = |secret: &mut String| *secret = REDACTED.to_string();
+        match self {
+            ChannelConfig::Slack(SlackChannel {
+                ref mut webhook_url,
+            })
+            | ChannelConfig::Teams(TeamsChannel {
+                ref mut webhook_url,
+            })
+            | ChannelConfig::Discord(DiscordChannel {
+                ref mut webhook_url,
+            }) => *webhook_url = redacted_url(webhook_url),
+            ChannelConfig::PagerDuty(ref mut channel) => redact(&mut channel.routing_key),
+            ChannelConfig::Opsgenie(ref mut channel) => redact(&mut channel.api_key),
+            ChannelConfig::Email(ref mut channel) => channel.password.iter_mut().for_each(redact),
+            ChannelConfig::Webhook(ref mut channel) => {
+                channel.secret.iter_mut().for_each(redact);
+                channel
+                    .headers
+                    .iter_mut()
+                    .flatten()
+                    .for_each(|(_, value)| redact(value));
+            }
+        }
+        self
+    }
+
+    /// Keep the credentials of the channel's current config where the update
+    /// leaves them out or sends back the redacted ones
+    pub(crate) fn keep_credentials(&mut self, current: &ChannelConfig) {
+        let keep = |secret: &mut String, current: &String| {
+            if secret.is_empty() || secret == REDACTED {
+                secret.clone_from(current);
+            }
+        };
+        let keep_optional = |secret: &mut Option<String>, current: &Option<String>| {
+            if secret.as_deref().is_none_or(|secret| secret == REDACTED) {
+                secret.clone_from(current);
+            }
+        };
+        let keep_url = |url: &mut String, current: &String| {
+            if url.is_empty() || *url == redacted_url(current) {
+                url.clone_from(current);
+            }
+        };
+        match (self, current) {
+            (ChannelConfig::Slack(channel), ChannelConfig::Slack(current)) => {
+                keep_url(&mut channel.webhook_url, &current.webhook_url)
+            }
+            (ChannelConfig::Teams(channel), ChannelConfig::Teams(current)) => {
+                keep_url(&mut channel.webhook_url, &current.webhook_url)
+            }
+            (ChannelConfig::Discord(channel), ChannelConfig::Discord(current)) => {
+                keep_url(&mut channel.webhook_url, &current.webhook_url)
+            }
+            (ChannelConfig::PagerDuty(channel), ChannelConfig::PagerDuty(current)) => {
+                keep(&mut channel.routing_key, &current.routing_key)
+            }
+            (ChannelConfig::Opsgenie(channel), ChannelConfig::Opsgenie(current)) => {
+                keep(&mut channel.api_key, &current.api_key)
+            }
+            (ChannelConfig::Email(channel), ChannelConfig::Email(current)) => {
+                keep_optional(&mut channel.password, &current.password)
+            }
+            (ChannelConfig::Webhook(channel), ChannelConfig::Webhook(current)) => {
+                keep_optional(&mut channel.secret, &current.secret);
+                if let (Some(headers), Some(current)) = (&mut channel.headers, &current.headers) {
+                    for (name, value) in headers.iter_mut() {
+                        if let Some(current) = current.get(name) {
+                            keep(value, current);
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Webhooks always sign their deliveries, generate a secret if none was given
+    pub(crate) fn ensure_secret(&mut self) {
+        if let ChannelConfig::Webhook(channel) = self {
+            channel
+                .secret
+                .get_or_insert_with(deliveries::generate_secret);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::REDACTED;
+    use crate::models::{ChannelConfig, SlackChannel, WebhookChannel};
+
+    fn webhook(secret: &str, authorization: &str) -> ChannelConfig {
+        ChannelConfig::Webhook(WebhookChannel {
+            url: "https://example.com/hook".to_string(),
+            headers: Some(HashMap::from([(
+                "Authorization".to_string(),
+                authorization.to_string(),
+            )])),
+            secret: Some(secret.to_string()),
+            template: None,
+        })
+    }
+
+    #[test]
+    fn redacted_credentials_are_kept_on_update() {
+        let current = webhook("secret", "Bearer token");
+        let ChannelConfig::Webhook(read) = current.clone().redacted() else {
+            unreachable!()
+        };
+        assert_eq!(read.secret.as_deref(), Some(REDACTED));
+        assert_eq!(read.headers.as_ref().unwrap()["Authorization"], REDACTED);
+
+        let mut update = ChannelConfig::Webhook(read);
+        update.keep_credentials(&current);
+        let ChannelConfig::Webhook(updated) = update else {
+            unreachable!()
+        };
+        assert_eq!(updated.secret.as_deref(), Some("secret"));
+        assert_eq!(updated.headers.unwrap()["Authorization"], "Bearer token");
+
+        let mut update = webhook(REDACTED, "Bearer other");
+        update.keep_credentials(&current);
+        let ChannelConfig::Webhook(updated) = update else {
+            unreachable!()
+        };
+        assert_eq!(updated.headers.unwrap()["Authorization"], "Bearer other");
+    }
+
+    #[test]
+    fn chat_webhook_urls_only_show_their_origin() {
+        let url = "https://hooks.slack.com/services/T000/B000/XXXX".to_string();
+        let current = ChannelConfig::Slack(SlackChannel {
+            webhook_url: url.clone(),
+        });
+        let mut read = current.clone().redacted();
+        let ChannelConfig::Slack(ref channel) = read else {
+            unreachable!()
+        };
+        assert_eq!(
+            channel.webhook_url,
+            format!("https://hooks.slack.com/{REDACTED}")
+        );
+
+        read.keep_credentials(&current);
+        let ChannelConfig::Slack(channel) = read else {
+            unreachable!()
+        };
+        assert_eq!(channel.webhook_url, url);
+    }
+}
@@ -0,0 +1,154 @@
+use bson::oid::ObjectId;
+use bson::{doc, Document};
+use chrono::{DateTime, Utc};
+
+use crate::models::SortOrder;
+use crate::timestamps;
+
+/// Position right after the last item of a page.
+///
+/// Items are ordered by a date field and then by id, so items sharing the
+/// same date are neither skipped nor repeated across pages. Items missing the
+/// date come first in ascending order and last in descending order, the way
+/// MongoDB sorts them. Dates keep milliseconds, the precision they are
+/// stored with, so the item a cursor points at compares equal to it.
+pub(crate) struct Cursor {
+    pub(crate) at: Option<DateTime<Utc>>,
+    pub(crate) id: ObjectId,
+}
+
+impl Cursor {
+    pub(crate) fn new(at: Option<DateTime<Utc>>, id: ObjectId) -> Self {
+        Self { at, id }
+    }
+
+    /// Opaque string handed to clients
+    pub(crate) fn encode(&self) -> String {
+        let at = self
+            .at
+            .map_or_else(String::new, |at| at.timestamp_millis().to_string());
+        hex::encode(format!("{at}.{}", self.id.to_hex()))
+    }
+
+    pub(crate) fn decode(cursor: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid cursor '{cursor}'");
+        let decoded = hex::decode(cursor).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+        let (at, id) = decoded.split_once('.').ok_or_else(invalid)?;
+        let at = match at {
+            "" => None,
+            at => Some(
+                at.parse()
+                    .ok()
+                    .and_then(DateTime::from_timestamp_millis)
+                    .ok_or_else(invalid)?,
+            ),
+        };
+        let id = ObjectId::parse_str(id).map_err(|_| invalid())?;
+        Ok(Self { at, id })
+    }
+
+    /// Filter matching the items that come after the cursor when sorted on `field`
+    pub(crate) fn filter(&self, field: &str, order: &SortOrder) -> Document {
+        let operator = match order {
+            SortOrder::Asc => "$gt",
+            SortOrder::Desc => "$lt",
+        };
+        let Some(at) = self.at else {
+            return match order {
+                SortOrder::Asc => doc! {
+                    "$or": [
+                        {field: null, "_id": {"$gt": self.id}},
+                        {field: {"$ne": null}},
+                    ]
+                },
+                SortOrder::Desc => doc! {field: null, "_id": {"$lt": self.id}},
+            };
+        };
+
+        let at = timestamps::to_bson(at);
+        let mut after = vec![
+            doc! {field: {operator: at.clone()}},
+            doc! {field: at, "_id": {operator: self.id}},
+        ];
+        if *order == SortOrder::Desc {
+            after.push(doc! {field: null});
+        }
+        doc! {"$or": after}
+    }
+}
+
+/// Sort document matching `Cursor::filter`
+pub(crate) fn sort(field: &str, order: &SortOrder) -> Document {
+    let direction = match order {
+        SortOrder::Asc => 1,
+        SortOrder::Desc => -1,
+    };
+    doc! {field: direction, "_id": direction}
+}
+
+/// Which part of a listing to read
+pub(crate) struct PageRequest {
+    pub(crate) order: SortOrder,
+    pub(crate) limit: usize,
+    /// Position of the last item of the previous page
+    pub(crate) cursor: Option<Cursor>,
+}
+
+/// Up to `limit` items, and the cursor of the next page if there is one
+pub(crate) struct Page<T> {
+    pub(crate) items: Vec<T>,
+    pub(crate) next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Page out of sorted items, fetching one more than `limit` tells whether there is a next page
+    pub(crate) fn new(mut items: Vec<T>, limit: usize, position: impl Fn(&T) -> Cursor) -> Self {
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(|last| position(last).encode())
+        } else {
+            None
+        };
+        Self { items, next_cursor }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::oid::ObjectId;
+    use bson::Bson;
+    use chrono::{DateTime, TimeZone, Utc};
+    use serde::{Deserialize, Serialize};
+
+    use super::Cursor;
+    use crate::models::SortOrder;
+
+    #[derive(Serialize, Deserialize)]
+    struct Item {
+        _id: ObjectId,
+        #[serde(with = "crate::timestamps")]
+        created_at: DateTime<Utc>,
+    }
+
+    #[test]
+    fn cursors_keep_the_stored_date() {
+        let item = Item {
+            _id: ObjectId::new(),
+            created_at: Utc.timestamp_nanos(1_700_000_000_123_456_789),
+        };
+        // As read back from the store
+        let stored = crate::store::to_document(&item).unwrap();
+        let item: Item = bson::from_document(stored.clone()).unwrap();
+        let cursor = Cursor::new(Some(item.created_at), item._id);
+        let cursor = Cursor::decode(&cursor.encode()).unwrap();
+
+        let filter = cursor.filter("created_at", &SortOrder::Desc);
+        let same_date = filter.get_array("$or").unwrap()[1].as_document().unwrap();
+        assert_eq!(same_date.get("created_at"), stored.get("created_at"));
+        assert!(matches!(
+            same_date.get("created_at"),
+            Some(Bson::DateTime(_))
+        ));
+    }
+}
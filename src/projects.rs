@@ -0,0 +1,67 @@
+use bson::oid::ObjectId;
+use chrono::Utc;
+use tracing::{info, warn};
+
+use crate::models::{Check, CheckKind, Project, ProjectQuota};
+use crate::store::{Storage, Store};
+
+/// Project everything created before projects existed belongs to, along with the admin key
+pub(crate) const DEFAULT_PROJECT_ID: ObjectId = ObjectId::from_bytes([0; 12]);
+
+pub(crate) fn default_project_id() -> ObjectId {
+    DEFAULT_PROJECT_ID
+}
+
+/// Create the default project and give it everything that has no project yet
+pub(crate) async fn bootstrap(store: &Storage) {
+    match store.project(DEFAULT_PROJECT_ID).await {
+        Ok(Some(_)) => (),
+        Ok(None) => {
+            let project = Project {
+                _id: DEFAULT_PROJECT_ID,
+                name: "Default".to_string(),
+                quota: ProjectQuota::default(),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            };
+            match store.insert_project(&project).await {
+                Ok(()) => info!("Created the default project"),
+                Err(err) => warn!("Error creating the default project: {err}"),
+            }
+        }
+        Err(err) => warn!("Error reading the default project: {err}"),
+    }
+
+    if let Err(err) = store.adopt_orphans(DEFAULT_PROJECT_ID).await {
+        warn!("Error moving data without a project to the default project: {err}");
+    }
+}
+
+impl ProjectQuota {
+    /// Refuse a check that would run more often than the project allows
+    pub(crate) fn validate_check(&self, check: &Check) -> Result<(), String> {
+        let Some(min_interval_secs) = self.min_interval_secs else {
+            return Ok(());
+        };
+        // Heartbeat checks wait for pings, they do not run against anything
+        if check.kind == CheckKind::Heartbeat {
+            return Ok(());
+        }
+        match check.frequency.shortest_period() {
+            Some(period) if period.num_seconds() < min_interval_secs as i64 => Err(format!(
+                "The project's checks may run at most every {min_interval_secs} seconds."
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Refuse one more check when the project already has as many as it may
+    pub(crate) fn validate_count(&self, checks: u64) -> Result<(), String> {
+        match self.max_checks {
+            Some(max_checks) if checks >= max_checks as u64 => Err(format!(
+                "The project already has the {max_checks} checks it may have."
+            )),
+            _ => Ok(()),
+        }
+    }
+}
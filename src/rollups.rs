@@ -0,0 +1,181 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use tracing::{debug, info, warn};
+
+use crate::config::Config;
+use crate::models::{LatencySummary, Resolution, Rollup, Status};
+use crate::stats::{percentile, Sample};
+use crate::store::{self, Storage, Store};
+
+const COMPACTION_INTERVAL: Duration = Duration::from_secs(60);
+/// Leave recent buckets alone for a while, executions are saved once they finish
+const GRACE: chrono::Duration = chrono::Duration::minutes(1);
+/// Most raw history of a check read at once while catching up
+const CHUNK: chrono::Duration = chrono::Duration::days(1);
+
+struct Context {
+    store: Storage,
+    history_retention_days: u64,
+    retention: HashMap<Resolution, u64>,
+}
+
+/// Keep aggregating raw history into minute, hour and day rollups
+pub(crate) async fn start(store: Storage, config: Config) {
+    let context = Context {
+        store,
+        history_retention_days: config.history_retention_days,
+        retention: HashMap::from([
+            (Resolution::Minute, config.minute_rollup_retention_days),
+            (Resolution::Hour, config.hour_rollup_retention_days),
+            (Resolution::Day, 0),
+        ]),
+    };
+    info!("Starting history compaction task");
+
+    // Start of the first bucket not compacted yet, per resolution
+    let mut pending = HashMap::new();
+    loop {
+        for resolution in [Resolution::Minute, Resolution::Hour, Resolution::Day] {
+            let start = match pending.get(&resolution) {
+                Some(start) => *start,
+                None => match first_pending_bucket(resolution, &context).await {
+                    Some(start) => start,
+                    None => continue,
+                },
+            };
+            pending.insert(resolution, compact(resolution, start, &context).await);
+        }
+        remove_expired(&context).await;
+        tokio::time::sleep(COMPACTION_INTERVAL).await;
+    }
+}
+
+/// Bucket after the latest rollup, or the one holding the oldest raw entry
+async fn first_pending_bucket(resolution: Resolution, context: &Context) -> Option<DateTime<Utc>> {
+    match context.store.latest_rollup(resolution).await {
+        Ok(Some(rollup)) => return Some(rollup.bucket + resolution.size()?),
+        Ok(None) => {}
+        Err(err) => {
+            warn!("Error fetching the latest {resolution:?} rollup: {err}");
+            return None;
+        }
+    }
+
+    match context.store.oldest_history().await {
+        Ok(Some(created_at)) => Some(resolution.floor(created_at)),
+        // Nothing recorded yet, start from now
+        Ok(None) => Some(resolution.floor(Utc::now())),
+        Err(err) => {
+            warn!("Error fetching the oldest history entry: {err}");
+            None
+        }
+    }
+}
+
+/// Build every complete bucket from `start` on, returns where the next run should start
+async fn compact(
+    resolution: Resolution,
+    mut start: DateTime<Utc>,
+    context: &Context,
+) -> DateTime<Utc> {
+    let end = resolution.floor(Utc::now() - GRACE);
+    while start < end {
+        let chunk_end = (start + CHUNK).min(end);
+        match compact_range(resolution, start, chunk_end, context).await {
+            Ok(rollups) => debug!("Saved {rollups} {resolution:?} rollups from {start}"),
+            Err(err) => {
+                warn!("Error compacting {resolution:?} rollups from {start}: {err}");
+                break;
+            }
+        }
+        start = chunk_end;
+    }
+    start
+}
+
+/// Build the buckets between `start` and `end` one check at a time, so only the
+/// raw history of a single check is held in memory
+async fn compact_range(
+    resolution: Resolution,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    context: &Context,
+) -> store::Result<usize> {
+    let retention = context.retention.get(&resolution).copied().unwrap_or(0);
+    let mut count = 0;
+    for check in context.store.checks().await? {
+        let samples = context.store.samples(check._id, start, end).await?;
+
+        let mut buckets: BTreeMap<DateTime<Utc>, Vec<Sample>> = BTreeMap::new();
+        for sample in samples {
+            buckets
+                .entry(resolution.floor(sample.created_at))
+                .or_default()
+                .push(sample);
+        }
+
+        count += buckets.len();
+        for (bucket, samples) in buckets {
+            let rollup = aggregate(check._id, resolution, bucket, &samples, retention);
+            // Buckets are rebuilt as a whole, running twice over the same range is harmless
+            context.store.save_rollup(&rollup).await?;
+        }
+    }
+    Ok(count)
+}
+
+/// Drop raw history past its retention and expired rollups, for stores without TTL indexes
+async fn remove_expired(context: &Context) {
+    let history_before = (context.history_retention_days > 0)
+        .then(|| Utc::now().checked_sub_signed(days(context.history_retention_days)?))
+        .flatten();
+    if let Err(err) = context.store.remove_expired(history_before).await {
+        warn!("Error removing expired history: {err}");
+    }
+}
+
+fn aggregate(
+    check_id: ObjectId,
+    resolution: Resolution,
+    bucket: DateTime<Utc>,
+    samples: &[Sample],
+    retention_days: u64,
+) -> Rollup {
+    let mut latencies: Vec<u64> = samples
+        .iter()
+        .filter_map(|sample| sample.latency.as_ref().map(|latency| latency.total_ms))
+        .collect();
+    latencies.sort_unstable();
+    let latency = (!latencies.is_empty()).then(|| LatencySummary {
+        min: latencies[0],
+        avg: latencies.iter().sum::<u64>() / latencies.len() as u64,
+        max: latencies[latencies.len() - 1],
+        p50: percentile(&latencies, 50.0),
+        p90: percentile(&latencies, 90.0),
+        p99: percentile(&latencies, 99.0),
+    });
+
+    Rollup {
+        _id: ObjectId::new(),
+        check_id,
+        resolution,
+        bucket,
+        count: samples.len() as u64,
+        failures: samples
+            .iter()
+            .filter(|sample| sample.status == Status::Error)
+            .count() as u64,
+        latency,
+        expires_at: (retention_days > 0)
+            .then(|| bucket.checked_add_signed(days(retention_days)?))
+            .flatten(),
+    }
+}
+
+/// None when the retention is too long to count in, as good as forever
+fn days(days: u64) -> Option<chrono::Duration> {
+    chrono::Duration::try_days(days.try_into().ok()?)
+}
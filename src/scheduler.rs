@@ -0,0 +1,170 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tracing::warn;
+
+use crate::heartbeats;
+use crate::models::{Check, Frequency};
+
+pub(crate) enum Command {
+    Schedule(Box<Check>),
+    Unschedule(ObjectId),
+}
+
+/// Handle used by the API to tell the monitor that a check changed
+#[derive(Clone)]
+pub(crate) struct Scheduler {
+    sender: UnboundedSender<Command>,
+}
+
+impl Scheduler {
+    pub(crate) fn new() -> (Self, UnboundedReceiver<Command>) {
+        let (sender, receiver) = unbounded_channel();
+        (Self { sender }, receiver)
+    }
+
+    pub(crate) fn schedule(&self, check: Check) {
+        if self
+            .sender
+            .send(Command::Schedule(Box::new(check)))
+            .is_err()
+        {
+            warn!("Monitor is not running, check will not be scheduled");
+        }
+    }
+
+    pub(crate) fn unschedule(&self, check_id: ObjectId) {
+        if self.sender.send(Command::Unschedule(check_id)).is_err() {
+            warn!("Monitor is not running, check '{check_id}' will not be unscheduled");
+        }
+    }
+}
+
+/// When a check should run next, based on the last time it ran
+pub(crate) fn next_run_at(check: &Check) -> Option<DateTime<Utc>> {
+    if let Some(ref heartbeat) = check.heartbeat {
+        return heartbeats::next_evaluation(check, heartbeat);
+    }
+    match (&check.frequency, check.last_run_at) {
+        // Cron checks wait for their first slot, everything else runs right away
+        (Frequency::Cron(_), None) => check.frequency.next_after(Utc::now()),
+        (_, None) => Some(Utc::now()),
+        (_, Some(last_run_at)) => check.frequency.next_after(last_run_at),
+    }
+}
+
+/// Carry over what the monitor recorded about the last run if `other` ran more recently
+fn keep_latest_run(check: &mut Check, other: &Check) {
+    if other.last_run_at > check.last_run_at {
+        check.last_run_at = other.last_run_at;
+        check.current_state = other.current_state.clone();
+        check.consecutive_failures = other.consecutive_failures;
+        check.consecutive_successes = other.consecutive_successes;
+    }
+}
+
+/// What happened to a check through the API while it was running
+enum Running {
+    Unchanged,
+    Updated(Box<Check>),
+    Removed,
+}
+
+/// Checks ordered by the moment they are due.
+///
+/// Rescheduling a check does not touch the heap, the stale entry is simply
+/// discarded when it reaches the top and no longer matches `entries`.
+/// Checks handed out by `pop_due` stay out of the queue until `finish` is
+/// called, so a check never runs twice at the same time.
+#[derive(Default)]
+pub(crate) struct Queue {
+    heap: BinaryHeap<Reverse<(DateTime<Utc>, ObjectId)>>,
+    entries: HashMap<ObjectId, (DateTime<Utc>, Check)>,
+    running: HashMap<ObjectId, Running>,
+}
+
+impl Queue {
+    pub(crate) fn schedule(&mut self, mut check: Check) {
+        if let Some(running) = self.running.get_mut(&check._id) {
+            *running = Running::Updated(Box::new(check));
+            return;
+        }
+
+        // The API may hand over a copy loaded before the latest run was saved
+        if let Some((_, current)) = self.entries.get(&check._id) {
+            keep_latest_run(&mut check, current);
+        }
+        let Some(run_at) = next_run_at(&check) else {
+            warn!(
+                "Check '{}' has no upcoming run, it will not be scheduled",
+                check._id
+            );
+            self.entries.remove(&check._id);
+            return;
+        };
+        self.heap.push(Reverse((run_at, check._id)));
+        self.entries.insert(check._id, (run_at, check));
+    }
+
+    pub(crate) fn unschedule(&mut self, check_id: &ObjectId) {
+        self.entries.remove(check_id);
+        if let Some(running) = self.running.get_mut(check_id) {
+            *running = Running::Removed;
+        }
+    }
+
+    /// Put a check handed out by `pop_due` back in the queue once it ran
+    pub(crate) fn finish(&mut self, check: Check) {
+        match self.running.remove(&check._id) {
+            Some(Running::Removed) => {}
+            Some(Running::Updated(mut updated)) => {
+                keep_latest_run(&mut updated, &check);
+                self.schedule(*updated);
+            }
+            Some(Running::Unchanged) | None => self.schedule(check),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len() + self.running.len()
+    }
+
+    /// Moment the earliest check is due, if any check is scheduled
+    pub(crate) fn next_due(&mut self) -> Option<DateTime<Utc>> {
+        while let Some(Reverse((run_at, check_id))) = self.heap.peek() {
+            if self.is_current(run_at, check_id) {
+                return Some(*run_at);
+            }
+            self.heap.pop();
+        }
+        None
+    }
+
+    /// Remove and return every check due at `now`
+    pub(crate) fn pop_due(&mut self, now: DateTime<Utc>) -> Vec<Check> {
+        let mut due = vec![];
+        while let Some(Reverse((run_at, check_id))) = self.heap.peek() {
+            if *run_at > now {
+                break;
+            }
+            let (run_at, check_id) = (*run_at, *check_id);
+            self.heap.pop();
+            if self.is_current(&run_at, &check_id) {
+                if let Some((_, check)) = self.entries.remove(&check_id) {
+                    self.running.insert(check_id, Running::Unchanged);
+                    due.push(check);
+                }
+            }
+        }
+        due
+    }
+
+    fn is_current(&self, run_at: &DateTime<Utc>, check_id: &ObjectId) -> bool {
+        self.entries
+            .get(check_id)
+            .is_some_and(|(scheduled_at, _)| scheduled_at == run_at)
+    }
+}
@@ -0,0 +1,281 @@
+use bson::oid::ObjectId;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+
+use crate::maintenance;
+use crate::models::{
+    Check, CheckStats, Incident, LatencyPercentiles, LatencySummary, Resolution, Rollup, SlaReport,
+    SortOrder, StatsWindow, Status,
+};
+use crate::pagination::{Cursor, PageRequest};
+use crate::store::{self, HistoryFilter, IncidentFilter, Storage, Store};
+
+/// Rollups read per query when falling back to them
+const ROLLUP_PAGE: usize = 1000;
+
+/// The parts of a history entry stats are computed from
+#[derive(Deserialize)]
+pub(crate) struct Sample {
+    #[serde(with = "crate::timestamps")]
+    pub(crate) created_at: DateTime<Utc>,
+    pub(crate) status: Status,
+    pub(crate) latency: Option<SampleLatency>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SampleLatency {
+    pub(crate) total_ms: u64,
+}
+
+impl StatsWindow {
+    /// How far back the window goes from now, none for a custom window
+    pub(crate) fn duration(&self) -> Option<Duration> {
+        match self {
+            StatsWindow::Day => Some(Duration::hours(24)),
+            StatsWindow::Week => Some(Duration::days(7)),
+            StatsWindow::Month => Some(Duration::days(30)),
+            StatsWindow::Custom => None,
+        }
+    }
+}
+
+/// Seconds the two periods have in common
+fn overlap_secs(
+    (a_start, a_end): (DateTime<Utc>, DateTime<Utc>),
+    (b_start, b_end): (DateTime<Utc>, DateTime<Utc>),
+) -> i64 {
+    (a_end.min(b_end) - a_start.max(b_start))
+        .num_seconds()
+        .max(0)
+}
+
+/// Aggregate the samples, rollups and incidents of `check` between `from` and `to`.
+///
+/// Downtime is the time incidents were open, so a check is only counted as
+/// down once it crossed its failure threshold, the same way it alerts.
+/// Maintenance periods, merged and in order, count neither as up nor as down.
+/// Rollups stand for the part of the window whose raw history expired, their
+/// percentiles weighted by their execution count approximate the latency there.
+pub(crate) fn compute(
+    check: &Check,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    incidents: &[Incident],
+    samples: &[Sample],
+    rollups: &[Rollup],
+    maintenance: &[(DateTime<Utc>, DateTime<Utc>)],
+) -> CheckStats {
+    // Time before the check existed is neither up nor down
+    let start = from.max(check.created_at).min(to);
+    let in_maintenance = |period| {
+        maintenance
+            .iter()
+            .map(|window| overlap_secs(period, *window))
+            .sum::<i64>()
+    };
+    let maintenance_secs = in_maintenance((start, to));
+    let observed_secs = (to - start).num_seconds() - maintenance_secs;
+
+    let downtime_secs: i64 = incidents
+        .iter()
+        .map(|incident| {
+            let started_at = incident.started_at.max(start);
+            let ended_at = incident.resolved_at.unwrap_or_else(Utc::now).min(to);
+            let secs = (ended_at - started_at).num_seconds().max(0);
+            (secs - in_maintenance((started_at, ended_at))).max(0)
+        })
+        .sum();
+    let uptime_secs = (observed_secs - downtime_secs).max(0);
+    let uptime_percentage = if observed_secs > 0 {
+        uptime_secs as f64 * 100.0 / observed_secs as f64
+    } else {
+        100.0
+    };
+
+    let recoveries: Vec<i64> = incidents
+        .iter()
+        .filter(|incident| {
+            incident
+                .resolved_at
+                .is_some_and(|at| at >= from && at <= to)
+        })
+        .filter_map(|incident| incident.duration_secs)
+        .collect();
+    let mttr_secs = mean(&recoveries);
+    let failures = incidents
+        .iter()
+        .filter(|incident| incident.started_at >= from)
+        .count() as i64;
+    let mtbf_secs = (failures > 0).then(|| uptime_secs / failures);
+
+    let raw_latencies: Vec<u64> = samples
+        .iter()
+        .filter_map(|sample| sample.latency.as_ref().map(|latency| latency.total_ms))
+        .collect();
+    let latency_at = |percentile: f64, of_rollup: fn(&LatencySummary) -> u64| {
+        let mut weighted: Vec<(u64, u64)> = raw_latencies
+            .iter()
+            .map(|latency| (*latency, 1))
+            .chain(rollups.iter().filter_map(|rollup| {
+                let latency = rollup.latency.as_ref()?;
+                Some((of_rollup(latency), rollup.count))
+            }))
+            .collect();
+        weighted.sort_unstable();
+        weighted_percentile(&weighted, percentile)
+    };
+    let has_latency =
+        !raw_latencies.is_empty() || rollups.iter().any(|rollup| rollup.latency.is_some());
+    let latency = has_latency.then(|| LatencyPercentiles {
+        p50: latency_at(50.0, |latency| latency.p50),
+        p90: latency_at(90.0, |latency| latency.p90),
+        p99: latency_at(99.0, |latency| latency.p99),
+    });
+
+    let sla = check.sla_target.map(|target| {
+        let allowed_downtime_secs = (observed_secs as f64 * (100.0 - target) / 100.0) as i64;
+        SlaReport {
+            target,
+            met: uptime_percentage >= target,
+            allowed_downtime_secs,
+            remaining_downtime_secs: allowed_downtime_secs - downtime_secs,
+        }
+    });
+
+    CheckStats {
+        check_id: check._id,
+        from,
+        to,
+        uptime_percentage,
+        downtime_secs,
+        maintenance_secs,
+        incidents: incidents.len() as u64,
+        mttr_secs,
+        mtbf_secs,
+        samples: samples.len() as u64 + rollups.iter().map(|rollup| rollup.count).sum::<u64>(),
+        failed_samples: samples
+            .iter()
+            .filter(|sample| sample.status == Status::Error)
+            .count() as u64
+            + rollups.iter().map(|rollup| rollup.failures).sum::<u64>(),
+        latency,
+        sla,
+    }
+}
+
+/// Read what the stats of `check` between `from` and `to` are computed from, and compute them
+pub(crate) async fn read(
+    store: &Storage,
+    check: &Check,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> store::Result<CheckStats> {
+    let incidents = store
+        .find_incidents(&IncidentFilter {
+            project_id: Some(check.project_id),
+            check_id: Some(check._id),
+            during: Some((from, to)),
+            ..Default::default()
+        })
+        .await?;
+    let rollups = expired_part(store, check, from, to).await?;
+    // Raw history picks up where the rollups end
+    let raw_from = rollups
+        .iter()
+        .map(|rollup| rollup.bucket + rollup.resolution.size().unwrap_or_default())
+        .max()
+        .map_or(from, |end| end.clamp(from, to));
+    let mut samples = store.samples(check._id, raw_from, to).await?;
+    samples.retain(|sample| sample.status != Status::Maintenance);
+    let windows = store.maintenance_windows_for(check).await?;
+    let periods = maintenance::periods(&windows, from, to);
+    Ok(compute(
+        check, from, to, &incidents, &samples, &rollups, &periods,
+    ))
+}
+
+/// Rollups covering the part of the window older than the raw history kept:
+/// hour rollups, and day rollups before them once those expired too
+async fn expired_part(
+    store: &Storage,
+    check: &Check,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> store::Result<Vec<Rollup>> {
+    let Some(oldest) = store
+        .oldest_history()
+        .await?
+        .filter(|oldest| *oldest > from)
+    else {
+        return Ok(vec![]);
+    };
+    let hours = all_rollups(store, check._id, Resolution::Hour, from, oldest.min(to)).await?;
+    let hours_from = hours.first().map_or(oldest, |first| first.bucket);
+    let mut rollups = vec![];
+    if hours_from > from {
+        // Only whole days, the hours cover the rest
+        let days = all_rollups(store, check._id, Resolution::Day, from, hours_from).await?;
+        rollups.extend(
+            days.into_iter()
+                .filter(|day| day.bucket + Duration::days(1) <= hours_from),
+        );
+    }
+    rollups.extend(hours);
+    Ok(rollups)
+}
+
+/// Every rollup of `resolution` overlapping the period, oldest first
+async fn all_rollups(
+    store: &Storage,
+    check_id: ObjectId,
+    resolution: Resolution,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> store::Result<Vec<Rollup>> {
+    let filter = HistoryFilter {
+        check_id,
+        from: Some(resolution.floor(from)),
+        to: Some(to),
+        status: None,
+    };
+    let mut rollups = vec![];
+    let mut cursor = None;
+    loop {
+        let request = PageRequest {
+            order: SortOrder::Asc,
+            limit: ROLLUP_PAGE,
+            cursor,
+        };
+        let page = store.find_rollups(&filter, resolution, &request).await?;
+        rollups.extend(page.items);
+        // Cursors made by the store itself always decode
+        let Some(next) = page.next_cursor.and_then(|next| Cursor::decode(&next).ok()) else {
+            return Ok(rollups);
+        };
+        cursor = Some(next);
+    }
+}
+
+fn mean(values: &[i64]) -> Option<i64> {
+    (!values.is_empty()).then(|| values.iter().sum::<i64>() / values.len() as i64)
+}
+
+/// Nearest-rank percentile of already sorted values, each counted `weight` times
+fn weighted_percentile(sorted: &[(u64, u64)], percentile: f64) -> u64 {
+    let total: u64 = sorted.iter().map(|(_, weight)| weight).sum();
+    let rank = ((percentile / 100.0 * total as f64).ceil() as u64).max(1);
+    let mut seen = 0;
+    for (value, weight) in sorted {
+        seen += weight;
+        if seen >= rank {
+            return *value;
+        }
+    }
+    sorted.last().map_or(0, |(value, _)| *value)
+}
+
+/// Nearest-rank percentile of already sorted values
+pub(crate) fn percentile(sorted: &[u64], percentile: f64) -> u64 {
+    let rank = (percentile / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
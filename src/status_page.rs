@@ -0,0 +1,590 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::path::Path as FilePath;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use poem::http::header::{CACHE_CONTROL, CONTENT_TYPE};
+use poem::http::StatusCode;
+use poem::web::{Data, Json, Path};
+use poem::{handler, IntoResponse, Response};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::maintenance;
+use crate::models::{
+    Check, CheckState, Incident, IncidentStatus, IncidentUpdate, Resolution, SortOrder, Status,
+    StatusPage,
+};
+use crate::pagination::PageRequest;
+use crate::store::{self, HistoryFilter, IncidentFilter, Storage, Store, StoreError};
+
+/// Logos and the stylesheet of status pages
+const ASSETS_DIR: &str = "resources/status";
+/// Days covered by the uptime bars, today included
+const DAYS: i64 = 90;
+/// Days resolved incidents stay listed
+const PAST_INCIDENT_DAYS: i64 = 14;
+/// Pages are rebuilt from the store at most this often, and cached by clients as long
+const PAGE_MAX_AGE_SECS: u32 = 30;
+const ASSET_MAX_AGE_SECS: u32 = 3600;
+
+/// State of a component, the worst of its checks. Later states win
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum ComponentState {
+    /// None of the checks ran yet
+    Unknown,
+    Operational,
+    Maintenance,
+    Degraded,
+    Outage,
+}
+
+impl ComponentState {
+    fn label(&self) -> &'static str {
+        match self {
+            ComponentState::Unknown => "No data",
+            ComponentState::Operational => "Operational",
+            ComponentState::Maintenance => "Under maintenance",
+            ComponentState::Degraded => "Degraded performance",
+            ComponentState::Outage => "Outage",
+        }
+    }
+
+    fn summary(&self) -> &'static str {
+        match self {
+            ComponentState::Unknown => "No data yet",
+            ComponentState::Operational => "All systems operational",
+            ComponentState::Maintenance => "Maintenance in progress",
+            ComponentState::Degraded => "Some systems are degraded",
+            ComponentState::Outage => "Some systems are down",
+        }
+    }
+
+    fn class(&self) -> &'static str {
+        match self {
+            ComponentState::Unknown => "unknown",
+            ComponentState::Operational => "operational",
+            ComponentState::Maintenance => "maintenance",
+            ComponentState::Degraded => "degraded",
+            ComponentState::Outage => "outage",
+        }
+    }
+}
+
+/// What a status page shows, as served by its JSON variant
+#[derive(Serialize)]
+pub(crate) struct StatusPageView {
+    title: String,
+    description: Option<String>,
+    state: ComponentState,
+    components: Vec<ComponentView>,
+    /// Open incidents, then the ones resolved lately, most recent first
+    incidents: Vec<IncidentView>,
+    generated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct ComponentView {
+    name: String,
+    description: Option<String>,
+    state: ComponentState,
+    /// Over the days shown, missing before any execution was recorded
+    uptime_percentage: Option<f64>,
+    /// Oldest first, ending today
+    days: Vec<UptimeDay>,
+}
+
+#[derive(Serialize)]
+struct UptimeDay {
+    date: NaiveDate,
+    /// Share of executions that did not fail, missing on days without any
+    uptime_percentage: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct IncidentView {
+    id: String,
+    /// Components of the failing check
+    components: Vec<String>,
+    status: IncidentStatus,
+    started_at: DateTime<Utc>,
+    resolved_at: Option<DateTime<Utc>>,
+    /// Most recent first
+    updates: Vec<IncidentUpdate>,
+}
+
+/// Executions and failures, per day
+type Tally = BTreeMap<NaiveDate, (u64, u64)>;
+
+impl StatusPage {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        let slug = Regex::new("^[a-z0-9][a-z0-9-]*$").unwrap();
+        if !slug.is_match(&self.slug) {
+            return Err(
+                "Slugs are made of lowercase letters, digits and dashes, and do not start with a dash."
+                    .to_string(),
+            );
+        }
+        if self.title.trim().is_empty() {
+            return Err("Status pages need a title.".to_string());
+        }
+        for component in &self.components {
+            if component.name.trim().is_empty() {
+                return Err("Components need a name.".to_string());
+            }
+            if component.checks.is_empty() {
+                return Err(format!("Component '{}' has no checks.", component.name));
+            }
+        }
+
+        if let Some(ref color) = self.branding.accent_color {
+            let hex = Regex::new("^#([0-9a-fA-F]{3}|[0-9a-fA-F]{6})$").unwrap();
+            if !hex.is_match(color) {
+                return Err(format!(
+                    "Invalid accent color '{color}', expected a hex color such as #1f6feb."
+                ));
+            }
+        }
+        if let Some(ref logo) = self.branding.logo {
+            if !is_asset_name(logo) || !FilePath::new(ASSETS_DIR).join(logo).is_file() {
+                return Err(format!("Logo '{logo}' not found in {ASSETS_DIR}."));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A file directly in the assets folder, nothing above or below it
+fn is_asset_name(name: &str) -> bool {
+    !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
+fn check_state(check: &Check, in_maintenance: bool) -> ComponentState {
+    if in_maintenance {
+        return ComponentState::Maintenance;
+    }
+    match check.current_state {
+        CheckState::Up => ComponentState::Operational,
+        CheckState::Degraded => ComponentState::Degraded,
+        CheckState::Down => ComponentState::Outage,
+        CheckState::Unknown => ComponentState::Unknown,
+    }
+}
+
+/// Executions of the check per day since `from`: day rollups, then raw history for today
+async fn tally(
+    store: &Storage,
+    check: &Check,
+    from: DateTime<Utc>,
+    today: DateTime<Utc>,
+) -> store::Result<Tally> {
+    let filter = HistoryFilter {
+        check_id: check._id,
+        from: Some(from),
+        to: Some(today),
+        status: None,
+    };
+    let request = PageRequest {
+        order: SortOrder::Asc,
+        limit: DAYS as usize,
+        cursor: None,
+    };
+    let rollups = store
+        .find_rollups(&filter, Resolution::Day, &request)
+        .await?;
+
+    let mut tally = Tally::new();
+    for rollup in rollups.items {
+        let day = tally.entry(rollup.bucket.date_naive()).or_default();
+        day.0 += rollup.count;
+        day.1 += rollup.failures;
+    }
+    // Today is only rolled up once it is over
+    let samples = store.samples(check._id, today, Utc::now()).await?;
+    let day = tally.entry(today.date_naive()).or_default();
+    for sample in samples {
+        if sample.status == Status::Maintenance {
+            continue;
+        }
+        day.0 += 1;
+        if sample.status == Status::Error {
+            day.1 += 1;
+        }
+    }
+    Ok(tally)
+}
+
+fn uptime(count: u64, failures: u64) -> Option<f64> {
+    (count > 0).then(|| (count - failures) as f64 * 100.0 / count as f64)
+}
+
+/// Gather the state, uptime and incidents of the page's components
+pub(crate) async fn build(store: &Storage, page: &StatusPage) -> store::Result<StatusPageView> {
+    let now = Utc::now();
+    let today = Resolution::Day.floor(now);
+    let from = today - Duration::days(DAYS - 1);
+
+    let mut components = vec![];
+    for component in &page.components {
+        let mut state = None;
+        let mut days = Tally::new();
+        for check_id in &component.checks {
+            // Checks of other projects are not shown, even if they were put on the page
+            let Some(check) = store
+                .check(*check_id)
+                .await?
+                .filter(|check| check.project_id == page.project_id)
+            else {
+                continue;
+            };
+            let in_maintenance = maintenance::open_window(store, &check).await?.is_some();
+            state = state.max(Some(check_state(&check, in_maintenance)));
+            for (date, (count, failures)) in tally(store, &check, from, today).await? {
+                let day = days.entry(date).or_default();
+                day.0 += count;
+                day.1 += failures;
+            }
+        }
+
+        let (count, failures) = days
+            .values()
+            .fold((0, 0), |total, day| (total.0 + day.0, total.1 + day.1));
+        components.push(ComponentView {
+            name: component.name.clone(),
+            description: component.description.clone(),
+            state: state.unwrap_or(ComponentState::Unknown),
+            uptime_percentage: uptime(count, failures),
+            days: (0..DAYS)
+                .map(|offset| {
+                    let date = (from + Duration::days(offset)).date_naive();
+                    let (count, failures) = days.get(&date).copied().unwrap_or_default();
+                    UptimeDay {
+                        date,
+                        uptime_percentage: uptime(count, failures),
+                    }
+                })
+                .collect(),
+        });
+    }
+
+    let filter = IncidentFilter {
+        project_id: Some(page.project_id),
+        during: Some((now - Duration::days(PAST_INCIDENT_DAYS), now)),
+        ..Default::default()
+    };
+    let mut incidents: Vec<Incident> = store.find_incidents(&filter).await?;
+    incidents.sort_by(|a, b| {
+        let resolved = |incident: &Incident| incident.status == IncidentStatus::Resolved;
+        resolved(a)
+            .cmp(&resolved(b))
+            .then(b.started_at.cmp(&a.started_at))
+    });
+    let incidents = incidents
+        .into_iter()
+        .filter_map(|incident| {
+            let names: Vec<String> = page
+                .components
+                .iter()
+                .filter(|component| component.checks.contains(&incident.check_id))
+                .map(|component| component.name.clone())
+                .collect();
+            (!names.is_empty()).then(|| IncidentView {
+                id: incident._id.to_hex(),
+                components: names,
+                status: incident.status,
+                started_at: incident.started_at,
+                resolved_at: incident.resolved_at,
+                updates: incident.updates.into_iter().rev().collect(),
+            })
+        })
+        .collect();
+
+    Ok(StatusPageView {
+        title: page.title.clone(),
+        description: page.description.clone(),
+        state: components
+            .iter()
+            .map(|component| component.state)
+            .max()
+            .unwrap_or(ComponentState::Unknown),
+        components,
+        incidents,
+        generated_at: now,
+    })
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn format_time(at: DateTime<Utc>) -> String {
+    at.format("%Y-%m-%d %H:%M UTC").to_string()
+}
+
+fn day_class(uptime_percentage: Option<f64>) -> &'static str {
+    match uptime_percentage {
+        None => "unknown",
+        Some(uptime) if uptime >= 99.9 => "operational",
+        Some(uptime) if uptime >= 99.0 => "degraded",
+        Some(_) => "outage",
+    }
+}
+
+fn render_component(html: &mut String, component: &ComponentView) {
+    let _ = write!(
+        html,
+        "<article class=\"component\"><div class=\"component-header\"><h3>{}</h3>\
+         <span class=\"state state-{}\">{}</span></div>",
+        escape(&component.name),
+        component.state.class(),
+        component.state.label()
+    );
+    if let Some(ref description) = component.description {
+        let _ = write!(html, "<p class=\"description\">{}</p>", escape(description));
+    }
+    html.push_str("<div class=\"bars\">");
+    for day in &component.days {
+        let uptime = day
+            .uptime_percentage
+            .map_or("No data".to_string(), |uptime| format!("{uptime:.2}%"));
+        let _ = write!(
+            html,
+            "<span class=\"bar state-{}\" title=\"{}: {uptime}\"></span>",
+            day_class(day.uptime_percentage),
+            day.date
+        );
+    }
+    let uptime = component
+        .uptime_percentage
+        .map_or("No data".to_string(), |uptime| {
+            format!("{uptime:.2}% uptime")
+        });
+    let _ = write!(
+        html,
+        "</div><div class=\"bars-legend\"><span>{DAYS} days ago</span><span>{uptime}</span>\
+         <span>Today</span></div></article>"
+    );
+}
+
+fn render_incident(html: &mut String, incident: &IncidentView) {
+    let _ = write!(
+        html,
+        "<article class=\"incident\"><h3>{}</h3><p class=\"period\">Started {}",
+        escape(&incident.components.join(", ")),
+        format_time(incident.started_at)
+    );
+    if let Some(resolved_at) = incident.resolved_at {
+        let _ = write!(html, ", resolved {}", format_time(resolved_at));
+    }
+    html.push_str("</p>");
+    for update in &incident.updates {
+        let _ = write!(
+            html,
+            "<div class=\"update\"><strong>{:?}</strong> {}<time>{}</time></div>",
+            update.status,
+            escape(&update.message),
+            format_time(update.created_at)
+        );
+    }
+    html.push_str("</article>");
+}
+
+fn render(page: &StatusPage, view: &StatusPageView) -> String {
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html><html lang=\"en\"><head><meta charset=\"utf-8\">\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\
+         <title>{}</title><link rel=\"icon\" href=\"/favicon.ico\">\
+         <link rel=\"stylesheet\" href=\"/status-assets/style.css\">",
+        escape(&view.title)
+    );
+    // Validated as a hex color when the page was saved
+    if let Some(ref color) = page.branding.accent_color {
+        let _ = write!(html, "<style>:root {{ --accent: {color}; }}</style>");
+    }
+    html.push_str("</head><body><header>");
+    if let Some(ref logo) = page.branding.logo {
+        let _ = write!(
+            html,
+            "<img class=\"logo\" src=\"/status-assets/{}\" alt=\"\">",
+            escape(logo)
+        );
+    }
+    let _ = write!(html, "<h1>{}</h1>", escape(&view.title));
+    if let Some(ref description) = view.description {
+        let _ = write!(html, "<p>{}</p>", escape(description));
+    }
+    let _ = write!(
+        html,
+        "</header><main><section class=\"summary state-{}\">{}</section>",
+        view.state.class(),
+        view.state.summary()
+    );
+
+    let (active, past): (Vec<_>, Vec<_>) = view
+        .incidents
+        .iter()
+        .partition(|incident| incident.status == IncidentStatus::Open);
+    if !active.is_empty() {
+        html.push_str("<section class=\"incidents active\"><h2>Active incidents</h2>");
+        for incident in active {
+            render_incident(&mut html, incident);
+        }
+        html.push_str("</section>");
+    }
+
+    html.push_str("<section class=\"components\"><h2>Components</h2>");
+    for component in &view.components {
+        render_component(&mut html, component);
+    }
+    html.push_str("</section>");
+
+    html.push_str("<section class=\"incidents past\"><h2>Past incidents</h2>");
+    if past.is_empty() {
+        let _ = write!(
+            html,
+            "<p class=\"empty\">No incidents in the last {PAST_INCIDENT_DAYS} days.</p>"
+        );
+    }
+    for incident in past {
+        render_incident(&mut html, incident);
+    }
+    let _ = write!(
+        html,
+        "</section></main><footer>Updated {}</footer></body></html>",
+        format_time(view.generated_at)
+    );
+    html
+}
+
+fn internal_error(err: StoreError) -> poem::Error {
+    poem::Error::from_string(err.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Page and what it shows, as last built
+struct CachedPage {
+    page: StatusPage,
+    view: StatusPageView,
+    built_at: Instant,
+}
+
+impl CachedPage {
+    fn is_fresh(&self) -> bool {
+        self.built_at.elapsed().as_secs() < u64::from(PAGE_MAX_AGE_SECS)
+    }
+}
+
+/// Built pages by slug, so visitors of a busy page don't each rebuild it from the store
+#[derive(Clone, Default)]
+pub(crate) struct PageCache {
+    pages: Arc<Mutex<HashMap<String, Arc<CachedPage>>>>,
+}
+
+impl PageCache {
+    /// Forget the page, for changes to show without waiting for it to expire
+    pub(crate) fn invalidate(&self, slug: &str) {
+        self.pages.lock().unwrap().remove(slug);
+    }
+
+    async fn get(&self, store: &Storage, slug: &str) -> poem::Result<Arc<CachedPage>> {
+        let cached = self.pages.lock().unwrap().get(slug).cloned();
+        if let Some(cached) = cached.filter(|cached| cached.is_fresh()) {
+            return Ok(cached);
+        }
+
+        let page = find_page(store, slug).await?;
+        let view = build(store, &page).await.map_err(internal_error)?;
+        let cached = Arc::new(CachedPage {
+            page,
+            view,
+            built_at: Instant::now(),
+        });
+        let mut pages = self.pages.lock().unwrap();
+        pages.retain(|_, cached| cached.is_fresh());
+        pages.insert(slug.to_string(), cached.clone());
+        Ok(cached)
+    }
+}
+
+async fn find_page(store: &Storage, slug: &str) -> poem::Result<StatusPage> {
+    match store.status_page_by_slug(slug).await {
+        Ok(Some(page)) => Ok(page),
+        Ok(None) => Err(poem::Error::from_string(
+            "No status page found with this slug",
+            StatusCode::NOT_FOUND,
+        )),
+        Err(err) => Err(internal_error(err)),
+    }
+}
+
+/// Status page, rendered as HTML
+#[handler]
+pub(crate) async fn page_html(
+    Path(slug): Path<String>,
+    Data(store): Data<&Storage>,
+    Data(cache): Data<&PageCache>,
+) -> poem::Result<Response> {
+    let cached = cache.get(store, &slug).await?;
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, "text/html; charset=utf-8")
+        .header(
+            CACHE_CONTROL,
+            format!("public, max-age={PAGE_MAX_AGE_SECS}"),
+        )
+        .body(render(&cached.page, &cached.view)))
+}
+
+/// Status page, as JSON
+#[handler]
+pub(crate) async fn page_json(
+    Path(slug): Path<String>,
+    Data(store): Data<&Storage>,
+    Data(cache): Data<&PageCache>,
+) -> poem::Result<Response> {
+    let cached = cache.get(store, &slug).await?;
+    Ok(Json(&cached.view)
+        .with_header(
+            CACHE_CONTROL,
+            format!("public, max-age={PAGE_MAX_AGE_SECS}"),
+        )
+        .into_response())
+}
+
+/// Logos and the stylesheet status pages are branded with
+#[handler]
+pub(crate) async fn asset(Path(name): Path<String>) -> poem::Result<Response> {
+    let not_found = || poem::Error::from_string("No such asset", StatusCode::NOT_FOUND);
+    if !is_asset_name(&name) {
+        return Err(not_found());
+    }
+    let content = tokio::fs::read(FilePath::new(ASSETS_DIR).join(&name))
+        .await
+        .map_err(|_| not_found())?;
+    let content_type = match name.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("css") => "text/css; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        _ => "application/octet-stream",
+    };
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, content_type)
+        .header(
+            CACHE_CONTROL,
+            format!("public, max-age={ASSET_MAX_AGE_SECS}"),
+        )
+        .body(content))
+}
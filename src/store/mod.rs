@@ -0,0 +1,608 @@
+use std::fmt;
+
+use bson::oid::ObjectId;
+use bson::Document;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::models::{
+    ApiKey, Check, CheckHistory, CheckSortField, CheckState, DeliveryStatus, FrequencyKind,
+    HTTPMethod, HeartbeatPing, HeartbeatSignal, Incident, IncidentStatus, IncidentUpdate,
+    MaintenanceWindow, NotificationChannel, Project, Resolution, Rollup, Status, StatusPage, User,
+    WebhookDelivery,
+};
+use crate::pagination::{Page, PageRequest};
+use crate::stats::Sample;
+
+mod mongo;
+mod sqlite;
+mod updates;
+
+pub(crate) use mongo::MongoStore;
+pub(crate) use sqlite::SqliteStore;
+pub(crate) use updates::{
+    ApiKeyFields, ChannelFields, CheckFields, MaintenanceWindowFields, ProjectFields,
+    StatusPageFields,
+};
+
+pub(crate) type Result<T> = std::result::Result<T, StoreError>;
+
+/// Failure reported by the backend, only ever logged or shown as is
+#[derive(Debug)]
+pub(crate) struct StoreError(String);
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<String> for StoreError {
+    fn from(err: String) -> Self {
+        Self(err)
+    }
+}
+
+macro_rules! store_error_from {
+    ($($error:ty),*) => {
+        $(impl From<$error> for StoreError {
+            fn from(err: $error) -> Self {
+                Self(err.to_string())
+            }
+        })*
+    };
+}
+
+store_error_from!(
+    mongodb::error::Error,
+    rusqlite::Error,
+    serde_json::Error,
+    bson::ser::Error,
+    bson::de::Error,
+    bson::raw::Error,
+    tokio::task::JoinError
+);
+
+/// Checks listed, every filter given must match
+#[derive(Default)]
+pub(crate) struct CheckFilter {
+    pub(crate) project_id: Option<ObjectId>,
+    pub(crate) method: Option<HTTPMethod>,
+    pub(crate) frequency: Option<FrequencyKind>,
+    pub(crate) current_state: Option<CheckState>,
+    /// Checks having every one of these tags
+    pub(crate) tags: Vec<String>,
+    /// Case insensitive text searched in the URL and the name
+    pub(crate) search: Option<String>,
+}
+
+/// History entries or rollups of one check, every filter given must match
+pub(crate) struct HistoryFilter {
+    pub(crate) check_id: ObjectId,
+    /// Entries at or after this moment, rollups starting at or after it
+    pub(crate) from: Option<DateTime<Utc>>,
+    /// Entries before this moment, rollups starting before it
+    pub(crate) to: Option<DateTime<Utc>>,
+    /// Rollups match `Error` when any of their executions failed, and any other status otherwise
+    pub(crate) status: Option<Status>,
+}
+
+/// Incidents listed most recent first, every filter given must match
+#[derive(Default)]
+pub(crate) struct IncidentFilter {
+    pub(crate) project_id: Option<ObjectId>,
+    pub(crate) check_id: Option<ObjectId>,
+    pub(crate) status: Option<IncidentStatus>,
+    /// Incidents open at some point between the two moments
+    pub(crate) during: Option<(DateTime<Utc>, DateTime<Utc>)>,
+}
+
+/// Webhook deliveries listed most recent first, every filter given must match
+#[derive(Default)]
+pub(crate) struct DeliveryFilter {
+    pub(crate) project_id: Option<ObjectId>,
+    pub(crate) channel_id: Option<ObjectId>,
+    pub(crate) check_id: Option<ObjectId>,
+    pub(crate) status: Option<DeliveryStatus>,
+}
+
+/// Where checks, their history and everything attached to them are kept.
+///
+/// Updates take the fields to change and return the document as saved.
+pub(crate) trait Store {
+    async fn checks(&self) -> Result<Vec<Check>>;
+    async fn find_checks(
+        &self,
+        filter: &CheckFilter,
+        sort: &CheckSortField,
+        request: &PageRequest,
+    ) -> Result<Page<Check>>;
+    async fn check(&self, check_id: ObjectId) -> Result<Option<Check>>;
+    async fn check_by_token(&self, token: &str) -> Result<Option<Check>>;
+    /// The ids out of `check_ids` that belong to a check of the project
+    async fn existing_checks(
+        &self,
+        project_id: ObjectId,
+        check_ids: &[ObjectId],
+    ) -> Result<Vec<ObjectId>>;
+    async fn count_checks(&self, project_id: ObjectId) -> Result<u64>;
+    async fn insert_check(&self, check: &Check) -> Result<()>;
+    async fn update_check(&self, check_id: ObjectId, fields: &CheckFields)
+        -> Result<Option<Check>>;
+    async fn delete_check(&self, check_id: ObjectId) -> Result<bool>;
+    /// Stop checks from notifying the channel, returns them as saved
+    async fn remove_channel_from_checks(&self, channel_id: ObjectId) -> Result<Vec<Check>>;
+
+    async fn insert_history(&self, entry: &CheckHistory) -> Result<()>;
+    async fn find_history(
+        &self,
+        filter: &HistoryFilter,
+        request: &PageRequest,
+    ) -> Result<Page<CheckHistory>>;
+    /// Entries of the check created between `from` and `to`
+    async fn samples(
+        &self,
+        check_id: ObjectId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Sample>>;
+    async fn oldest_history(&self) -> Result<Option<DateTime<Utc>>>;
+    /// Remove the raw history and the rollups of a check, returns how many raw entries it had
+    async fn delete_history(&self, check_id: ObjectId) -> Result<u64>;
+    async fn find_rollups(
+        &self,
+        filter: &HistoryFilter,
+        resolution: Resolution,
+        request: &PageRequest,
+    ) -> Result<Page<Rollup>>;
+    async fn latest_rollup(&self, resolution: Resolution) -> Result<Option<Rollup>>;
+    /// Save the rollup in place of the one of the same check, resolution and bucket
+    async fn save_rollup(&self, rollup: &Rollup) -> Result<()>;
+    /// Remove raw history created before `history_before` and rollups past their expiry
+    async fn remove_expired(&self, history_before: Option<DateTime<Utc>>) -> Result<()>;
+
+    async fn insert_incident(&self, incident: &Incident) -> Result<()>;
+    async fn save_incident(&self, incident: &Incident) -> Result<()>;
+    async fn incident(&self, incident_id: ObjectId) -> Result<Option<Incident>>;
+    async fn open_incident(&self, check_id: ObjectId) -> Result<Option<Incident>>;
+    /// Append the update to the incident, returns the incident as saved
+    async fn add_incident_update(
+        &self,
+        incident_id: ObjectId,
+        update: &IncidentUpdate,
+    ) -> Result<Option<Incident>>;
+    async fn find_incidents(&self, filter: &IncidentFilter) -> Result<Vec<Incident>>;
+
+    async fn channels(&self, project_id: ObjectId) -> Result<Vec<NotificationChannel>>;
+    async fn channel(&self, channel_id: ObjectId) -> Result<Option<NotificationChannel>>;
+    async fn channels_by_id(&self, channel_ids: &[ObjectId]) -> Result<Vec<NotificationChannel>>;
+    async fn insert_channel(&self, channel: &NotificationChannel) -> Result<()>;
+    async fn update_channel(
+        &self,
+        channel_id: ObjectId,
+        fields: &ChannelFields,
+    ) -> Result<Option<NotificationChannel>>;
+    async fn delete_channel(&self, channel_id: ObjectId) -> Result<bool>;
+
+    async fn insert_delivery(&self, delivery: &WebhookDelivery) -> Result<()>;
+    async fn save_delivery(&self, delivery: &WebhookDelivery) -> Result<()>;
+    async fn delivery(&self, delivery_id: ObjectId) -> Result<Option<WebhookDelivery>>;
+    async fn find_deliveries(
+        &self,
+        filter: &DeliveryFilter,
+        limit: u32,
+    ) -> Result<Vec<WebhookDelivery>>;
+    /// Pending deliveries whose next attempt is due at `now`
+    async fn due_deliveries(&self, now: DateTime<Utc>) -> Result<Vec<WebhookDelivery>>;
+
+    async fn insert_ping(&self, ping: &HeartbeatPing) -> Result<()>;
+    /// Pings of the check, most recent first
+    async fn find_pings(
+        &self,
+        check_id: ObjectId,
+        signal: Option<HeartbeatSignal>,
+        limit: u32,
+    ) -> Result<Vec<HeartbeatPing>>;
+
+    async fn maintenance_windows(&self, project_id: ObjectId) -> Result<Vec<MaintenanceWindow>>;
+    /// Windows of the check's project covering it by id or by tag, whether they are open or not
+    async fn maintenance_windows_for(&self, check: &Check) -> Result<Vec<MaintenanceWindow>>;
+    async fn maintenance_window(&self, window_id: ObjectId) -> Result<Option<MaintenanceWindow>>;
+    async fn insert_maintenance_window(&self, window: &MaintenanceWindow) -> Result<()>;
+    async fn update_maintenance_window(
+        &self,
+        window_id: ObjectId,
+        fields: &MaintenanceWindowFields,
+    ) -> Result<Option<MaintenanceWindow>>;
+    async fn delete_maintenance_window(&self, window_id: ObjectId) -> Result<bool>;
+
+    /// Keys of the project, or of every project when none is given
+    async fn api_keys(&self, project_id: Option<ObjectId>) -> Result<Vec<ApiKey>>;
+    async fn api_key(&self, key_id: ObjectId) -> Result<Option<ApiKey>>;
+    async fn api_key_by_hash(&self, hash: &str) -> Result<Option<ApiKey>>;
+    async fn insert_api_key(&self, api_key: &ApiKey) -> Result<()>;
+    async fn update_api_key(
+        &self,
+        key_id: ObjectId,
+        fields: &ApiKeyFields,
+    ) -> Result<Option<ApiKey>>;
+
+    /// Users of the project, or of every project when none is given
+    async fn users(&self, project_id: Option<ObjectId>) -> Result<Vec<User>>;
+    async fn user(&self, user_id: ObjectId) -> Result<Option<User>>;
+    async fn insert_user(&self, user: &User) -> Result<()>;
+    /// Delete the user and revoke the keys minted for them
+    async fn delete_user(&self, user_id: ObjectId) -> Result<bool>;
+
+    async fn projects(&self) -> Result<Vec<Project>>;
+    async fn project(&self, project_id: ObjectId) -> Result<Option<Project>>;
+    async fn insert_project(&self, project: &Project) -> Result<()>;
+    async fn update_project(
+        &self,
+        project_id: ObjectId,
+        fields: &ProjectFields,
+    ) -> Result<Option<Project>>;
+    /// Give everything saved without a project to this one
+    async fn adopt_orphans(&self, project_id: ObjectId) -> Result<()>;
+
+    async fn status_pages(&self, project_id: ObjectId) -> Result<Vec<StatusPage>>;
+    async fn status_page(&self, page_id: ObjectId) -> Result<Option<StatusPage>>;
+    async fn status_page_by_slug(&self, slug: &str) -> Result<Option<StatusPage>>;
+    async fn insert_status_page(&self, page: &StatusPage) -> Result<()>;
+    async fn update_status_page(
+        &self,
+        page_id: ObjectId,
+        fields: &StatusPageFields,
+    ) -> Result<Option<StatusPage>>;
+    async fn delete_status_page(&self, page_id: ObjectId) -> Result<bool>;
+}
+
+/// The store picked by the configuration, cheap to clone into tasks and handlers
+#[derive(Clone)]
+pub(crate) enum Storage {
+    Mongo(MongoStore),
+    Sqlite(SqliteStore),
+}
+
+macro_rules! dispatch {
+    ($self:ident.$method:ident($($arg:expr),*)) => {
+        match $self {
+            Storage::Mongo(store) => store.$method($($arg),*).await,
+            Storage::Sqlite(store) => store.$method($($arg),*).await,
+        }
+    };
+}
+
+impl Store for Storage {
+    async fn checks(&self) -> Result<Vec<Check>> {
+        dispatch!(self.checks())
+    }
+
+    async fn find_checks(
+        &self,
+        filter: &CheckFilter,
+        sort: &CheckSortField,
+        request: &PageRequest,
+    ) -> Result<Page<Check>> {
+        dispatch!(self.find_checks(filter, sort, request))
+    }
+
+    async fn check(&self, check_id: ObjectId) -> Result<Option<Check>> {
+        dispatch!(self.check(check_id))
+    }
+
+    async fn check_by_token(&self, token: &str) -> Result<Option<Check>> {
+        dispatch!(self.check_by_token(token))
+    }
+
+    async fn existing_checks(
+        &self,
+        project_id: ObjectId,
+        check_ids: &[ObjectId],
+    ) -> Result<Vec<ObjectId>> {
+        dispatch!(self.existing_checks(project_id, check_ids))
+    }
+
+    async fn count_checks(&self, project_id: ObjectId) -> Result<u64> {
+        dispatch!(self.count_checks(project_id))
+    }
+
+    async fn insert_check(&self, check: &Check) -> Result<()> {
+        dispatch!(self.insert_check(check))
+    }
+
+    async fn update_check(
+        &self,
+        check_id: ObjectId,
+        fields: &CheckFields,
+    ) -> Result<Option<Check>> {
+        dispatch!(self.update_check(check_id, fields))
+    }
+
+    async fn delete_check(&self, check_id: ObjectId) -> Result<bool> {
+        dispatch!(self.delete_check(check_id))
+    }
+
+    async fn remove_channel_from_checks(&self, channel_id: ObjectId) -> Result<Vec<Check>> {
+        dispatch!(self.remove_channel_from_checks(channel_id))
+    }
+
+    async fn insert_history(&self, entry: &CheckHistory) -> Result<()> {
+        dispatch!(self.insert_history(entry))
+    }
+
+    async fn find_history(
+        &self,
+        filter: &HistoryFilter,
+        request: &PageRequest,
+    ) -> Result<Page<CheckHistory>> {
+        dispatch!(self.find_history(filter, request))
+    }
+
+    async fn samples(
+        &self,
+        check_id: ObjectId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Sample>> {
+        dispatch!(self.samples(check_id, from, to))
+    }
+
+    async fn oldest_history(&self) -> Result<Option<DateTime<Utc>>> {
+        dispatch!(self.oldest_history())
+    }
+
+    async fn delete_history(&self, check_id: ObjectId) -> Result<u64> {
+        dispatch!(self.delete_history(check_id))
+    }
+
+    async fn find_rollups(
+        &self,
+        filter: &HistoryFilter,
+        resolution: Resolution,
+        request: &PageRequest,
+    ) -> Result<Page<Rollup>> {
+        dispatch!(self.find_rollups(filter, resolution, request))
+    }
+
+    async fn latest_rollup(&self, resolution: Resolution) -> Result<Option<Rollup>> {
+        dispatch!(self.latest_rollup(resolution))
+    }
+
+    async fn save_rollup(&self, rollup: &Rollup) -> Result<()> {
+        dispatch!(self.save_rollup(rollup))
+    }
+
+    async fn remove_expired(&self, history_before: Option<DateTime<Utc>>) -> Result<()> {
+        dispatch!(self.remove_expired(history_before))
+    }
+
+    async fn insert_incident(&self, incident: &Incident) -> Result<()> {
+        dispatch!(self.insert_incident(incident))
+    }
+
+    async fn save_incident(&self, incident: &Incident) -> Result<()> {
+        dispatch!(self.save_incident(incident))
+    }
+
+    async fn incident(&self, incident_id: ObjectId) -> Result<Option<Incident>> {
+        dispatch!(self.incident(incident_id))
+    }
+
+    async fn open_incident(&self, check_id: ObjectId) -> Result<Option<Incident>> {
+        dispatch!(self.open_incident(check_id))
+    }
+
+    async fn add_incident_update(
+        &self,
+        incident_id: ObjectId,
+        update: &IncidentUpdate,
+    ) -> Result<Option<Incident>> {
+        dispatch!(self.add_incident_update(incident_id, update))
+    }
+
+    async fn find_incidents(&self, filter: &IncidentFilter) -> Result<Vec<Incident>> {
+        dispatch!(self.find_incidents(filter))
+    }
+
+    async fn channels(&self, project_id: ObjectId) -> Result<Vec<NotificationChannel>> {
+        dispatch!(self.channels(project_id))
+    }
+
+    async fn channel(&self, channel_id: ObjectId) -> Result<Option<NotificationChannel>> {
+        dispatch!(self.channel(channel_id))
+    }
+
+    async fn channels_by_id(&self, channel_ids: &[ObjectId]) -> Result<Vec<NotificationChannel>> {
+        dispatch!(self.channels_by_id(channel_ids))
+    }
+
+    async fn insert_channel(&self, channel: &NotificationChannel) -> Result<()> {
+        dispatch!(self.insert_channel(channel))
+    }
+
+    async fn update_channel(
+        &self,
+        channel_id: ObjectId,
+        fields: &ChannelFields,
+    ) -> Result<Option<NotificationChannel>> {
+        dispatch!(self.update_channel(channel_id, fields))
+    }
+
+    async fn delete_channel(&self, channel_id: ObjectId) -> Result<bool> {
+        dispatch!(self.delete_channel(channel_id))
+    }
+
+    async fn insert_delivery(&self, delivery: &WebhookDelivery) -> Result<()> {
+        dispatch!(self.insert_delivery(delivery))
+    }
+
+    async fn save_delivery(&self, delivery: &WebhookDelivery) -> Result<()> {
+        dispatch!(self.save_delivery(delivery))
+    }
+
+    async fn delivery(&self, delivery_id: ObjectId) -> Result<Option<WebhookDelivery>> {
+        dispatch!(self.delivery(delivery_id))
+    }
+
+    async fn find_deliveries(
+        &self,
+        filter: &DeliveryFilter,
+        limit: u32,
+    ) -> Result<Vec<WebhookDelivery>> {
+        dispatch!(self.find_deliveries(filter, limit))
+    }
+
+    async fn due_deliveries(&self, now: DateTime<Utc>) -> Result<Vec<WebhookDelivery>> {
+        dispatch!(self.due_deliveries(now))
+    }
+
+    async fn insert_ping(&self, ping: &HeartbeatPing) -> Result<()> {
+        dispatch!(self.insert_ping(ping))
+    }
+
+    async fn find_pings(
+        &self,
+        check_id: ObjectId,
+        signal: Option<HeartbeatSignal>,
+        limit: u32,
+    ) -> Result<Vec<HeartbeatPing>> {
+        dispatch!(self.find_pings(check_id, signal, limit))
+    }
+
+    async fn maintenance_windows(&self, project_id: ObjectId) -> Result<Vec<MaintenanceWindow>> {
+        dispatch!(self.maintenance_windows(project_id))
+    }
+
+    async fn maintenance_windows_for(&self, check: &Check) -> Result<Vec<MaintenanceWindow>> {
+        dispatch!(self.maintenance_windows_for(check))
+    }
+
+    async fn maintenance_window(&self, window_id: ObjectId) -> Result<Option<MaintenanceWindow>> {
+        dispatch!(self.maintenance_window(window_id))
+    }
+
+    async fn insert_maintenance_window(&self, window: &MaintenanceWindow) -> Result<()> {
+        dispatch!(self.insert_maintenance_window(window))
+    }
+
+    async fn update_maintenance_window(
+        &self,
+        window_id: ObjectId,
+        fields: &MaintenanceWindowFields,
+    ) -> Result<Option<MaintenanceWindow>> {
+        dispatch!(self.update_maintenance_window(window_id, fields))
+    }
+
+    async fn delete_maintenance_window(&self, window_id: ObjectId) -> Result<bool> {
+        dispatch!(self.delete_maintenance_window(window_id))
+    }
+
+    async fn api_keys(&self, project_id: Option<ObjectId>) -> Result<Vec<ApiKey>> {
+        dispatch!(self.api_keys(project_id))
+    }
+
+    async fn api_key(&self, key_id: ObjectId) -> Result<Option<ApiKey>> {
+        dispatch!(self.api_key(key_id))
+    }
+
+    async fn api_key_by_hash(&self, hash: &str) -> Result<Option<ApiKey>> {
+        dispatch!(self.api_key_by_hash(hash))
+    }
+
+    async fn insert_api_key(&self, api_key: &ApiKey) -> Result<()> {
+        dispatch!(self.insert_api_key(api_key))
+    }
+
+    async fn update_api_key(
+        &self,
+        key_id: ObjectId,
+        fields: &ApiKeyFields,
+    ) -> Result<Option<ApiKey>> {
+        dispatch!(self.update_api_key(key_id, fields))
+    }
+
+    async fn users(&self, project_id: Option<ObjectId>) -> Result<Vec<User>> {
+        dispatch!(self.users(project_id))
+    }
+
+    async fn user(&self, user_id: ObjectId) -> Result<Option<User>> {
+        dispatch!(self.user(user_id))
+    }
+
+    async fn insert_user(&self, user: &User) -> Result<()> {
+        dispatch!(self.insert_user(user))
+    }
+
+    async fn delete_user(&self, user_id: ObjectId) -> Result<bool> {
+        dispatch!(self.delete_user(user_id))
+    }
+
+    async fn projects(&self) -> Result<Vec<Project>> {
+        dispatch!(self.projects())
+    }
+
+    async fn project(&self, project_id: ObjectId) -> Result<Option<Project>> {
+        dispatch!(self.project(project_id))
+    }
+
+    async fn insert_project(&self, project: &Project) -> Result<()> {
+        dispatch!(self.insert_project(project))
+    }
+
+    async fn update_project(
+        &self,
+        project_id: ObjectId,
+        fields: &ProjectFields,
+    ) -> Result<Option<Project>> {
+        dispatch!(self.update_project(project_id, fields))
+    }
+
+    async fn adopt_orphans(&self, project_id: ObjectId) -> Result<()> {
+        dispatch!(self.adopt_orphans(project_id))
+    }
+
+    async fn status_pages(&self, project_id: ObjectId) -> Result<Vec<StatusPage>> {
+        dispatch!(self.status_pages(project_id))
+    }
+
+    async fn status_page(&self, page_id: ObjectId) -> Result<Option<StatusPage>> {
+        dispatch!(self.status_page(page_id))
+    }
+
+    async fn status_page_by_slug(&self, slug: &str) -> Result<Option<StatusPage>> {
+        dispatch!(self.status_page_by_slug(slug))
+    }
+
+    async fn insert_status_page(&self, page: &StatusPage) -> Result<()> {
+        dispatch!(self.insert_status_page(page))
+    }
+
+    async fn update_status_page(
+        &self,
+        page_id: ObjectId,
+        fields: &StatusPageFields,
+    ) -> Result<Option<StatusPage>> {
+        dispatch!(self.update_status_page(page_id, fields))
+    }
+
+    async fn delete_status_page(&self, page_id: ObjectId) -> Result<bool> {
+        dispatch!(self.delete_status_page(page_id))
+    }
+}
+
+/// `value` the way the driver writes it, with BSON dates where JSON gets RFC 3339 strings
+pub(crate) fn to_document(value: &impl Serialize) -> Result<Document> {
+    Ok(bson::to_raw_document_buf(value)?.to_document()?)
+}
+
+/// `value` with `fields` set, the way a `$set` update leaves it
+pub(crate) fn merge<T: Serialize + DeserializeOwned>(
+    value: &T,
+    fields: &impl Serialize,
+) -> Result<T> {
+    let mut document = to_document(value)?;
+    document.extend(to_document(fields)?);
+    Ok(bson::from_document(document)?)
+}
@@ -0,0 +1,842 @@
+use bson::oid::ObjectId;
+use bson::{doc, Bson, Document};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use mongodb::options::ReturnDocument;
+use mongodb::{Collection, Database};
+use serde::de::DeserializeOwned;
+
+use super::{
+    to_document, ApiKeyFields, ChannelFields, CheckFields, CheckFilter, DeliveryFilter,
+    HistoryFilter, IncidentFilter, MaintenanceWindowFields, ProjectFields, Result,
+    StatusPageFields, Store,
+};
+use crate::models::{
+    ApiKey, Check, CheckHistory, CheckSortField, FrequencyKind, HeartbeatPing, HeartbeatSignal,
+    Incident, IncidentUpdate, MaintenanceWindow, NotificationChannel, Project, Resolution, Rollup,
+    Status, StatusPage, User, WebhookDelivery,
+};
+use crate::pagination::{self, Cursor, Page, PageRequest};
+use crate::stats::Sample;
+use crate::timestamps;
+
+/// The MongoDB database the service has always run on
+#[derive(Clone)]
+pub(crate) struct MongoStore {
+    database: Database,
+}
+
+impl MongoStore {
+    pub(crate) fn new(database: Database) -> Self {
+        Self { database }
+    }
+
+    fn checks_collection(&self) -> Collection<Check> {
+        self.database.collection::<Check>("checks")
+    }
+
+    fn history_collection(&self) -> Collection<CheckHistory> {
+        self.database.collection::<CheckHistory>("checks_history")
+    }
+
+    fn rollups_collection(&self) -> Collection<Rollup> {
+        self.database.collection::<Rollup>("checks_rollups")
+    }
+
+    fn incidents_collection(&self) -> Collection<Incident> {
+        self.database.collection::<Incident>("incidents")
+    }
+
+    fn channels_collection(&self) -> Collection<NotificationChannel> {
+        self.database
+            .collection::<NotificationChannel>("notification_channels")
+    }
+
+    fn deliveries_collection(&self) -> Collection<WebhookDelivery> {
+        self.database
+            .collection::<WebhookDelivery>("webhook_deliveries")
+    }
+
+    fn pings_collection(&self) -> Collection<HeartbeatPing> {
+        self.database.collection::<HeartbeatPing>("heartbeat_pings")
+    }
+
+    fn maintenance_collection(&self) -> Collection<MaintenanceWindow> {
+        self.database
+            .collection::<MaintenanceWindow>("maintenance_windows")
+    }
+
+    fn api_keys_collection(&self) -> Collection<ApiKey> {
+        self.database.collection::<ApiKey>("api_keys")
+    }
+
+    fn users_collection(&self) -> Collection<User> {
+        self.database.collection::<User>("users")
+    }
+
+    fn projects_collection(&self) -> Collection<Project> {
+        self.database.collection::<Project>("projects")
+    }
+
+    fn status_pages_collection(&self) -> Collection<StatusPage> {
+        self.database.collection::<StatusPage>("status_pages")
+    }
+}
+
+/// Collections whose documents belong to a project
+const PROJECT_COLLECTIONS: [&str; 6] = [
+    "checks",
+    "incidents",
+    "notification_channels",
+    "webhook_deliveries",
+    "maintenance_windows",
+    "api_keys",
+];
+
+/// Fetch up to `limit` items sorted on `field`, and the cursor of the next page if there is one
+async fn find_page<T, F>(
+    collection: &Collection<T>,
+    mut filter: Document,
+    field: &str,
+    request: &PageRequest,
+    position: F,
+) -> Result<Page<T>>
+where
+    T: DeserializeOwned + Send + Sync,
+    F: Fn(&T) -> Cursor,
+{
+    if let Some(ref cursor) = request.cursor {
+        filter = doc! {"$and": [filter, cursor.filter(field, &request.order)]};
+    }
+    // Fetch one extra item to know whether there is a next page
+    let items: Vec<T> = collection
+        .find(filter)
+        .sort(pagination::sort(field, &request.order))
+        .limit(request.limit as i64 + 1)
+        .await?
+        .try_collect()
+        .await?;
+    Ok(Page::new(items, request.limit, position))
+}
+
+fn check_filter(filter: &CheckFilter) -> Document {
+    let mut conditions = vec![];
+    if let Some(project_id) = filter.project_id {
+        conditions.push(doc! {"project_id": project_id});
+    }
+    if let Some(ref method) = filter.method {
+        conditions.push(doc! {"method": method.to_string()});
+    }
+    if let Some(ref frequency) = filter.frequency {
+        conditions.push(match frequency {
+            FrequencyKind::Interval => doc! {"frequency.seconds": {"$exists": true}},
+            FrequencyKind::Cron => doc! {"frequency.cron": {"$exists": true}},
+            preset => doc! {"frequency": bson::to_bson(preset).unwrap()},
+        });
+    }
+    if let Some(ref current_state) = filter.current_state {
+        conditions.push(doc! {"current_state": bson::to_bson(current_state).unwrap()});
+    }
+    if !filter.tags.is_empty() {
+        conditions.push(doc! {"tags": {"$all": &filter.tags}});
+    }
+    if let Some(ref search) = filter.search {
+        let pattern = bson::Regex {
+            pattern: regex::escape(search),
+            options: "i".to_string(),
+        };
+        conditions.push(doc! {
+            "$or": [{"url": pattern.clone()}, {"name": pattern}]
+        });
+    }
+    if conditions.is_empty() {
+        doc! {}
+    } else {
+        doc! {"$and": conditions}
+    }
+}
+
+fn history_filter(filter: &HistoryFilter, field: &str) -> Document {
+    let mut range = doc! {};
+    if let Some(from) = filter.from {
+        range.insert("$gte", timestamps::to_bson(from));
+    }
+    if let Some(to) = filter.to {
+        range.insert("$lt", timestamps::to_bson(to));
+    }
+    let mut document = doc! {"check_id": filter.check_id};
+    if !range.is_empty() {
+        document.insert(field, range);
+    }
+    document
+}
+
+impl Store for MongoStore {
+    async fn checks(&self) -> Result<Vec<Check>> {
+        Ok(self
+            .checks_collection()
+            .find(doc! {})
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn find_checks(
+        &self,
+        filter: &CheckFilter,
+        sort: &CheckSortField,
+        request: &PageRequest,
+    ) -> Result<Page<Check>> {
+        find_page(
+            &self.checks_collection(),
+            check_filter(filter),
+            sort.field(),
+            request,
+            |check| Cursor::new(sort.value(check), check._id),
+        )
+        .await
+    }
+
+    async fn check(&self, check_id: ObjectId) -> Result<Option<Check>> {
+        Ok(self
+            .checks_collection()
+            .find_one(doc! {"_id": check_id})
+            .await?)
+    }
+
+    async fn check_by_token(&self, token: &str) -> Result<Option<Check>> {
+        Ok(self
+            .checks_collection()
+            .find_one(doc! {"kind": "Heartbeat", "heartbeat.token": token})
+            .await?)
+    }
+
+    async fn existing_checks(
+        &self,
+        project_id: ObjectId,
+        check_ids: &[ObjectId],
+    ) -> Result<Vec<ObjectId>> {
+        let ids = self
+            .checks_collection()
+            .distinct(
+                "_id",
+                doc! {"_id": {"$in": check_ids}, "project_id": project_id},
+            )
+            .await?;
+        Ok(ids.into_iter().filter_map(|id| id.as_object_id()).collect())
+    }
+
+    async fn count_checks(&self, project_id: ObjectId) -> Result<u64> {
+        Ok(self
+            .checks_collection()
+            .count_documents(doc! {"project_id": project_id})
+            .await?)
+    }
+
+    async fn insert_check(&self, check: &Check) -> Result<()> {
+        self.checks_collection().insert_one(check).await?;
+        Ok(())
+    }
+
+    async fn update_check(
+        &self,
+        check_id: ObjectId,
+        fields: &CheckFields,
+    ) -> Result<Option<Check>> {
+        Ok(self
+            .checks_collection()
+            .find_one_and_update(doc! {"_id": check_id}, doc! {"$set": to_document(fields)?})
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn delete_check(&self, check_id: ObjectId) -> Result<bool> {
+        let delete = self
+            .checks_collection()
+            .delete_one(doc! {"_id": check_id})
+            .await?;
+        Ok(delete.deleted_count > 0)
+    }
+
+    async fn remove_channel_from_checks(&self, channel_id: ObjectId) -> Result<Vec<Check>> {
+        let checks = self.checks_collection();
+        let filter = doc! {"channels": channel_id};
+        let mut affected: Vec<Check> = checks.find(filter.clone()).await?.try_collect().await?;
+        checks
+            .update_many(filter, doc! {"$pull": {"channels": channel_id}})
+            .await?;
+        for check in &mut affected {
+            check.channels.retain(|id| id != &channel_id);
+        }
+        Ok(affected)
+    }
+
+    async fn insert_history(&self, entry: &CheckHistory) -> Result<()> {
+        self.history_collection().insert_one(entry).await?;
+        Ok(())
+    }
+
+    async fn find_history(
+        &self,
+        filter: &HistoryFilter,
+        request: &PageRequest,
+    ) -> Result<Page<CheckHistory>> {
+        let mut document = history_filter(filter, "created_at");
+        if let Some(ref status) = filter.status {
+            document.insert("status", bson::to_bson(status).unwrap());
+        }
+        find_page(
+            &self.history_collection(),
+            document,
+            "created_at",
+            request,
+            |entry| Cursor::new(Some(entry.created_at), entry._id),
+        )
+        .await
+    }
+
+    async fn samples(
+        &self,
+        check_id: ObjectId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Sample>> {
+        let filter = doc! {
+            "check_id": check_id,
+            "created_at": {
+                "$gte": timestamps::to_bson(from),
+                "$lt": timestamps::to_bson(to),
+            },
+        };
+        Ok(self
+            .history_collection()
+            .clone_with_type::<Sample>()
+            .find(filter)
+            .projection(doc! {"created_at": 1, "status": 1, "latency.total_ms": 1})
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn oldest_history(&self) -> Result<Option<DateTime<Utc>>> {
+        let oldest = self
+            .history_collection()
+            .find_one(doc! {})
+            .sort(doc! {"created_at": 1})
+            .await?;
+        Ok(oldest.map(|entry| entry.created_at))
+    }
+
+    async fn delete_history(&self, check_id: ObjectId) -> Result<u64> {
+        self.rollups_collection()
+            .delete_many(doc! {"check_id": check_id})
+            .await?;
+        let delete = self
+            .history_collection()
+            .delete_many(doc! {"check_id": check_id})
+            .await?;
+        Ok(delete.deleted_count)
+    }
+
+    async fn find_rollups(
+        &self,
+        filter: &HistoryFilter,
+        resolution: Resolution,
+        request: &PageRequest,
+    ) -> Result<Page<Rollup>> {
+        let mut document = history_filter(filter, "bucket");
+        match filter.status {
+            Some(Status::Error) => document.insert("failures", doc! {"$gt": 0}),
+            Some(_) => document.insert("failures", 0),
+            None => None,
+        };
+        document.insert("resolution", bson::to_bson(&resolution).unwrap());
+        find_page(
+            &self.rollups_collection(),
+            document,
+            "bucket",
+            request,
+            |rollup| Cursor::new(Some(rollup.bucket), rollup._id),
+        )
+        .await
+    }
+
+    async fn latest_rollup(&self, resolution: Resolution) -> Result<Option<Rollup>> {
+        Ok(self
+            .rollups_collection()
+            .find_one(doc! {"resolution": bson::to_bson(&resolution).unwrap()})
+            .sort(doc! {"bucket": -1})
+            .await?)
+    }
+
+    async fn save_rollup(&self, rollup: &Rollup) -> Result<()> {
+        let mut fields = to_document(rollup)?;
+        fields.remove("_id");
+        self.rollups_collection()
+            .update_one(
+                doc! {
+                    "check_id": rollup.check_id,
+                    "resolution": bson::to_bson(&rollup.resolution).unwrap(),
+                    "bucket": timestamps::to_bson(rollup.bucket),
+                },
+                doc! {"$set": fields},
+            )
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_expired(&self, _history_before: Option<DateTime<Utc>>) -> Result<()> {
+        // TTL indexes already take care of it
+        Ok(())
+    }
+
+    async fn insert_incident(&self, incident: &Incident) -> Result<()> {
+        self.incidents_collection().insert_one(incident).await?;
+        Ok(())
+    }
+
+    async fn save_incident(&self, incident: &Incident) -> Result<()> {
+        self.incidents_collection()
+            .replace_one(doc! {"_id": incident._id}, incident)
+            .await?;
+        Ok(())
+    }
+
+    async fn incident(&self, incident_id: ObjectId) -> Result<Option<Incident>> {
+        Ok(self
+            .incidents_collection()
+            .find_one(doc! {"_id": incident_id})
+            .await?)
+    }
+
+    async fn open_incident(&self, check_id: ObjectId) -> Result<Option<Incident>> {
+        Ok(self
+            .incidents_collection()
+            .find_one(doc! {"check_id": check_id, "status": "Open"})
+            .await?)
+    }
+
+    async fn add_incident_update(
+        &self,
+        incident_id: ObjectId,
+        update: &IncidentUpdate,
+    ) -> Result<Option<Incident>> {
+        Ok(self
+            .incidents_collection()
+            .find_one_and_update(
+                doc! {"_id": incident_id},
+                doc! {"$push": {"updates": to_document(update)?}},
+            )
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn find_incidents(&self, filter: &IncidentFilter) -> Result<Vec<Incident>> {
+        let mut document = doc! {};
+        if let Some(project_id) = filter.project_id {
+            document.insert("project_id", project_id);
+        }
+        if let Some(check_id) = filter.check_id {
+            document.insert("check_id", check_id);
+        }
+        if let Some(ref status) = filter.status {
+            document.insert("status", bson::to_bson(status).unwrap());
+        }
+        if let Some((from, to)) = filter.during {
+            document.insert("started_at", doc! {"$lt": timestamps::to_bson(to)});
+            document.insert(
+                "$or",
+                vec![
+                    doc! {"resolved_at": Bson::Null},
+                    doc! {"resolved_at": {"$gt": timestamps::to_bson(from)}},
+                ],
+            );
+        }
+        Ok(self
+            .incidents_collection()
+            .find(document)
+            .sort(doc! {"started_at": -1})
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn channels(&self, project_id: ObjectId) -> Result<Vec<NotificationChannel>> {
+        Ok(self
+            .channels_collection()
+            .find(doc! {"project_id": project_id})
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn channel(&self, channel_id: ObjectId) -> Result<Option<NotificationChannel>> {
+        Ok(self
+            .channels_collection()
+            .find_one(doc! {"_id": channel_id})
+            .await?)
+    }
+
+    async fn channels_by_id(&self, channel_ids: &[ObjectId]) -> Result<Vec<NotificationChannel>> {
+        Ok(self
+            .channels_collection()
+            .find(doc! {"_id": {"$in": channel_ids}})
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn insert_channel(&self, channel: &NotificationChannel) -> Result<()> {
+        self.channels_collection().insert_one(channel).await?;
+        Ok(())
+    }
+
+    async fn update_channel(
+        &self,
+        channel_id: ObjectId,
+        fields: &ChannelFields,
+    ) -> Result<Option<NotificationChannel>> {
+        Ok(self
+            .channels_collection()
+            .find_one_and_update(
+                doc! {"_id": channel_id},
+                doc! {"$set": to_document(fields)?},
+            )
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn delete_channel(&self, channel_id: ObjectId) -> Result<bool> {
+        let delete = self
+            .channels_collection()
+            .delete_one(doc! {"_id": channel_id})
+            .await?;
+        Ok(delete.deleted_count > 0)
+    }
+
+    async fn insert_delivery(&self, delivery: &WebhookDelivery) -> Result<()> {
+        self.deliveries_collection().insert_one(delivery).await?;
+        Ok(())
+    }
+
+    async fn save_delivery(&self, delivery: &WebhookDelivery) -> Result<()> {
+        self.deliveries_collection()
+            .replace_one(doc! {"_id": delivery._id}, delivery)
+            .await?;
+        Ok(())
+    }
+
+    async fn delivery(&self, delivery_id: ObjectId) -> Result<Option<WebhookDelivery>> {
+        Ok(self
+            .deliveries_collection()
+            .find_one(doc! {"_id": delivery_id})
+            .await?)
+    }
+
+    async fn find_deliveries(
+        &self,
+        filter: &DeliveryFilter,
+        limit: u32,
+    ) -> Result<Vec<WebhookDelivery>> {
+        let mut document = doc! {};
+        if let Some(project_id) = filter.project_id {
+            document.insert("project_id", project_id);
+        }
+        if let Some(channel_id) = filter.channel_id {
+            document.insert("channel_id", channel_id);
+        }
+        if let Some(check_id) = filter.check_id {
+            document.insert("check_id", check_id);
+        }
+        if let Some(ref status) = filter.status {
+            document.insert("status", bson::to_bson(status).unwrap());
+        }
+        Ok(self
+            .deliveries_collection()
+            .find(document)
+            .sort(doc! {"created_at": -1})
+            .limit(limit.into())
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn due_deliveries(&self, now: DateTime<Utc>) -> Result<Vec<WebhookDelivery>> {
+        Ok(self
+            .deliveries_collection()
+            .find(doc! {
+                "status": "Pending",
+                "next_attempt_at": {"$lte": timestamps::to_bson(now)},
+            })
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn insert_ping(&self, ping: &HeartbeatPing) -> Result<()> {
+        self.pings_collection().insert_one(ping).await?;
+        Ok(())
+    }
+
+    async fn find_pings(
+        &self,
+        check_id: ObjectId,
+        signal: Option<HeartbeatSignal>,
+        limit: u32,
+    ) -> Result<Vec<HeartbeatPing>> {
+        let mut filter = doc! {"check_id": check_id};
+        if let Some(signal) = signal {
+            filter.insert("signal", bson::to_bson(&signal).unwrap());
+        }
+        Ok(self
+            .pings_collection()
+            .find(filter)
+            .sort(doc! {"received_at": -1})
+            .limit(limit.into())
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn maintenance_windows(&self, project_id: ObjectId) -> Result<Vec<MaintenanceWindow>> {
+        Ok(self
+            .maintenance_collection()
+            .find(doc! {"project_id": project_id})
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn maintenance_windows_for(&self, check: &Check) -> Result<Vec<MaintenanceWindow>> {
+        Ok(self
+            .maintenance_collection()
+            .find(doc! {
+                "project_id": check.project_id,
+                "$or": [{"checks": check._id}, {"tags": {"$in": &check.tags}}],
+            })
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn maintenance_window(&self, window_id: ObjectId) -> Result<Option<MaintenanceWindow>> {
+        Ok(self
+            .maintenance_collection()
+            .find_one(doc! {"_id": window_id})
+            .await?)
+    }
+
+    async fn insert_maintenance_window(&self, window: &MaintenanceWindow) -> Result<()> {
+        self.maintenance_collection().insert_one(window).await?;
+        Ok(())
+    }
+
+    async fn update_maintenance_window(
+        &self,
+        window_id: ObjectId,
+        fields: &MaintenanceWindowFields,
+    ) -> Result<Option<MaintenanceWindow>> {
+        Ok(self
+            .maintenance_collection()
+            .find_one_and_update(doc! {"_id": window_id}, doc! {"$set": to_document(fields)?})
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn delete_maintenance_window(&self, window_id: ObjectId) -> Result<bool> {
+        let delete = self
+            .maintenance_collection()
+            .delete_one(doc! {"_id": window_id})
+            .await?;
+        Ok(delete.deleted_count > 0)
+    }
+
+    async fn api_keys(&self, project_id: Option<ObjectId>) -> Result<Vec<ApiKey>> {
+        let filter = match project_id {
+            Some(project_id) => doc! {"project_id": project_id},
+            None => doc! {},
+        };
+        Ok(self
+            .api_keys_collection()
+            .find(filter)
+            .sort(doc! {"created_at": 1})
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn api_key(&self, key_id: ObjectId) -> Result<Option<ApiKey>> {
+        Ok(self
+            .api_keys_collection()
+            .find_one(doc! {"_id": key_id})
+            .await?)
+    }
+
+    async fn api_key_by_hash(&self, hash: &str) -> Result<Option<ApiKey>> {
+        Ok(self
+            .api_keys_collection()
+            .find_one(doc! {"hash": hash})
+            .await?)
+    }
+
+    async fn insert_api_key(&self, api_key: &ApiKey) -> Result<()> {
+        self.api_keys_collection().insert_one(api_key).await?;
+        Ok(())
+    }
+
+    async fn update_api_key(
+        &self,
+        key_id: ObjectId,
+        fields: &ApiKeyFields,
+    ) -> Result<Option<ApiKey>> {
+        Ok(self
+            .api_keys_collection()
+            .find_one_and_update(doc! {"_id": key_id}, doc! {"$set": to_document(fields)?})
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn users(&self, project_id: Option<ObjectId>) -> Result<Vec<User>> {
+        let filter = match project_id {
+            Some(project_id) => doc! {"project_id": project_id},
+            None => doc! {},
+        };
+        Ok(self
+            .users_collection()
+            .find(filter)
+            .sort(doc! {"created_at": 1})
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn user(&self, user_id: ObjectId) -> Result<Option<User>> {
+        Ok(self
+            .users_collection()
+            .find_one(doc! {"_id": user_id})
+            .await?)
+    }
+
+    async fn insert_user(&self, user: &User) -> Result<()> {
+        self.users_collection().insert_one(user).await?;
+        Ok(())
+    }
+
+    async fn delete_user(&self, user_id: ObjectId) -> Result<bool> {
+        let delete = self
+            .users_collection()
+            .delete_one(doc! {"_id": user_id})
+            .await?;
+        let fields = ApiKeyFields {
+            revoked_at: Some(Utc::now()),
+            ..Default::default()
+        };
+        self.api_keys_collection()
+            .update_many(
+                doc! {"user_id": user_id, "revoked_at": null},
+                doc! {"$set": to_document(&fields)?},
+            )
+            .await?;
+        Ok(delete.deleted_count > 0)
+    }
+
+    async fn projects(&self) -> Result<Vec<Project>> {
+        Ok(self
+            .projects_collection()
+            .find(doc! {})
+            .sort(doc! {"created_at": 1})
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn project(&self, project_id: ObjectId) -> Result<Option<Project>> {
+        Ok(self
+            .projects_collection()
+            .find_one(doc! {"_id": project_id})
+            .await?)
+    }
+
+    async fn insert_project(&self, project: &Project) -> Result<()> {
+        self.projects_collection().insert_one(project).await?;
+        Ok(())
+    }
+
+    async fn update_project(
+        &self,
+        project_id: ObjectId,
+        fields: &ProjectFields,
+    ) -> Result<Option<Project>> {
+        Ok(self
+            .projects_collection()
+            .find_one_and_update(
+                doc! {"_id": project_id},
+                doc! {"$set": to_document(fields)?},
+            )
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn adopt_orphans(&self, project_id: ObjectId) -> Result<()> {
+        for name in PROJECT_COLLECTIONS {
+            self.database
+                .collection::<Document>(name)
+                .update_many(
+                    doc! {"project_id": {"$exists": false}},
+                    doc! {"$set": {"project_id": project_id}},
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn status_pages(&self, project_id: ObjectId) -> Result<Vec<StatusPage>> {
+        Ok(self
+            .status_pages_collection()
+            .find(doc! {"project_id": project_id})
+            .sort(doc! {"created_at": 1})
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn status_page(&self, page_id: ObjectId) -> Result<Option<StatusPage>> {
+        Ok(self
+            .status_pages_collection()
+            .find_one(doc! {"_id": page_id})
+            .await?)
+    }
+
+    async fn status_page_by_slug(&self, slug: &str) -> Result<Option<StatusPage>> {
+        Ok(self
+            .status_pages_collection()
+            .find_one(doc! {"slug": slug})
+            .await?)
+    }
+
+    async fn insert_status_page(&self, page: &StatusPage) -> Result<()> {
+        self.status_pages_collection().insert_one(page).await?;
+        Ok(())
+    }
+
+    async fn update_status_page(
+        &self,
+        page_id: ObjectId,
+        fields: &StatusPageFields,
+    ) -> Result<Option<StatusPage>> {
+        Ok(self
+            .status_pages_collection()
+            .find_one_and_update(doc! {"_id": page_id}, doc! {"$set": to_document(fields)?})
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn delete_status_page(&self, page_id: ObjectId) -> Result<bool> {
+        let delete = self
+            .status_pages_collection()
+            .delete_one(doc! {"_id": page_id})
+            .await?;
+        Ok(delete.deleted_count > 0)
+    }
+}
@@ -0,0 +1,1658 @@
+use std::sync::{Arc, Mutex};
+
+use bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Transaction};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::{
+    merge, to_document, ApiKeyFields, ChannelFields, CheckFields, CheckFilter, DeliveryFilter,
+    HistoryFilter, IncidentFilter, MaintenanceWindowFields, ProjectFields, Result,
+    StatusPageFields, Store,
+};
+use crate::models::{
+    ApiKey, Check, CheckHistory, CheckSortField, FrequencyKind, HeartbeatPing, HeartbeatSignal,
+    Incident, IncidentStatus, IncidentUpdate, MaintenanceWindow, NotificationChannel, Project,
+    Resolution, Rollup, SortOrder, Status, StatusPage, User, WebhookDelivery,
+};
+use crate::pagination::{Cursor, Page, PageRequest};
+use crate::stats::Sample;
+
+/// Every document is kept whole as JSON, next to the fields queries filter and sort on.
+/// Dates are stored as milliseconds since the epoch, the precision cursors keep.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS checks (
+    id TEXT PRIMARY KEY,
+    token TEXT UNIQUE,
+    created_at INTEGER NOT NULL DEFAULT 0,
+    updated_at INTEGER NOT NULL DEFAULT 0,
+    last_run_at INTEGER,
+    doc TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS checks_history (
+    id TEXT PRIMARY KEY,
+    check_id TEXT NOT NULL,
+    status TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    doc TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS checks_history_check ON checks_history (check_id, created_at);
+CREATE INDEX IF NOT EXISTS checks_history_created ON checks_history (created_at);
+CREATE TABLE IF NOT EXISTS checks_rollups (
+    id TEXT PRIMARY KEY,
+    check_id TEXT NOT NULL,
+    resolution TEXT NOT NULL,
+    bucket INTEGER NOT NULL,
+    failures INTEGER NOT NULL,
+    expires_at INTEGER,
+    doc TEXT NOT NULL,
+    UNIQUE (check_id, resolution, bucket)
+);
+CREATE INDEX IF NOT EXISTS checks_rollups_expiry ON checks_rollups (expires_at);
+CREATE TABLE IF NOT EXISTS incidents (
+    id TEXT PRIMARY KEY,
+    check_id TEXT NOT NULL,
+    status TEXT NOT NULL,
+    started_at INTEGER NOT NULL,
+    resolved_at INTEGER,
+    doc TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS incidents_check ON incidents (check_id, started_at);
+CREATE TABLE IF NOT EXISTS notification_channels (
+    id TEXT PRIMARY KEY,
+    doc TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id TEXT PRIMARY KEY,
+    channel_id TEXT NOT NULL,
+    check_id TEXT NOT NULL,
+    status TEXT NOT NULL,
+    next_attempt_at INTEGER,
+    created_at INTEGER NOT NULL,
+    doc TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS webhook_deliveries_status ON webhook_deliveries (status, next_attempt_at);
+CREATE TABLE IF NOT EXISTS heartbeat_pings (
+    id TEXT PRIMARY KEY,
+    check_id TEXT NOT NULL,
+    signal TEXT NOT NULL,
+    received_at INTEGER NOT NULL,
+    doc TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS heartbeat_pings_check ON heartbeat_pings (check_id, received_at);
+CREATE TABLE IF NOT EXISTS maintenance_windows (
+    id TEXT PRIMARY KEY,
+    doc TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS api_keys (
+    id TEXT PRIMARY KEY,
+    hash TEXT NOT NULL UNIQUE,
+    created_at INTEGER NOT NULL,
+    doc TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS users (
+    id TEXT PRIMARY KEY,
+    created_at INTEGER NOT NULL,
+    doc TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS projects (
+    id TEXT PRIMARY KEY,
+    doc TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS status_pages (
+    id TEXT PRIMARY KEY,
+    slug TEXT NOT NULL UNIQUE,
+    created_at INTEGER NOT NULL,
+    doc TEXT NOT NULL
+);
+";
+
+/// Tables whose documents belong to a project
+const PROJECT_TABLES: [&str; 6] = [
+    "checks",
+    "incidents",
+    "notification_channels",
+    "webhook_deliveries",
+    "maintenance_windows",
+    "api_keys",
+];
+
+/// Condition on the project a document belongs to, bound to the id's hex
+const PROJECT_CONDITION: &str = "json_extract(doc, '$.project_id.\"$oid\"') = ?";
+
+/// An SQLite database in a single file, or only in memory, for deployments without a database server
+#[derive(Clone)]
+pub(crate) struct SqliteStore {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStore {
+    pub(crate) fn open(path: &str) -> Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        Self::init(connection)
+    }
+
+    /// Nothing is written to disk, everything is gone once the process stops
+    pub(crate) fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(mut connection: Connection) -> Result<Self> {
+        connection.execute_batch(SCHEMA)?;
+        add_check_dates(&mut connection)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    /// Run `query` in a transaction, off the async runtime
+    async fn run<T, F>(&self, query: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Transaction) -> Result<T> + Send + 'static,
+    {
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection
+                .lock()
+                .map_err(|_| "SQLite connection poisoned".to_string())?;
+            let transaction = connection.transaction()?;
+            let result = query(&transaction)?;
+            transaction.commit()?;
+            Ok(result)
+        })
+        .await?
+    }
+}
+
+/// Checks are listed by these dates, databases created before that get them from the documents
+fn add_check_dates(connection: &mut Connection) -> Result<()> {
+    let exists = connection
+        .prepare("SELECT 1 FROM pragma_table_info('checks') WHERE name = 'last_run_at'")?
+        .exists([])?;
+    if exists {
+        return Ok(());
+    }
+    let transaction = connection.transaction()?;
+    transaction.execute_batch(
+        "ALTER TABLE checks ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0;
+         ALTER TABLE checks ADD COLUMN updated_at INTEGER NOT NULL DEFAULT 0;
+         ALTER TABLE checks ADD COLUMN last_run_at INTEGER;",
+    )?;
+    let checks: Vec<Check> = select(&transaction, "SELECT doc FROM checks", vec![])?;
+    for check in &checks {
+        save_check(&transaction, check, false)?;
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+fn millis(at: DateTime<Utc>) -> i64 {
+    at.timestamp_millis()
+}
+
+/// Name of a unit variant the way serde writes it
+fn name<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+fn json<T: Serialize>(value: &T) -> Result<String> {
+    Ok(serde_json::to_string(value)?)
+}
+
+/// Documents of the first column of every row, in order
+fn select<T: DeserializeOwned>(
+    transaction: &Transaction,
+    sql: &str,
+    values: Vec<Value>,
+) -> Result<Vec<T>> {
+    let mut statement = transaction.prepare(sql)?;
+    let docs = statement
+        .query_map(params_from_iter(values), |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    docs.iter()
+        .map(|doc| Ok(serde_json::from_str(doc)?))
+        .collect()
+}
+
+fn select_one<T: DeserializeOwned>(
+    transaction: &Transaction,
+    sql: &str,
+    values: Vec<Value>,
+) -> Result<Option<T>> {
+    let doc = transaction
+        .query_row(sql, params_from_iter(values), |row| row.get::<_, String>(0))
+        .optional()?;
+    doc.map(|doc| Ok(serde_json::from_str(&doc)?)).transpose()
+}
+
+/// Query made of the statement and its conditions, joined with `AND`
+struct Select {
+    sql: String,
+    conditions: Vec<String>,
+    values: Vec<Value>,
+}
+
+impl Select {
+    fn new(sql: &str) -> Self {
+        Self {
+            sql: sql.to_string(),
+            conditions: vec![],
+            values: vec![],
+        }
+    }
+
+    fn filter(&mut self, condition: &str, values: impl IntoIterator<Item = Value>) {
+        self.conditions.push(condition.to_string());
+        self.values.extend(values);
+    }
+
+    /// Items after the cursor sorted on `field` and then by id. Items missing
+    /// `field` come first in ascending order and last in descending order, as
+    /// SQLite sorts nulls and `Cursor::filter` has them.
+    fn page(&mut self, field: &str, request: &PageRequest) {
+        let (operator, direction) = match request.order {
+            SortOrder::Asc => (">", "ASC"),
+            SortOrder::Desc => ("<", "DESC"),
+        };
+        if let Some(ref cursor) = request.cursor {
+            let id = text(cursor.id.to_hex());
+            match (cursor.at, &request.order) {
+                (Some(at), order) => {
+                    let at = Value::Integer(millis(at));
+                    let missing = match order {
+                        SortOrder::Asc => "",
+                        SortOrder::Desc => &format!(" OR {field} IS NULL"),
+                    };
+                    self.filter(
+                        &format!(
+                            "({field} {operator} ? OR ({field} = ? AND id {operator} ?){missing})"
+                        ),
+                        [at.clone(), at, id],
+                    );
+                }
+                (None, SortOrder::Asc) => self.filter(
+                    &format!("(({field} IS NULL AND id > ?) OR {field} IS NOT NULL)"),
+                    [id],
+                ),
+                (None, SortOrder::Desc) => {
+                    self.filter(&format!("{field} IS NULL AND id < ?"), [id])
+                }
+            }
+        }
+        self.finish(&format!(
+            "ORDER BY {field} {direction}, id {direction} LIMIT {}",
+            request.limit + 1
+        ));
+    }
+
+    fn finish(&mut self, suffix: &str) {
+        if !self.conditions.is_empty() {
+            self.sql.push_str(" WHERE ");
+            self.sql.push_str(&self.conditions.join(" AND "));
+        }
+        self.sql.push(' ');
+        self.sql.push_str(suffix);
+    }
+
+    fn fetch<T: DeserializeOwned>(self, transaction: &Transaction) -> Result<Vec<T>> {
+        select(transaction, &self.sql, self.values)
+    }
+}
+
+fn text(value: impl ToString) -> Value {
+    Value::Text(value.to_string())
+}
+
+fn history_select(sql: &str, filter: &HistoryFilter, field: &str) -> Select {
+    let mut select = Select::new(sql);
+    select.filter("check_id = ?", [text(filter.check_id.to_hex())]);
+    if let Some(from) = filter.from {
+        select.filter(&format!("{field} >= ?"), [Value::Integer(millis(from))]);
+    }
+    if let Some(to) = filter.to {
+        select.filter(&format!("{field} < ?"), [Value::Integer(millis(to))]);
+    }
+    select
+}
+
+/// Conditions of the filter, as MongoDB matches them
+fn check_select(filter: &CheckFilter) -> Select {
+    let mut select = Select::new("SELECT doc FROM checks");
+    if let Some(project_id) = filter.project_id {
+        select.filter(PROJECT_CONDITION, [text(project_id.to_hex())]);
+    }
+    if let Some(ref method) = filter.method {
+        select.filter("json_extract(doc, '$.method') = ?", [text(method)]);
+    }
+    match filter.frequency {
+        Some(FrequencyKind::Interval) => {
+            select.filter("json_type(doc, '$.frequency.seconds') IS NOT NULL", [])
+        }
+        Some(FrequencyKind::Cron) => {
+            select.filter("json_type(doc, '$.frequency.cron') IS NOT NULL", [])
+        }
+        Some(ref preset) => {
+            select.filter("json_extract(doc, '$.frequency') = ?", [text(name(preset))])
+        }
+        None => (),
+    }
+    if let Some(ref current_state) = filter.current_state {
+        select.filter(
+            "json_extract(doc, '$.current_state') = ?",
+            [text(name(current_state))],
+        );
+    }
+    for tag in &filter.tags {
+        select.filter(
+            "EXISTS (SELECT 1 FROM json_each(doc, '$.tags') WHERE value = ?)",
+            [text(tag)],
+        );
+    }
+    if let Some(ref search) = filter.search {
+        // SQLite only lowers ASCII letters, other letters must match their case
+        let search = text(search.to_lowercase());
+        select.filter(
+            "(instr(lower(json_extract(doc, '$.url')), ?) > 0 \
+             OR instr(lower(json_extract(doc, '$.name')), ?) > 0)",
+            [search.clone(), search],
+        );
+    }
+    select
+}
+
+fn save_check(transaction: &Transaction, check: &Check, insert: bool) -> Result<()> {
+    let verb = if insert { "INSERT" } else { "REPLACE" };
+    transaction.execute(
+        &format!(
+            "{verb} INTO checks (id, token, created_at, updated_at, last_run_at, doc) \
+             VALUES (?, ?, ?, ?, ?, ?)"
+        ),
+        params![
+            check._id.to_hex(),
+            check.heartbeat.as_ref().map(|heartbeat| &heartbeat.token),
+            millis(check.created_at),
+            millis(check.updated_at),
+            check.last_run_at.map(millis),
+            json(check)?,
+        ],
+    )?;
+    Ok(())
+}
+
+fn save_api_key(transaction: &Transaction, api_key: &ApiKey, insert: bool) -> Result<()> {
+    let verb = if insert { "INSERT" } else { "REPLACE" };
+    transaction.execute(
+        &format!("{verb} INTO api_keys (id, hash, created_at, doc) VALUES (?, ?, ?, ?)"),
+        params![
+            api_key._id.to_hex(),
+            api_key.hash,
+            millis(api_key.created_at),
+            json(api_key)?,
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_user(transaction: &Transaction, user: &User) -> Result<()> {
+    transaction.execute(
+        "INSERT INTO users (id, created_at, doc) VALUES (?, ?, ?)",
+        params![user._id.to_hex(), millis(user.created_at), json(user)?],
+    )?;
+    Ok(())
+}
+
+fn save_status_page(transaction: &Transaction, page: &StatusPage, insert: bool) -> Result<()> {
+    let verb = if insert { "INSERT" } else { "REPLACE" };
+    transaction.execute(
+        &format!("{verb} INTO status_pages (id, slug, created_at, doc) VALUES (?, ?, ?, ?)"),
+        params![
+            page._id.to_hex(),
+            page.slug,
+            millis(page.created_at),
+            json(page)?,
+        ],
+    )?;
+    Ok(())
+}
+
+fn save_incident(transaction: &Transaction, incident: &Incident, insert: bool) -> Result<()> {
+    let verb = if insert { "INSERT" } else { "REPLACE" };
+    transaction.execute(
+        &format!(
+            "{verb} INTO incidents (id, check_id, status, started_at, resolved_at, doc) \
+             VALUES (?, ?, ?, ?, ?, ?)"
+        ),
+        params![
+            incident._id.to_hex(),
+            incident.check_id.to_hex(),
+            name(&incident.status),
+            millis(incident.started_at),
+            incident.resolved_at.map(millis),
+            json(incident)?,
+        ],
+    )?;
+    Ok(())
+}
+
+fn save_delivery(
+    transaction: &Transaction,
+    delivery: &WebhookDelivery,
+    insert: bool,
+) -> Result<()> {
+    let verb = if insert { "INSERT" } else { "REPLACE" };
+    transaction.execute(
+        &format!(
+            "{verb} INTO webhook_deliveries \
+             (id, channel_id, check_id, status, next_attempt_at, created_at, doc) \
+             VALUES (?, ?, ?, ?, ?, ?, ?)"
+        ),
+        params![
+            delivery._id.to_hex(),
+            delivery.channel_id.to_hex(),
+            delivery.check_id.to_hex(),
+            name(&delivery.status),
+            delivery.next_attempt_at.map(millis),
+            millis(delivery.created_at),
+            json(delivery)?,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Insert or replace a document kept without any other column
+fn save_doc<T: Serialize>(
+    transaction: &Transaction,
+    table: &str,
+    id: ObjectId,
+    value: &T,
+    insert: bool,
+) -> Result<()> {
+    let verb = if insert { "INSERT" } else { "REPLACE" };
+    transaction.execute(
+        &format!("{verb} INTO {table} (id, doc) VALUES (?, ?)"),
+        params![id.to_hex(), json(value)?],
+    )?;
+    Ok(())
+}
+
+fn find_by_id<T: DeserializeOwned>(
+    transaction: &Transaction,
+    table: &str,
+    id: ObjectId,
+) -> Result<Option<T>> {
+    select_one(
+        transaction,
+        &format!("SELECT doc FROM {table} WHERE id = ?"),
+        vec![text(id.to_hex())],
+    )
+}
+
+fn delete_by_id(transaction: &Transaction, table: &str, id: ObjectId) -> Result<bool> {
+    let deleted = transaction.execute(
+        &format!("DELETE FROM {table} WHERE id = ?"),
+        params![id.to_hex()],
+    )?;
+    Ok(deleted > 0)
+}
+
+impl Store for SqliteStore {
+    async fn checks(&self) -> Result<Vec<Check>> {
+        self.run(|transaction| select(transaction, "SELECT doc FROM checks", vec![]))
+            .await
+    }
+
+    async fn find_checks(
+        &self,
+        filter: &CheckFilter,
+        sort: &CheckSortField,
+        request: &PageRequest,
+    ) -> Result<Page<Check>> {
+        let mut select = check_select(filter);
+        select.page(sort.field(), request);
+        let limit = request.limit;
+        let checks = self
+            .run(move |transaction| select.fetch(transaction))
+            .await?;
+        Ok(Page::new(checks, limit, |check: &Check| {
+            Cursor::new(sort.value(check), check._id)
+        }))
+    }
+
+    async fn check(&self, check_id: ObjectId) -> Result<Option<Check>> {
+        self.run(move |transaction| find_by_id(transaction, "checks", check_id))
+            .await
+    }
+
+    async fn check_by_token(&self, token: &str) -> Result<Option<Check>> {
+        let token = token.to_string();
+        self.run(move |transaction| {
+            select_one(
+                transaction,
+                "SELECT doc FROM checks WHERE token = ?",
+                vec![Value::Text(token)],
+            )
+        })
+        .await
+    }
+
+    async fn existing_checks(
+        &self,
+        project_id: ObjectId,
+        check_ids: &[ObjectId],
+    ) -> Result<Vec<ObjectId>> {
+        let check_ids = check_ids.to_vec();
+        self.run(move |transaction| {
+            let mut statement = transaction.prepare(&format!(
+                "SELECT 1 FROM checks WHERE id = ? AND {PROJECT_CONDITION}"
+            ))?;
+            let mut existing = vec![];
+            for check_id in check_ids {
+                if statement.exists(params![check_id.to_hex(), project_id.to_hex()])? {
+                    existing.push(check_id);
+                }
+            }
+            Ok(existing)
+        })
+        .await
+    }
+
+    async fn count_checks(&self, project_id: ObjectId) -> Result<u64> {
+        self.run(move |transaction| {
+            let count: i64 = transaction.query_row(
+                &format!("SELECT COUNT(*) FROM checks WHERE {PROJECT_CONDITION}"),
+                params![project_id.to_hex()],
+                |row| row.get(0),
+            )?;
+            Ok(count as u64)
+        })
+        .await
+    }
+
+    async fn insert_check(&self, check: &Check) -> Result<()> {
+        let check = check.clone();
+        self.run(move |transaction| save_check(transaction, &check, true))
+            .await
+    }
+
+    async fn update_check(
+        &self,
+        check_id: ObjectId,
+        fields: &CheckFields,
+    ) -> Result<Option<Check>> {
+        let fields = to_document(fields)?;
+        self.run(move |transaction| {
+            let Some(check) = find_by_id::<Check>(transaction, "checks", check_id)? else {
+                return Ok(None);
+            };
+            let check = merge(&check, &fields)?;
+            save_check(transaction, &check, false)?;
+            Ok(Some(check))
+        })
+        .await
+    }
+
+    async fn delete_check(&self, check_id: ObjectId) -> Result<bool> {
+        self.run(move |transaction| delete_by_id(transaction, "checks", check_id))
+            .await
+    }
+
+    async fn remove_channel_from_checks(&self, channel_id: ObjectId) -> Result<Vec<Check>> {
+        self.run(move |transaction| {
+            let checks: Vec<Check> = select(transaction, "SELECT doc FROM checks", vec![])?;
+            let mut affected = vec![];
+            for mut check in checks {
+                if check.channels.contains(&channel_id) {
+                    check.channels.retain(|id| id != &channel_id);
+                    save_check(transaction, &check, false)?;
+                    affected.push(check);
+                }
+            }
+            Ok(affected)
+        })
+        .await
+    }
+
+    async fn insert_history(&self, entry: &CheckHistory) -> Result<()> {
+        let entry = entry.clone();
+        self.run(move |transaction| {
+            transaction.execute(
+                "INSERT INTO checks_history (id, check_id, status, created_at, doc) \
+                 VALUES (?, ?, ?, ?, ?)",
+                params![
+                    entry._id.to_hex(),
+                    entry.check_id.to_hex(),
+                    name(&entry.status),
+                    millis(entry.created_at),
+                    json(&entry)?,
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn find_history(
+        &self,
+        filter: &HistoryFilter,
+        request: &PageRequest,
+    ) -> Result<Page<CheckHistory>> {
+        let mut select = history_select("SELECT doc FROM checks_history", filter, "created_at");
+        if let Some(ref status) = filter.status {
+            select.filter("status = ?", [text(name(status))]);
+        }
+        select.page("created_at", request);
+        let limit = request.limit;
+        let entries = self
+            .run(move |transaction| select.fetch(transaction))
+            .await?;
+        Ok(Page::new(entries, limit, |entry: &CheckHistory| {
+            Cursor::new(Some(entry.created_at), entry._id)
+        }))
+    }
+
+    async fn samples(
+        &self,
+        check_id: ObjectId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Sample>> {
+        let mut select = Select::new("SELECT doc FROM checks_history");
+        select.filter(
+            "created_at >= ? AND created_at < ?",
+            [Value::Integer(millis(from)), Value::Integer(millis(to))],
+        );
+        select.filter("check_id = ?", [text(check_id.to_hex())]);
+        select.finish("");
+        self.run(move |transaction| select.fetch(transaction)).await
+    }
+
+    async fn oldest_history(&self) -> Result<Option<DateTime<Utc>>> {
+        let oldest = self
+            .run(|transaction| {
+                Ok(transaction.query_row(
+                    "SELECT MIN(created_at) FROM checks_history",
+                    [],
+                    |row| row.get::<_, Option<i64>>(0),
+                )?)
+            })
+            .await?;
+        Ok(oldest.and_then(DateTime::from_timestamp_millis))
+    }
+
+    async fn delete_history(&self, check_id: ObjectId) -> Result<u64> {
+        self.run(move |transaction| {
+            transaction.execute(
+                "DELETE FROM checks_rollups WHERE check_id = ?",
+                params![check_id.to_hex()],
+            )?;
+            let deleted = transaction.execute(
+                "DELETE FROM checks_history WHERE check_id = ?",
+                params![check_id.to_hex()],
+            )?;
+            Ok(deleted as u64)
+        })
+        .await
+    }
+
+    async fn find_rollups(
+        &self,
+        filter: &HistoryFilter,
+        resolution: Resolution,
+        request: &PageRequest,
+    ) -> Result<Page<Rollup>> {
+        let mut select = history_select("SELECT doc FROM checks_rollups", filter, "bucket");
+        select.filter("resolution = ?", [text(name(&resolution))]);
+        match filter.status {
+            Some(Status::Error) => select.filter("failures > 0", []),
+            Some(_) => select.filter("failures = 0", []),
+            None => {}
+        }
+        select.page("bucket", request);
+        let limit = request.limit;
+        let rollups = self
+            .run(move |transaction| select.fetch(transaction))
+            .await?;
+        Ok(Page::new(rollups, limit, |rollup: &Rollup| {
+            Cursor::new(Some(rollup.bucket), rollup._id)
+        }))
+    }
+
+    async fn latest_rollup(&self, resolution: Resolution) -> Result<Option<Rollup>> {
+        self.run(move |transaction| {
+            select_one(
+                transaction,
+                "SELECT doc FROM checks_rollups WHERE resolution = ? \
+                 ORDER BY bucket DESC LIMIT 1",
+                vec![text(name(&resolution))],
+            )
+        })
+        .await
+    }
+
+    async fn save_rollup(&self, rollup: &Rollup) -> Result<()> {
+        let rollup = rollup.clone();
+        self.run(move |transaction| {
+            transaction.execute(
+                "DELETE FROM checks_rollups WHERE check_id = ? AND resolution = ? AND bucket = ?",
+                params![
+                    rollup.check_id.to_hex(),
+                    name(&rollup.resolution),
+                    millis(rollup.bucket),
+                ],
+            )?;
+            transaction.execute(
+                "INSERT INTO checks_rollups \
+                 (id, check_id, resolution, bucket, failures, expires_at, doc) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    rollup._id.to_hex(),
+                    rollup.check_id.to_hex(),
+                    name(&rollup.resolution),
+                    millis(rollup.bucket),
+                    rollup.failures as i64,
+                    rollup.expires_at.map(millis),
+                    json(&rollup)?,
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn remove_expired(&self, history_before: Option<DateTime<Utc>>) -> Result<()> {
+        let now = Utc::now();
+        self.run(move |transaction| {
+            if let Some(history_before) = history_before {
+                transaction.execute(
+                    "DELETE FROM checks_history WHERE created_at < ?",
+                    params![millis(history_before)],
+                )?;
+            }
+            transaction.execute(
+                "DELETE FROM checks_rollups WHERE expires_at <= ?",
+                params![millis(now)],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn insert_incident(&self, incident: &Incident) -> Result<()> {
+        let incident = incident.clone();
+        self.run(move |transaction| save_incident(transaction, &incident, true))
+            .await
+    }
+
+    async fn save_incident(&self, incident: &Incident) -> Result<()> {
+        let incident = incident.clone();
+        self.run(move |transaction| save_incident(transaction, &incident, false))
+            .await
+    }
+
+    async fn incident(&self, incident_id: ObjectId) -> Result<Option<Incident>> {
+        self.run(move |transaction| find_by_id(transaction, "incidents", incident_id))
+            .await
+    }
+
+    async fn open_incident(&self, check_id: ObjectId) -> Result<Option<Incident>> {
+        self.run(move |transaction| {
+            select_one(
+                transaction,
+                "SELECT doc FROM incidents WHERE check_id = ? AND status = ? LIMIT 1",
+                vec![text(check_id.to_hex()), text(name(&IncidentStatus::Open))],
+            )
+        })
+        .await
+    }
+
+    async fn add_incident_update(
+        &self,
+        incident_id: ObjectId,
+        update: &IncidentUpdate,
+    ) -> Result<Option<Incident>> {
+        let update = update.clone();
+        self.run(move |transaction| {
+            let Some(mut incident) = find_by_id::<Incident>(transaction, "incidents", incident_id)?
+            else {
+                return Ok(None);
+            };
+            incident.updates.push(update);
+            save_incident(transaction, &incident, false)?;
+            Ok(Some(incident))
+        })
+        .await
+    }
+
+    async fn find_incidents(&self, filter: &IncidentFilter) -> Result<Vec<Incident>> {
+        let mut select = Select::new("SELECT doc FROM incidents");
+        if let Some(project_id) = filter.project_id {
+            select.filter(PROJECT_CONDITION, [text(project_id.to_hex())]);
+        }
+        if let Some(check_id) = filter.check_id {
+            select.filter("check_id = ?", [text(check_id.to_hex())]);
+        }
+        if let Some(ref status) = filter.status {
+            select.filter("status = ?", [text(name(status))]);
+        }
+        if let Some((from, to)) = filter.during {
+            select.filter(
+                "started_at < ? AND (resolved_at IS NULL OR resolved_at > ?)",
+                [Value::Integer(millis(to)), Value::Integer(millis(from))],
+            );
+        }
+        select.finish("ORDER BY started_at DESC");
+        self.run(move |transaction| select.fetch(transaction)).await
+    }
+
+    async fn channels(&self, project_id: ObjectId) -> Result<Vec<NotificationChannel>> {
+        self.run(move |transaction| {
+            select(
+                transaction,
+                &format!("SELECT doc FROM notification_channels WHERE {PROJECT_CONDITION}"),
+                vec![text(project_id.to_hex())],
+            )
+        })
+        .await
+    }
+
+    async fn channel(&self, channel_id: ObjectId) -> Result<Option<NotificationChannel>> {
+        self.run(move |transaction| find_by_id(transaction, "notification_channels", channel_id))
+            .await
+    }
+
+    async fn channels_by_id(&self, channel_ids: &[ObjectId]) -> Result<Vec<NotificationChannel>> {
+        let channel_ids = channel_ids.to_vec();
+        self.run(move |transaction| {
+            let mut channels = vec![];
+            for channel_id in channel_ids {
+                channels.extend(find_by_id(
+                    transaction,
+                    "notification_channels",
+                    channel_id,
+                )?);
+            }
+            Ok(channels)
+        })
+        .await
+    }
+
+    async fn insert_channel(&self, channel: &NotificationChannel) -> Result<()> {
+        let channel = channel.clone();
+        self.run(move |transaction| {
+            save_doc(
+                transaction,
+                "notification_channels",
+                channel._id,
+                &channel,
+                true,
+            )
+        })
+        .await
+    }
+
+    async fn update_channel(
+        &self,
+        channel_id: ObjectId,
+        fields: &ChannelFields,
+    ) -> Result<Option<NotificationChannel>> {
+        let fields = to_document(fields)?;
+        self.run(move |transaction| {
+            let Some(channel) = find_by_id::<NotificationChannel>(
+                transaction,
+                "notification_channels",
+                channel_id,
+            )?
+            else {
+                return Ok(None);
+            };
+            let channel = merge(&channel, &fields)?;
+            save_doc(
+                transaction,
+                "notification_channels",
+                channel_id,
+                &channel,
+                false,
+            )?;
+            Ok(Some(channel))
+        })
+        .await
+    }
+
+    async fn delete_channel(&self, channel_id: ObjectId) -> Result<bool> {
+        self.run(move |transaction| delete_by_id(transaction, "notification_channels", channel_id))
+            .await
+    }
+
+    async fn insert_delivery(&self, delivery: &WebhookDelivery) -> Result<()> {
+        let delivery = delivery.clone();
+        self.run(move |transaction| save_delivery(transaction, &delivery, true))
+            .await
+    }
+
+    async fn save_delivery(&self, delivery: &WebhookDelivery) -> Result<()> {
+        let delivery = delivery.clone();
+        self.run(move |transaction| save_delivery(transaction, &delivery, false))
+            .await
+    }
+
+    async fn delivery(&self, delivery_id: ObjectId) -> Result<Option<WebhookDelivery>> {
+        self.run(move |transaction| find_by_id(transaction, "webhook_deliveries", delivery_id))
+            .await
+    }
+
+    async fn find_deliveries(
+        &self,
+        filter: &DeliveryFilter,
+        limit: u32,
+    ) -> Result<Vec<WebhookDelivery>> {
+        let mut select = Select::new("SELECT doc FROM webhook_deliveries");
+        if let Some(project_id) = filter.project_id {
+            select.filter(PROJECT_CONDITION, [text(project_id.to_hex())]);
+        }
+        if let Some(channel_id) = filter.channel_id {
+            select.filter("channel_id = ?", [text(channel_id.to_hex())]);
+        }
+        if let Some(check_id) = filter.check_id {
+            select.filter("check_id = ?", [text(check_id.to_hex())]);
+        }
+        if let Some(ref status) = filter.status {
+            select.filter("status = ?", [text(name(status))]);
+        }
+        select.finish(&format!("ORDER BY created_at DESC LIMIT {limit}"));
+        self.run(move |transaction| select.fetch(transaction)).await
+    }
+
+    async fn due_deliveries(&self, now: DateTime<Utc>) -> Result<Vec<WebhookDelivery>> {
+        self.run(move |transaction| {
+            select(
+                transaction,
+                "SELECT doc FROM webhook_deliveries WHERE status = 'Pending' AND next_attempt_at <= ?",
+                vec![Value::Integer(millis(now))],
+            )
+        })
+        .await
+    }
+
+    async fn insert_ping(&self, ping: &HeartbeatPing) -> Result<()> {
+        let ping = ping.clone();
+        self.run(move |transaction| {
+            transaction.execute(
+                "INSERT INTO heartbeat_pings (id, check_id, signal, received_at, doc) \
+                 VALUES (?, ?, ?, ?, ?)",
+                params![
+                    ping._id.to_hex(),
+                    ping.check_id.to_hex(),
+                    name(&ping.signal),
+                    millis(ping.received_at),
+                    json(&ping)?,
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn find_pings(
+        &self,
+        check_id: ObjectId,
+        signal: Option<HeartbeatSignal>,
+        limit: u32,
+    ) -> Result<Vec<HeartbeatPing>> {
+        let mut select = Select::new("SELECT doc FROM heartbeat_pings");
+        select.filter("check_id = ?", [text(check_id.to_hex())]);
+        if let Some(signal) = signal {
+            select.filter("signal = ?", [text(name(&signal))]);
+        }
+        select.finish(&format!("ORDER BY received_at DESC LIMIT {limit}"));
+        self.run(move |transaction| select.fetch(transaction)).await
+    }
+
+    async fn maintenance_windows(&self, project_id: ObjectId) -> Result<Vec<MaintenanceWindow>> {
+        self.run(move |transaction| {
+            select(
+                transaction,
+                &format!("SELECT doc FROM maintenance_windows WHERE {PROJECT_CONDITION}"),
+                vec![text(project_id.to_hex())],
+            )
+        })
+        .await
+    }
+
+    async fn maintenance_windows_for(&self, check: &Check) -> Result<Vec<MaintenanceWindow>> {
+        let windows = self.maintenance_windows(check.project_id).await?;
+        Ok(windows
+            .into_iter()
+            .filter(|window| {
+                window.checks.contains(&check._id)
+                    || window.tags.iter().any(|tag| check.tags.contains(tag))
+            })
+            .collect())
+    }
+
+    async fn maintenance_window(&self, window_id: ObjectId) -> Result<Option<MaintenanceWindow>> {
+        self.run(move |transaction| find_by_id(transaction, "maintenance_windows", window_id))
+            .await
+    }
+
+    async fn insert_maintenance_window(&self, window: &MaintenanceWindow) -> Result<()> {
+        let window = window.clone();
+        self.run(move |transaction| {
+            save_doc(
+                transaction,
+                "maintenance_windows",
+                window._id,
+                &window,
+                true,
+            )
+        })
+        .await
+    }
+
+    async fn update_maintenance_window(
+        &self,
+        window_id: ObjectId,
+        fields: &MaintenanceWindowFields,
+    ) -> Result<Option<MaintenanceWindow>> {
+        let fields = to_document(fields)?;
+        self.run(move |transaction| {
+            let Some(window) =
+                find_by_id::<MaintenanceWindow>(transaction, "maintenance_windows", window_id)?
+            else {
+                return Ok(None);
+            };
+            let window = merge(&window, &fields)?;
+            save_doc(
+                transaction,
+                "maintenance_windows",
+                window_id,
+                &window,
+                false,
+            )?;
+            Ok(Some(window))
+        })
+        .await
+    }
+
+    async fn delete_maintenance_window(&self, window_id: ObjectId) -> Result<bool> {
+        self.run(move |transaction| delete_by_id(transaction, "maintenance_windows", window_id))
+            .await
+    }
+
+    async fn api_keys(&self, project_id: Option<ObjectId>) -> Result<Vec<ApiKey>> {
+        let mut select = Select::new("SELECT doc FROM api_keys");
+        if let Some(project_id) = project_id {
+            select.filter(PROJECT_CONDITION, [text(project_id.to_hex())]);
+        }
+        select.finish("ORDER BY created_at, id");
+        self.run(move |transaction| select.fetch(transaction)).await
+    }
+
+    async fn api_key(&self, key_id: ObjectId) -> Result<Option<ApiKey>> {
+        self.run(move |transaction| find_by_id(transaction, "api_keys", key_id))
+            .await
+    }
+
+    async fn api_key_by_hash(&self, hash: &str) -> Result<Option<ApiKey>> {
+        let hash = hash.to_string();
+        self.run(move |transaction| {
+            select_one(
+                transaction,
+                "SELECT doc FROM api_keys WHERE hash = ?",
+                vec![text(hash)],
+            )
+        })
+        .await
+    }
+
+    async fn insert_api_key(&self, api_key: &ApiKey) -> Result<()> {
+        let api_key = api_key.clone();
+        self.run(move |transaction| save_api_key(transaction, &api_key, true))
+            .await
+    }
+
+    async fn update_api_key(
+        &self,
+        key_id: ObjectId,
+        fields: &ApiKeyFields,
+    ) -> Result<Option<ApiKey>> {
+        let fields = to_document(fields)?;
+        self.run(move |transaction| {
+            let Some(api_key) = find_by_id::<ApiKey>(transaction, "api_keys", key_id)? else {
+                return Ok(None);
+            };
+            let api_key = merge(&api_key, &fields)?;
+            save_api_key(transaction, &api_key, false)?;
+            Ok(Some(api_key))
+        })
+        .await
+    }
+
+    async fn users(&self, project_id: Option<ObjectId>) -> Result<Vec<User>> {
+        let mut select = Select::new("SELECT doc FROM users");
+        if let Some(project_id) = project_id {
+            select.filter(PROJECT_CONDITION, [text(project_id.to_hex())]);
+        }
+        select.finish("ORDER BY created_at, id");
+        self.run(move |transaction| select.fetch(transaction)).await
+    }
+
+    async fn user(&self, user_id: ObjectId) -> Result<Option<User>> {
+        self.run(move |transaction| find_by_id(transaction, "users", user_id))
+            .await
+    }
+
+    async fn insert_user(&self, user: &User) -> Result<()> {
+        let user = user.clone();
+        self.run(move |transaction| insert_user(transaction, &user))
+            .await
+    }
+
+    async fn delete_user(&self, user_id: ObjectId) -> Result<bool> {
+        let fields = to_document(&ApiKeyFields {
+            revoked_at: Some(Utc::now()),
+            ..Default::default()
+        })?;
+        self.run(move |transaction| {
+            let deleted = delete_by_id(transaction, "users", user_id)?;
+            let api_keys: Vec<ApiKey> = select(
+                transaction,
+                "SELECT doc FROM api_keys WHERE json_extract(doc, '$.user_id.\"$oid\"') = ? \
+                 AND json_extract(doc, '$.revoked_at') IS NULL",
+                vec![text(user_id.to_hex())],
+            )?;
+            for api_key in &api_keys {
+                save_api_key(transaction, &merge(api_key, &fields)?, false)?;
+            }
+            Ok(deleted)
+        })
+        .await
+    }
+
+    async fn projects(&self) -> Result<Vec<Project>> {
+        let mut projects: Vec<Project> = self
+            .run(|transaction| select(transaction, "SELECT doc FROM projects", vec![]))
+            .await?;
+        projects.sort_by_key(|project| project.created_at);
+        Ok(projects)
+    }
+
+    async fn project(&self, project_id: ObjectId) -> Result<Option<Project>> {
+        self.run(move |transaction| find_by_id(transaction, "projects", project_id))
+            .await
+    }
+
+    async fn insert_project(&self, project: &Project) -> Result<()> {
+        let project = project.clone();
+        self.run(move |transaction| save_doc(transaction, "projects", project._id, &project, true))
+            .await
+    }
+
+    async fn update_project(
+        &self,
+        project_id: ObjectId,
+        fields: &ProjectFields,
+    ) -> Result<Option<Project>> {
+        let fields = to_document(fields)?;
+        self.run(move |transaction| {
+            let Some(project) = find_by_id::<Project>(transaction, "projects", project_id)? else {
+                return Ok(None);
+            };
+            let project = merge(&project, &fields)?;
+            save_doc(transaction, "projects", project_id, &project, false)?;
+            Ok(Some(project))
+        })
+        .await
+    }
+
+    async fn adopt_orphans(&self, project_id: ObjectId) -> Result<()> {
+        let project_id = json(&project_id)?;
+        self.run(move |transaction| {
+            for table in PROJECT_TABLES {
+                transaction.execute(
+                    &format!(
+                        "UPDATE {table} SET doc = json_set(doc, '$.project_id', json(?)) \
+                         WHERE json_extract(doc, '$.project_id') IS NULL"
+                    ),
+                    params![project_id],
+                )?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn status_pages(&self, project_id: ObjectId) -> Result<Vec<StatusPage>> {
+        self.run(move |transaction| {
+            select(
+                transaction,
+                &format!(
+                    "SELECT doc FROM status_pages WHERE {PROJECT_CONDITION} ORDER BY created_at, id"
+                ),
+                vec![text(project_id.to_hex())],
+            )
+        })
+        .await
+    }
+
+    async fn status_page(&self, page_id: ObjectId) -> Result<Option<StatusPage>> {
+        self.run(move |transaction| find_by_id(transaction, "status_pages", page_id))
+            .await
+    }
+
+    async fn status_page_by_slug(&self, slug: &str) -> Result<Option<StatusPage>> {
+        let slug = slug.to_string();
+        self.run(move |transaction| {
+            select_one(
+                transaction,
+                "SELECT doc FROM status_pages WHERE slug = ?",
+                vec![text(slug)],
+            )
+        })
+        .await
+    }
+
+    async fn insert_status_page(&self, page: &StatusPage) -> Result<()> {
+        let page = page.clone();
+        self.run(move |transaction| save_status_page(transaction, &page, true))
+            .await
+    }
+
+    async fn update_status_page(
+        &self,
+        page_id: ObjectId,
+        fields: &StatusPageFields,
+    ) -> Result<Option<StatusPage>> {
+        let fields = to_document(fields)?;
+        self.run(move |transaction| {
+            let Some(page) = find_by_id::<StatusPage>(transaction, "status_pages", page_id)? else {
+                return Ok(None);
+            };
+            let page = merge(&page, &fields)?;
+            save_status_page(transaction, &page, false)?;
+            Ok(Some(page))
+        })
+        .await
+    }
+
+    async fn delete_status_page(&self, page_id: ObjectId) -> Result<bool> {
+        self.run(move |transaction| delete_by_id(transaction, "status_pages", page_id))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::oid::ObjectId;
+    use chrono::{DateTime, TimeZone, Utc};
+    use rusqlite::Connection;
+    use serde_json::{json, Value};
+
+    use super::SqliteStore;
+    use crate::models::{
+        ApiKey, ApiKeyScope, Check, CheckHistory, CheckSortField, CheckState, FrequencyKind,
+        HTTPMethod, MaintenanceMode, MaintenanceWindow, NewUser, SortOrder, Status, User,
+    };
+    use crate::pagination::{Cursor, PageRequest};
+    use crate::store::{CheckFields, CheckFilter, HistoryFilter, MaintenanceWindowFields, Store};
+
+    fn store() -> SqliteStore {
+        SqliteStore::in_memory().unwrap()
+    }
+
+    fn new_check(project_id: ObjectId, fields: Value) -> Check {
+        let mut new_check = json!({"url": "https://example.com", "frequency": {"seconds": 60}});
+        new_check
+            .as_object_mut()
+            .unwrap()
+            .extend(fields.as_object().unwrap().clone());
+        Check::from_new(serde_json::from_value(new_check).unwrap(), project_id)
+    }
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000 + seconds, 0).unwrap()
+    }
+
+    /// Ids of every page, read with cursors until the last one
+    async fn page_through(
+        store: &SqliteStore,
+        filter: &CheckFilter,
+        sort: &CheckSortField,
+        order: SortOrder,
+    ) -> Vec<ObjectId> {
+        let mut ids = vec![];
+        let mut cursor = None;
+        loop {
+            let request = PageRequest {
+                order: order.clone(),
+                limit: 2,
+                cursor: cursor.map(|cursor: String| Cursor::decode(&cursor).unwrap()),
+            };
+            let page = store.find_checks(filter, sort, &request).await.unwrap();
+            ids.extend(page.items.iter().map(|check| check._id));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                return ids;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn updates_set_only_the_fields_given() {
+        let store = store();
+        let check = new_check(
+            ObjectId::new(),
+            json!({"name": "Home", "tags": ["web"], "timeout_ms": 500}),
+        );
+        store.insert_check(&check).await.unwrap();
+
+        let fields = CheckFields {
+            name: Some(None),
+            method: Some(HTTPMethod::POST),
+            last_run_at: Some(at(10)),
+            ..Default::default()
+        };
+        let updated = store
+            .update_check(check._id, &fields)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.name, None);
+        assert!(updated.method == HTTPMethod::POST);
+        assert_eq!(updated.last_run_at, Some(at(10)));
+        assert_eq!(updated.tags, vec!["web".to_string()]);
+        assert_eq!(updated.timeout_ms, Some(500));
+
+        let read = store.check(check._id).await.unwrap().unwrap();
+        assert_eq!(read.last_run_at, Some(at(10)));
+        assert!(store
+            .update_check(ObjectId::new(), &fields)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn updates_clear_dates() {
+        let store = store();
+        let window = MaintenanceWindow {
+            _id: ObjectId::new(),
+            project_id: ObjectId::new(),
+            name: "Deploys".to_string(),
+            starts_at: at(0),
+            ends_at: Some(at(3600)),
+            recurrence: None,
+            duration_secs: None,
+            checks: vec![],
+            tags: vec!["web".to_string()],
+            mode: MaintenanceMode::Record,
+            created_at: at(0),
+            updated_at: at(0),
+        };
+        store.insert_maintenance_window(&window).await.unwrap();
+
+        let fields = MaintenanceWindowFields {
+            ends_at: Some(None),
+            updated_at: Some(at(60)),
+            ..Default::default()
+        };
+        let updated = store
+            .update_maintenance_window(window._id, &fields)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.ends_at, None);
+        assert_eq!(updated.starts_at, at(0));
+        assert_eq!(updated.updated_at, at(60));
+    }
+
+    #[tokio::test]
+    async fn finds_checks_matching_every_filter() {
+        let store = store();
+        let project_id = ObjectId::new();
+        let checks = [
+            new_check(
+                project_id,
+                json!({"name": "Home Page", "tags": ["web", "prod"]}),
+            ),
+            new_check(
+                project_id,
+                json!({"url": "https://api.example.com", "method": "POST", "tags": ["prod"]}),
+            ),
+            new_check(project_id, json!({"frequency": "Hourly", "tags": ["web"]})),
+            new_check(project_id, json!({"frequency": {"cron": "0 * * * *"}})),
+            new_check(ObjectId::new(), json!({"name": "Home"})),
+        ];
+        for check in &checks {
+            store.insert_check(check).await.unwrap();
+        }
+        store
+            .update_check(
+                checks[3]._id,
+                &CheckFields {
+                    current_state: Some(CheckState::Down),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let find = |filter: CheckFilter| {
+            let store = store.clone();
+            async move {
+                let filter = CheckFilter {
+                    project_id: Some(project_id),
+                    ..filter
+                };
+                let mut ids =
+                    page_through(&store, &filter, &CheckSortField::CreatedAt, SortOrder::Asc).await;
+                ids.sort();
+                ids
+            }
+        };
+        let ids = |indexes: &[usize]| {
+            let mut ids: Vec<_> = indexes.iter().map(|index| checks[*index]._id).collect();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(find(CheckFilter::default()).await, ids(&[0, 1, 2, 3]));
+        let method = CheckFilter {
+            method: Some(HTTPMethod::POST),
+            ..Default::default()
+        };
+        assert_eq!(find(method).await, ids(&[1]));
+        let interval = CheckFilter {
+            frequency: Some(FrequencyKind::Interval),
+            ..Default::default()
+        };
+        assert_eq!(find(interval).await, ids(&[0, 1]));
+        let hourly = CheckFilter {
+            frequency: Some(FrequencyKind::Hourly),
+            ..Default::default()
+        };
+        assert_eq!(find(hourly).await, ids(&[2]));
+        let cron = CheckFilter {
+            frequency: Some(FrequencyKind::Cron),
+            ..Default::default()
+        };
+        assert_eq!(find(cron).await, ids(&[3]));
+        let state = CheckFilter {
+            current_state: Some(CheckState::Down),
+            ..Default::default()
+        };
+        assert_eq!(find(state).await, ids(&[3]));
+        let tags = CheckFilter {
+            tags: vec!["web".to_string(), "prod".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(find(tags).await, ids(&[0]));
+        let search = CheckFilter {
+            search: Some("HOME".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(find(search).await, ids(&[0]));
+        let search = CheckFilter {
+            search: Some("api.".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(find(search).await, ids(&[1]));
+    }
+
+    #[tokio::test]
+    async fn pages_checks_without_skipping_or_repeating_them() {
+        let store = store();
+        let project_id = ObjectId::new();
+        let mut checks = vec![];
+        for index in 0..5 {
+            let mut check = new_check(project_id, json!({}));
+            // Checks share their creation date two by two, and some never ran
+            check.created_at = at(index / 2);
+            check.last_run_at = (index % 2 == 0).then(|| at(100 - index));
+            store.insert_check(&check).await.unwrap();
+            checks.push(check);
+        }
+        let filter = CheckFilter::default();
+
+        for sort in [CheckSortField::CreatedAt, CheckSortField::LastRun] {
+            let mut expected = checks.clone();
+            expected.sort_by_key(|check| (sort.value(check), check._id));
+            let expected: Vec<_> = expected.iter().map(|check| check._id).collect();
+
+            let ascending = page_through(&store, &filter, &sort, SortOrder::Asc).await;
+            assert_eq!(ascending, expected);
+            let mut descending = page_through(&store, &filter, &sort, SortOrder::Desc).await;
+            descending.reverse();
+            assert_eq!(descending, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn pages_history_within_the_range() {
+        let store = store();
+        let check_id = ObjectId::new();
+        for index in 0..6 {
+            let mut entry = CheckHistory::new(check_id, vec![]);
+            entry.created_at = at(index / 2);
+            entry.status = if index == 3 {
+                Status::Ok
+            } else {
+                Status::Error
+            };
+            store.insert_history(&entry).await.unwrap();
+        }
+        store
+            .insert_history(&CheckHistory::new(ObjectId::new(), vec![]))
+            .await
+            .unwrap();
+
+        let filter = HistoryFilter {
+            check_id,
+            from: Some(at(1)),
+            to: Some(at(3)),
+            status: None,
+        };
+        let mut request = PageRequest {
+            order: SortOrder::Desc,
+            limit: 3,
+            cursor: None,
+        };
+        let first = store.find_history(&filter, &request).await.unwrap();
+        request.cursor = first
+            .next_cursor
+            .as_deref()
+            .map(|c| Cursor::decode(c).unwrap());
+        let second = store.find_history(&filter, &request).await.unwrap();
+        assert_eq!((first.items.len(), second.items.len()), (3, 1));
+        assert!(second.next_cursor.is_none());
+        let dates: Vec<_> = first
+            .items
+            .iter()
+            .chain(&second.items)
+            .map(|entry| entry.created_at)
+            .collect();
+        assert_eq!(dates, [at(2), at(2), at(1), at(1)]);
+
+        let ok = HistoryFilter {
+            status: Some(Status::Ok),
+            ..filter
+        };
+        request.cursor = None;
+        let page = store.find_history(&ok, &request).await.unwrap();
+        assert_eq!(page.items.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn deleting_a_user_revokes_their_keys() {
+        let store = store();
+        let project_id = ObjectId::new();
+        let user = User::from_new(
+            NewUser {
+                name: "Ada".to_string(),
+                email: "ada@example.com".to_string(),
+                scope: ApiKeyScope::Write,
+                project_id: None,
+            },
+            project_id,
+        );
+        store.insert_user(&user).await.unwrap();
+        let (theirs, _) = ApiKey::mint(
+            project_id,
+            "ci".to_string(),
+            ApiKeyScope::Read,
+            Some(user._id),
+        );
+        let (other, _) = ApiKey::mint(project_id, "ops".to_string(), ApiKeyScope::Read, None);
+        store.insert_api_key(&theirs).await.unwrap();
+        store.insert_api_key(&other).await.unwrap();
+
+        let users = store.users(Some(project_id)).await.unwrap();
+        assert_eq!(users.len(), 1);
+        assert!(store.users(Some(ObjectId::new())).await.unwrap().is_empty());
+
+        assert!(store.delete_user(user._id).await.unwrap());
+        assert!(store.user(user._id).await.unwrap().is_none());
+        let theirs = store.api_key(theirs._id).await.unwrap().unwrap();
+        assert!(theirs.revoked_at.is_some());
+        let other = store.api_key(other._id).await.unwrap().unwrap();
+        assert!(other.revoked_at.is_none());
+        assert!(!store.delete_user(user._id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn databases_without_check_dates_get_them() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE checks (id TEXT PRIMARY KEY, token TEXT UNIQUE, doc TEXT NOT NULL);",
+            )
+            .unwrap();
+        let mut check = new_check(ObjectId::new(), json!({}));
+        check.last_run_at = Some(at(5));
+        connection
+            .execute(
+                "INSERT INTO checks (id, doc) VALUES (?, ?)",
+                [check._id.to_hex(), serde_json::to_string(&check).unwrap()],
+            )
+            .unwrap();
+
+        let store = SqliteStore::init(connection).unwrap();
+        let request = PageRequest {
+            order: SortOrder::Desc,
+            limit: 10,
+            cursor: Some(Cursor::new(Some(at(6)), ObjectId::new())),
+        };
+        let page = store
+            .find_checks(&CheckFilter::default(), &CheckSortField::LastRun, &request)
+            .await
+            .unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].last_run_at, Some(at(5)));
+    }
+}
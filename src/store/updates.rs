@@ -0,0 +1,184 @@
+//! Fields updates change, the ones left as `None` keep their value.
+//!
+//! Fields that may be missing are cleared with `Some(None)`. Backends set what
+//! is given the way MongoDB's `$set` does, as serialized by `bson`.
+
+use std::collections::HashMap;
+
+use bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::models::{
+    ApiKeyScope, Assertion, CertificatePolicy, ChannelConfig, CheckKind, CheckState, CronFrequency,
+    DnsOptions, Frequency, HTTPMethod, Heartbeat, MaintenanceMode, ProjectQuota, RequestBody,
+    RetryPolicy, StatusBranding, StatusComponent,
+};
+
+#[derive(Serialize, Default)]
+pub(crate) struct CheckFields {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) kind: Option<CheckKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) name: Option<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) tags: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) frequency: Option<Frequency>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) method: Option<HTTPMethod>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) headers: Option<Option<HashMap<String, String>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) query: Option<Option<HashMap<String, String>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) body: Option<Option<RequestBody>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) expected_body: Option<Option<serde_json::Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) dns: Option<Option<DnsOptions>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) heartbeat: Option<Option<Heartbeat>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) badge_token: Option<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) grace_secs: Option<Option<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) assertions: Option<Vec<Assertion>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) channels: Option<Vec<ObjectId>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) timeout_ms: Option<Option<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) retry: Option<Option<RetryPolicy>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) failure_threshold: Option<Option<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) recovery_threshold: Option<Option<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) sla_target: Option<Option<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) certificate_policy: Option<Option<CertificatePolicy>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) certificate_expiry_days: Option<Option<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) current_state: Option<CheckState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) consecutive_failures: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) consecutive_successes: Option<u32>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::timestamps::optional::serialize"
+    )]
+    pub(crate) last_run_at: Option<DateTime<Utc>>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::timestamps::optional::serialize"
+    )]
+    pub(crate) updated_at: Option<DateTime<Utc>>,
+}
+
+impl CheckFields {
+    /// Whether the update changes what the check's probe is given
+    pub(crate) fn changes_target(&self) -> bool {
+        self.kind.is_some()
+            || self.method.is_some()
+            || self.url.is_some()
+            || self.dns.is_some()
+            || self.headers.is_some()
+            || self.query.is_some()
+            || self.body.is_some()
+            || self.expected_body.is_some()
+            || self.assertions.is_some()
+    }
+}
+
+#[derive(Serialize, Default)]
+pub(crate) struct MaintenanceWindowFields {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) name: Option<String>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::timestamps::optional::serialize"
+    )]
+    pub(crate) starts_at: Option<DateTime<Utc>>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::timestamps::nullable::serialize"
+    )]
+    pub(crate) ends_at: Option<Option<DateTime<Utc>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) recurrence: Option<Option<CronFrequency>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) duration_secs: Option<Option<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) checks: Option<Vec<ObjectId>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) tags: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) mode: Option<MaintenanceMode>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::timestamps::optional::serialize"
+    )]
+    pub(crate) updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Default)]
+pub(crate) struct ChannelFields {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) config: Option<ChannelConfig>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::timestamps::optional::serialize"
+    )]
+    pub(crate) updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Default)]
+pub(crate) struct ApiKeyFields {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) scope: Option<ApiKeyScope>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::timestamps::optional::serialize"
+    )]
+    pub(crate) revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Default)]
+pub(crate) struct ProjectFields {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) quota: Option<ProjectQuota>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::timestamps::optional::serialize"
+    )]
+    pub(crate) updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Default)]
+pub(crate) struct StatusPageFields {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) slug: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) description: Option<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) components: Option<Vec<StatusComponent>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) branding: Option<StatusBranding>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::timestamps::optional::serialize"
+    )]
+    pub(crate) updated_at: Option<DateTime<Utc>>,
+}
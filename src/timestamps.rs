@@ -0,0 +1,161 @@
+//! Dates are stored as BSON dates, the only kind MongoDB sorts, compares and expires as dates.
+//!
+//! They keep milliseconds. Human readable formats such as the JSON of webhooks get RFC 3339
+//! strings instead, see `store::to_document`. Dates stored as RFC 3339 strings before are still
+//! read.
+
+use bson::Bson;
+use chrono::{DateTime, Utc};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+fn to_bson_date(at: DateTime<Utc>) -> bson::DateTime {
+    bson::DateTime::from_millis(at.timestamp_millis())
+}
+
+pub(crate) fn serialize<S: Serializer>(
+    at: &DateTime<Utc>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        at.serialize(serializer)
+    } else {
+        to_bson_date(*at).serialize(serializer)
+    }
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<DateTime<Utc>, D::Error> {
+    from_bson(Bson::deserialize(deserializer)?).map_err(D::Error::custom)
+}
+
+fn from_bson(value: Bson) -> Result<DateTime<Utc>, String> {
+    match value {
+        Bson::DateTime(at) => DateTime::from_timestamp_millis(at.timestamp_millis())
+            .ok_or_else(|| format!("Date out of range: {at}")),
+        Bson::String(at) => DateTime::parse_from_rfc3339(&at)
+            .map(|at| at.with_timezone(&Utc))
+            .map_err(|err| format!("Invalid date '{at}': {err}")),
+        value => Err(format!("Expected a date, found {value}")),
+    }
+}
+
+/// Value to store or compare a date with, in filters and updates
+pub(crate) fn to_bson(at: DateTime<Utc>) -> Bson {
+    Bson::DateTime(to_bson_date(at))
+}
+
+pub(crate) mod optional {
+    use bson::Bson;
+    use chrono::{DateTime, Utc};
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(
+        at: &Option<DateTime<Utc>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match at {
+            Some(at) => serializer.serialize_some(&Date(*at)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// A date written the way `timestamps::serialize` writes it
+    struct Date(DateTime<Utc>);
+
+    impl Serialize for Date {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            super::serialize(&self.0, serializer)
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<Utc>>, D::Error> {
+        match Option::<Bson>::deserialize(deserializer)? {
+            None | Some(Bson::Null) => Ok(None),
+            Some(value) => super::from_bson(value).map(Some).map_err(D::Error::custom),
+        }
+    }
+}
+
+/// Dates of updates, where `Some(None)` clears the date
+pub(crate) mod nullable {
+    use chrono::{DateTime, Utc};
+    use serde::Serializer;
+
+    pub(crate) fn serialize<S: Serializer>(
+        at: &Option<Option<DateTime<Utc>>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        super::optional::serialize(&at.flatten(), serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{doc, Bson};
+    use chrono::{DateTime, TimeZone, Utc};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Dated {
+        #[serde(with = "crate::timestamps")]
+        at: DateTime<Utc>,
+        #[serde(default, with = "crate::timestamps::optional")]
+        until: Option<DateTime<Utc>>,
+    }
+
+    fn dated() -> Dated {
+        Dated {
+            at: Utc.timestamp_millis_opt(1_700_000_000_123).unwrap(),
+            until: None,
+        }
+    }
+
+    #[test]
+    fn stored_as_bson_dates() {
+        let document = crate::store::to_document(&dated()).unwrap();
+        assert!(matches!(document.get("at"), Some(Bson::DateTime(_))));
+        assert_eq!(document.get("until"), Some(&Bson::Null));
+
+        // The driver writes documents with the raw serializer
+        let raw = bson::to_raw_document_buf(&dated()).unwrap();
+        assert!(raw.get_datetime("at").is_ok());
+        assert_eq!(bson::from_slice::<Dated>(raw.as_bytes()).unwrap(), dated());
+    }
+
+    #[test]
+    fn written_as_rfc3339_in_json() {
+        let dated = Dated {
+            until: Some(Utc.timestamp_millis_opt(1_700_000_060_000).unwrap()),
+            ..dated()
+        };
+        let json = serde_json::to_value(&dated).unwrap();
+        assert_eq!(json["at"], "2023-11-14T22:13:20.123Z");
+        assert_eq!(json["until"], "2023-11-14T22:14:20Z");
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let json = serde_json::to_string(&dated()).unwrap();
+        assert_eq!(serde_json::from_str::<Dated>(&json).unwrap(), dated());
+    }
+
+    #[test]
+    fn reads_dates_stored_as_strings() {
+        let document =
+            doc! {"at": "2023-11-14T22:13:20.123456789Z", "until": "2023-11-15T00:00:00+00:00"};
+        let read: Dated = bson::from_document(document).unwrap();
+        assert_eq!(
+            read.at.timestamp_nanos_opt(),
+            Some(1_700_000_000_123_456_789)
+        );
+        assert!(read.until.is_some());
+
+        let json = r#"{"at": "2023-11-14T22:13:20.123Z"}"#;
+        assert_eq!(serde_json::from_str::<Dated>(json).unwrap(), dated());
+    }
+}